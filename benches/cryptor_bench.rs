@@ -1,5 +1,5 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use crypt_ro::Cryptor;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use crypt_ro::{Cryptor, FixedCryptor, Key};
 use rsa::{RsaPrivateKey, RsaPublicKey, pkcs1v15::Pkcs1v15Encrypt};
 use rand_core::OsRng;
 
@@ -14,7 +14,7 @@ fn setup_cryptor() -> Cryptor {
 
 
 fn bench_cryptor_encrypt(c: &mut Criterion) {
-    let mut cryptor = setup_cryptor();
+    let cryptor = setup_cryptor();
     let text = "a".repeat(SIZE);
     let text = text.as_bytes();
     let key = "strong-password-123";
@@ -25,7 +25,7 @@ fn bench_cryptor_encrypt(c: &mut Criterion) {
 }
 
 fn bench_cryptor_decrypt(c: &mut Criterion) {
-    let mut cryptor = setup_cryptor();
+    let cryptor = setup_cryptor();
     let text = "a".repeat(SIZE);
     let text = text.as_bytes();
     let key = "strong-password-123";
@@ -36,8 +36,60 @@ fn bench_cryptor_decrypt(c: &mut Criterion) {
     });
 }
 
+fn bench_fixed_vs_dynamic(c: &mut Criterion) {
+    let dynamic = setup_cryptor();
+    let fixed = FixedCryptor::<32>::new();
+    let key = "strong-password-123";
+    let mut group = c.benchmark_group("Fixed vs Dynamic");
+
+    for size in [SIZE, 1024, 64 * 1024] {
+        let text = vec![b'a'; size];
+        let encrypted = dynamic.encrypt(&text, key).unwrap();
+
+        group.bench_function(format!("Cryptor Encrypt {size}B"), |b| {
+            b.iter(|| dynamic.encrypt(black_box(&text), black_box(key)).unwrap())
+        });
+        group.bench_function(format!("FixedCryptor<32> Encrypt {size}B"), |b| {
+            b.iter(|| fixed.encrypt(black_box(&text), black_box(key)).unwrap())
+        });
+        group.bench_function(format!("Cryptor Decrypt {size}B"), |b| {
+            b.iter(|| dynamic.decrypt(black_box(&encrypted), black_box(key)).unwrap())
+        });
+        group.bench_function(format!("FixedCryptor<32> Decrypt {size}B"), |b| {
+            b.iter(|| fixed.decrypt(black_box(&encrypted), black_box(key)).unwrap())
+        });
+    }
+    group.finish();
+}
 
 
+fn bench_prepared_key(c: &mut Criterion) {
+    let cryptor = setup_cryptor();
+    let password = "strong-password-123";
+    let key = Key::new(password);
+    let mut group = c.benchmark_group("Prepared Key");
+
+    for size in [50, 1024, 64 * 1024] {
+        let text = vec![b'a'; size];
+        // Every message has its own random prefix, so decrypt a fresh token each time
+        let fresh_token = || cryptor.encrypt(&text, password).unwrap();
+
+        group.bench_function(format!("Encrypt str {size}B"), |b| {
+            b.iter(|| cryptor.encrypt(black_box(&text), black_box(password)).unwrap())
+        });
+        group.bench_function(format!("Encrypt Key {size}B"), |b| {
+            b.iter(|| cryptor.encrypt(black_box(&text), black_box(&key)).unwrap())
+        });
+        group.bench_function(format!("Decrypt str {size}B"), |b| {
+            b.iter_batched(fresh_token, |encrypted| cryptor.decrypt(black_box(&encrypted), black_box(password)).unwrap(), BatchSize::SmallInput)
+        });
+        group.bench_function(format!("Decrypt Key {size}B"), |b| {
+            b.iter_batched(fresh_token, |encrypted| cryptor.decrypt(black_box(&encrypted), black_box(&key)).unwrap(), BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
 fn setup_aes_gcm() -> (Aes256Gcm, Vec<u8>) {
     let key = Aes256Gcm::generate_key(OsRng);
 
@@ -113,6 +165,8 @@ criterion_group!(
     benches,
     bench_cryptor_encrypt,
     bench_cryptor_decrypt,
+    bench_fixed_vs_dynamic,
+    bench_prepared_key,
     bench_aes_gcm_encrypt,
     bench_aes_gcm_decrypt,
     bench_rsa_encrypt,
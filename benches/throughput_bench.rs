@@ -0,0 +1,72 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crypt_ro::{Cryptor, Preset};
+
+const SIZES: [usize; 6] = [1 << 10, 16 << 10, 256 << 10, 1 << 20, 16 << 20, 64 << 20];
+
+fn bench_cryptor(c: &mut Criterion) {
+    let cryptor = Cryptor::new();
+    let key = "strong-password-123";
+    let mut group = c.benchmark_group("Cryptor Throughput");
+    group.sample_size(10);
+
+    for size in SIZES {
+        let data = vec![b'a'; size];
+        let encrypted = cryptor.encrypt(&data, key).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("Encrypt", size), &data, |b, data| {
+            b.iter(|| cryptor.encrypt(black_box(data), black_box(key)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("Decrypt", size), &encrypted, |b, encrypted| {
+            b.iter(|| cryptor.decrypt(black_box(encrypted), black_box(key)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_presets(c: &mut Criterion) {
+    let key = "strong-password-123";
+    let size = 256 << 10;
+    let data = vec![b'a'; size];
+    let mut group = c.benchmark_group("Round Presets");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(size as u64));
+
+    for preset in [Preset::Fast, Preset::Balanced, Preset::Paranoid] {
+        let cryptor = Cryptor::builder().preset(preset).build().unwrap();
+        let encrypted = cryptor.encrypt(&data, key).unwrap();
+        group.bench_with_input(BenchmarkId::new("Encrypt", format!("{preset:?}")), &data, |b, data| {
+            b.iter(|| cryptor.encrypt(black_box(data), black_box(key)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("Decrypt", format!("{preset:?}")), &encrypted, |b, encrypted| {
+            b.iter(|| cryptor.decrypt(black_box(encrypted), black_box(key)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "parallel")]
+fn bench_parallel(c: &mut Criterion) {
+    let cryptor = Cryptor::new();
+    let key = "strong-password-123";
+    let mut group = c.benchmark_group("Parallel Throughput");
+    group.sample_size(10);
+
+    for size in SIZES.into_iter().filter(|&size| size >= 1 << 20) {
+        let data = vec![b'a'; size];
+        let encrypted = cryptor.encrypt_parallel(&data, key).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("Encrypt", size), &data, |b, data| {
+            b.iter(|| cryptor.encrypt_parallel(black_box(data), black_box(key)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("Decrypt", size), &encrypted, |b, encrypted| {
+            b.iter(|| cryptor.decrypt_parallel(black_box(encrypted), black_box(key)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "parallel"))]
+fn bench_parallel(_: &mut Criterion) {}
+
+criterion_group!(benches, bench_cryptor, bench_presets, bench_parallel);
+criterion_main!(benches);
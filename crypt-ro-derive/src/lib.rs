@@ -0,0 +1,89 @@
+//! Derive macro for `crypt_ro::EncryptFields`.
+//!
+//! Use it through the `derive` feature of `crypt-ro`, which re-exports the macro next to
+//! the trait:
+//!
+//! ```ignore
+//! use crypt_ro::EncryptFields;
+//!
+//! #[derive(EncryptFields)]
+//! struct Customer {
+//!     name: String,
+//!     #[encrypt]
+//!     phone: String,
+//!     #[encrypt]
+//!     address: Option<Address>,
+//! }
+//! ```
+//!
+//! Every field marked `#[encrypt]` must implement `EncryptFields` itself, which
+//! `String`, `Vec<u8>`, `Option`s of them and other derived structs do.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index, Meta};
+
+/// Implements `crypt_ro::EncryptFields` by encrypting every field marked `#[encrypt]`,
+/// turning the earlier fields back if a later one fails.
+#[proc_macro_derive(EncryptFields, attributes(encrypt))]
+pub fn derive_encrypt_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "EncryptFields can only be derived for structs"));
+    };
+    let fields = encrypted_fields(&data.fields)?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::crypt_ro::EncryptFields for #name #ty_generics #where_clause {
+            fn encrypt_fields(
+                &mut self,
+                cryptor: &::crypt_ro::Cryptor,
+                key: &::crypt_ro::Key,
+            ) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                ::crypt_ro::encrypt_each(&mut [#( &mut self.#fields as &mut dyn ::crypt_ro::EncryptFields ),*], cryptor, key)
+            }
+
+            fn decrypt_fields(
+                &mut self,
+                cryptor: &::crypt_ro::Cryptor,
+                key: &::crypt_ro::Key,
+            ) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                ::crypt_ro::decrypt_each(&mut [#( &mut self.#fields as &mut dyn ::crypt_ro::EncryptFields ),*], cryptor, key)
+            }
+        }
+    })
+}
+
+/// Returns the accessors of the fields marked `#[encrypt]`.
+fn encrypted_fields(fields: &Fields) -> syn::Result<Vec<TokenStream2>> {
+    let mut marked = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let mut encrypt = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("encrypt")) {
+            if !matches!(attr.meta, Meta::Path(_)) {
+                return Err(syn::Error::new_spanned(attr, "#[encrypt] takes no arguments"));
+            }
+            if encrypt {
+                return Err(syn::Error::new_spanned(attr, "duplicate #[encrypt] attribute"));
+            }
+            encrypt = true;
+        }
+        if encrypt {
+            marked.push(match &field.ident {
+                Some(ident) => quote!(#ident),
+                None => {
+                    let index = Index::from(i);
+                    quote!(#index)
+                }
+            });
+        }
+    }
+    Ok(marked)
+}
@@ -0,0 +1,102 @@
+use std::error::Error;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use hmac::Mac;
+use crate::backend::{envelope_header, open_envelope, seal_envelope, Algorithm, Decryptor, Encryptor};
+use crate::commit::derived_mac;
+use crate::error::DecryptError;
+use crate::kdf::Kdf;
+use crate::key::Key;
+use crate::rand::random_bytes;
+
+/// Bytes of the random nonce starting an AES-256-GCM payload.
+const NONCE_LEN: usize = 12;
+
+/// Derives the 32-byte cipher key for the purpose named by `domain`.
+pub(crate) fn aead_key(key: &Key, kdf: Kdf, salt: &[u8], domain: &[u8]) -> [u8; 32] {
+    derived_mac(key, kdf, salt, 32, domain).finalize().into_bytes().into()
+}
+
+/// AES-256-GCM backend for the [`Encryptor`] and [`Decryptor`] traits.
+///
+/// The cipher key is derived from the [`Key`] with the configured [`Kdf`], so the same
+/// keys can be used with [`Cryptor`](crate::Cryptor). The payload is a random 12-byte
+/// nonce followed by the ciphertext and tag, preceded by a random 16-byte salt with
+/// [`Kdf::Pbkdf2`]; the envelope header is authenticated as associated data.
+///
+/// # Example
+/// ```
+/// use crypt_ro::{AesGcmCryptor, Decryptor, Encryptor, Key};
+///
+/// let key = Key::new("tenant key");
+/// let cipher = AesGcmCryptor::new();
+/// let envelope = cipher.encrypt_bytes(b"data", &key).unwrap();
+/// assert_eq!(cipher.decrypt_bytes(&envelope, &key).unwrap(), b"data");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AesGcmCryptor {
+    kdf: Kdf,
+}
+
+impl AesGcmCryptor {
+    /// Creates a backend deriving its key with [`Kdf::Sha256`].
+    pub fn new() -> Self {
+        Self { kdf: Kdf::Sha256 }
+    }
+
+    /// Creates a backend deriving its key with `kdf`, e.g. [`Kdf::Pbkdf2`] for passwords.
+    pub fn with_kdf(kdf: Kdf) -> Self {
+        Self { kdf }
+    }
+
+    /// Returns the key derivation function.
+    pub fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+
+    fn cipher(&self, key: &Key, salt: &[u8]) -> Aes256Gcm {
+        Aes256Gcm::new(&aead_key(key, self.kdf, salt, b"crypt-ro aes-256-gcm").into())
+    }
+}
+
+impl Default for AesGcmCryptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encryptor for AesGcmCryptor {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::Aes256Gcm
+    }
+
+    fn encrypt_bytes(&self, data: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        let salt = self.kdf.new_salt()?;
+        let salt = salt.as_ref().map_or(&[][..], |salt| salt);
+        let nonce = random_bytes(NONCE_LEN)?;
+        let aad = envelope_header(Algorithm::Aes256Gcm);
+        let ciphertext = self.cipher(key, salt)
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: data, aad: &aad })
+            .map_err(|_| "Encryption Failed")?;
+        let mut payload = salt.to_vec();
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&ciphertext);
+        Ok(seal_envelope(Algorithm::Aes256Gcm, &payload))
+    }
+}
+
+impl Decryptor for AesGcmCryptor {
+    fn decrypt_bytes(&self, envelope: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        let payload = open_envelope(Algorithm::Aes256Gcm, envelope)?;
+        let salt_len = self.kdf.salt_len();
+        if payload.len() < salt_len + NONCE_LEN {
+            return Err("Invalid Envelope".into());
+        }
+        let (salt, payload) = payload.split_at(salt_len);
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let aad = envelope_header(Algorithm::Aes256Gcm);
+        Ok(self.cipher(key, salt)
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| DecryptError::AuthenticationFailed)?)
+    }
+}
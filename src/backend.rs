@@ -0,0 +1,169 @@
+use std::error::Error;
+use std::fmt;
+use crate::encoding::Encoding;
+use crate::key::Key;
+use crate::Cryptor;
+
+/// Marks an envelope written by an [`Encryptor`].
+pub const ENVELOPE_MAGIC: [u8; 3] = *b"CRE";
+
+/// Bytes before the payload of an envelope: the magic and the algorithm.
+pub(crate) const ENVELOPE_HEADER_LEN: usize = ENVELOPE_MAGIC.len() + 1;
+
+/// Text encoding of envelopes, shared by every backend.
+const ENVELOPE_ENCODING: Encoding = Encoding::Base64UrlNoPad;
+
+/// Cipher that produced an envelope.
+///
+/// # Example
+/// ```
+/// use crypt_ro::{Algorithm, Cryptor, Encryptor, Key};
+///
+/// let envelope = Cryptor::new().encrypt_bytes(b"data", &Key::new("key")).unwrap();
+/// assert_eq!(Algorithm::detect(&envelope).unwrap(), Algorithm::CryptRo);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Algorithm {
+    /// A [`Cryptor`] token.
+    CryptRo,
+    /// AES-256-GCM, see `AesGcmCryptor` (feature `aes-gcm`).
+    Aes256Gcm,
+}
+
+impl Algorithm {
+    fn id(self) -> u8 {
+        match self {
+            Algorithm::CryptRo => 1,
+            Algorithm::Aes256Gcm => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Algorithm::CryptRo),
+            2 => Some(Algorithm::Aes256Gcm),
+            _ => None,
+        }
+    }
+
+    /// Returns the algorithm of an envelope without decrypting it.
+    pub fn detect(envelope: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if envelope.len() < ENVELOPE_HEADER_LEN || !envelope.starts_with(&ENVELOPE_MAGIC) {
+            return Err("Invalid Envelope".into());
+        }
+        Self::from_id(envelope[ENVELOPE_MAGIC.len()])
+            .ok_or_else(|| format!("Unknown Algorithm {}", envelope[ENVELOPE_MAGIC.len()]).into())
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::CryptRo => "crypt-ro",
+            Algorithm::Aes256Gcm => "AES-256-GCM",
+        })
+    }
+}
+
+/// Returns the envelope header for `algorithm`, which AEAD backends authenticate.
+pub(crate) fn envelope_header(algorithm: Algorithm) -> [u8; ENVELOPE_HEADER_LEN] {
+    let [a, b, c] = ENVELOPE_MAGIC;
+    [a, b, c, algorithm.id()]
+}
+
+/// Wraps `payload` in an envelope.
+pub(crate) fn seal_envelope(algorithm: Algorithm, payload: &[u8]) -> Vec<u8> {
+    let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LEN + payload.len());
+    envelope.extend_from_slice(&envelope_header(algorithm));
+    envelope.extend_from_slice(payload);
+    envelope
+}
+
+/// Returns the payload of an envelope written by `algorithm`.
+pub(crate) fn open_envelope(algorithm: Algorithm, envelope: &[u8]) -> Result<&[u8], Box<dyn Error>> {
+    let found = Algorithm::detect(envelope)?;
+    if found != algorithm {
+        return Err(format!("Algorithm Mismatch: expected {algorithm}, found {found}").into());
+    }
+    Ok(&envelope[ENVELOPE_HEADER_LEN..])
+}
+
+/// Encrypts data into envelopes.
+///
+/// Application code written against `dyn Encryptor` and [`Decryptor`] does not depend
+/// on the cipher, so it can be chosen per tenant or replaced by a mock in tests. Every
+/// backend writes the same envelope: [`ENVELOPE_MAGIC`], one byte naming the
+/// [`Algorithm`], then the backend's payload.
+///
+/// # Example
+/// ```
+/// use crypt_ro::{Cryptor, Decryptor, Encryptor, Key};
+///
+/// fn backend(_tenant: &str) -> Box<dyn Cipher> {
+///     Box::new(Cryptor::new())
+/// }
+/// trait Cipher: Encryptor + Decryptor {}
+/// impl<T: Encryptor + Decryptor> Cipher for T {}
+///
+/// let key = Key::new("tenant key");
+/// let cipher = backend("acme");
+/// let token = cipher.encrypt_str("secret", &key).unwrap();
+/// assert_eq!(cipher.decrypt_str(&token, &key).unwrap(), "secret");
+/// ```
+pub trait Encryptor {
+    /// Returns the algorithm written into envelopes.
+    fn algorithm(&self) -> Algorithm;
+
+    /// Encrypts bytes into an envelope.
+    fn encrypt_bytes(&self, data: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Encrypts text into an envelope encoded as URL-safe base64 without padding.
+    fn encrypt_str(&self, text: &str, key: &Key) -> Result<String, Box<dyn Error>> {
+        Ok(ENVELOPE_ENCODING.encode(&self.encrypt_bytes(text.as_bytes(), key)?))
+    }
+}
+
+/// Decrypts envelopes written by the matching [`Encryptor`].
+pub trait Decryptor {
+    /// Decrypts an envelope. Envelopes of another [`Algorithm`] are rejected.
+    fn decrypt_bytes(&self, envelope: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Decrypts text written by [`Encryptor::encrypt_str`].
+    fn decrypt_str(&self, text: &str, key: &Key) -> Result<String, Box<dyn Error>> {
+        Ok(String::from_utf8(self.decrypt_bytes(&ENVELOPE_ENCODING.decode(text)?, key)?)?)
+    }
+}
+
+impl<T: Encryptor + ?Sized> Encryptor for &T {
+    fn algorithm(&self) -> Algorithm {
+        (**self).algorithm()
+    }
+
+    fn encrypt_bytes(&self, data: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        (**self).encrypt_bytes(data, key)
+    }
+}
+
+impl<T: Decryptor + ?Sized> Decryptor for &T {
+    fn decrypt_bytes(&self, envelope: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        (**self).decrypt_bytes(envelope, key)
+    }
+}
+
+/// The payload is a token from [`Cryptor::encrypt`].
+impl Encryptor for Cryptor {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::CryptRo
+    }
+
+    fn encrypt_bytes(&self, data: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(seal_envelope(Algorithm::CryptRo, &self.encrypt(data, key)?))
+    }
+}
+
+impl Decryptor for Cryptor {
+    fn decrypt_bytes(&self, envelope: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.decrypt(open_envelope(Algorithm::CryptRo, envelope)?, key)
+    }
+}
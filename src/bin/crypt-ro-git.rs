@@ -0,0 +1,137 @@
+//! Git clean/smudge filter storing files as seekable crypt-ro containers.
+//!
+//! ```text
+//! crypt-ro-git clean [FILE]     encrypt stdin to stdout when staging
+//! crypt-ro-git smudge [FILE]    decrypt stdin to stdout when checking out
+//! crypt-ro-git textconv FILE    print the plaintext of FILE for `git diff`
+//! ```
+//!
+//! `clean` is deterministic, so staging an unchanged file yields the same blob and git
+//! sees no modification. The key is read from the `CRYPT_RO_KEY` environment variable,
+//! or from the file named by `CRYPT_RO_KEY_FILE`.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::process::ExitCode;
+use crypt_ro::{encrypt_deterministic, Cryptor, Kdf, Key, SeekableDecryptReader, TokenLayout, DEFAULT_CHUNK_SIZE};
+
+const USAGE: &str = "\
+Usage: crypt-ro-git <command> [FILE]
+
+Commands:
+  clean [FILE]      Encrypt stdin to stdout. Input that is already encrypted is
+                    passed through.
+  smudge [FILE]     Decrypt stdin to stdout. Input that is not encrypted is passed
+                    through.
+  textconv FILE     Print the decrypted content of FILE.
+  help              Show this message.
+
+FILE is the path git passes with %f and is only used in error messages. The key is
+read from CRYPT_RO_KEY or from the file named by CRYPT_RO_KEY_FILE.
+
+Setup:
+  git config filter.crypt-ro.clean 'crypt-ro-git clean %f'
+  git config filter.crypt-ro.smudge 'crypt-ro-git smudge %f'
+  git config filter.crypt-ro.required true
+  git config diff.crypt-ro.textconv 'crypt-ro-git textconv'
+  echo 'secrets/** filter=crypt-ro diff=crypt-ro' >> .gitattributes";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("clean") => clean(&args[1..]),
+        Some("smudge") => smudge(&args[1..]),
+        Some("textconv") => textconv(&args[1..]),
+        Some("help" | "-h" | "--help") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(format!("Unknown Command {command}\n\n{USAGE}").into()),
+        None => Err(USAGE.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("crypt-ro-git: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Configuration of the containers written by the filter.
+fn cryptor() -> Cryptor {
+    Cryptor::builder().kdf(Kdf::Sha256).build().expect("valid configuration")
+}
+
+/// Returns `true` if `data` is a raw seekable container.
+///
+/// `Cryptor::inspect` also accepts hex and base64 text, but the filter only ever writes raw
+/// bytes, so a text-encoded container in the working tree is plaintext to be encrypted.
+fn is_encrypted(data: &[u8]) -> bool {
+    Cryptor::inspect(data)
+        .is_ok_and(|info| info.encoding.is_none() && matches!(info.layout, TokenLayout::Seekable { .. }))
+}
+
+/// Checks the optional path argument and reads stdin.
+fn read_stdin(args: &[String]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if args.len() > 1 {
+        return Err(format!("Unexpected Argument {}\n\n{USAGE}", args[1]).into());
+    }
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input)?;
+    Ok(input)
+}
+
+fn decrypt(data: Vec<u8>, key: &Key) -> io::Result<Vec<u8>> {
+    let mut reader = SeekableDecryptReader::new(Cursor::new(data), key)?;
+    let mut plain = Vec::with_capacity(reader.len() as usize);
+    reader.read_to_end(&mut plain)?;
+    Ok(plain)
+}
+
+/// Wraps `e` with the path git passed, if any.
+fn with_path(args: &[String], e: io::Error) -> Box<dyn Error> {
+    match args.first() {
+        Some(path) => format!("{path}: {e}").into(),
+        None => e.into(),
+    }
+}
+
+fn clean(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let input = read_stdin(args)?;
+    let output = if is_encrypted(&input) {
+        input
+    } else {
+        let key = Key::from_env()?;
+        encrypt_deterministic(&cryptor(), Vec::new(), &key, DEFAULT_CHUNK_SIZE, &input)
+            .map_err(|e| with_path(args, e))?
+    };
+    io::stdout().lock().write_all(&output)?;
+    Ok(())
+}
+
+fn smudge(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let input = read_stdin(args)?;
+    let output = if is_encrypted(&input) {
+        decrypt(input, &Key::from_env()?).map_err(|e| with_path(args, e))?
+    } else {
+        input
+    };
+    io::stdout().lock().write_all(&output)?;
+    Ok(())
+}
+
+fn textconv(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [path] = args else {
+        return Err(format!("Expected One File\n\n{USAGE}").into());
+    };
+    let input = fs::read(path)?;
+    let output = if is_encrypted(&input) {
+        decrypt(input, &Key::from_env()?).map_err(|e| with_path(args, e))?
+    } else {
+        input
+    };
+    io::stdout().lock().write_all(&output)?;
+    Ok(())
+}
@@ -0,0 +1,297 @@
+//! Command line tool for crypt-ro tokens.
+//!
+//! ```text
+//! crypt-ro inspect [TOKEN]    describe a token read from the argument or stdin
+//! crypt-ro encrypt FILE       encrypt the values of a JSON, TOML or YAML file
+//! crypt-ro decrypt FILE       decrypt a file written by `encrypt`
+//! crypt-ro edit FILE          edit an encrypted file in $EDITOR
+//! crypt-ro encrypt-env FILE   encrypt the values of a .env file in place
+//! ```
+//!
+//! `encrypt`, `decrypt` and `edit` need the `config` feature. Commands working on files
+//! read the key from the `CRYPT_RO_KEY` environment variable, or from the file named by
+//! `CRYPT_RO_KEY_FILE`, and stretch it with PBKDF2 (`CRYPT_RO_PBKDF2_ITERATIONS`
+//! iterations, 100 000 by default).
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use crypt_ro::{Cryptor, Kdf, Key};
+
+const USAGE: &str = "\
+Usage: crypt-ro <command> [arguments]
+
+Commands:
+  inspect [TOKEN]    Describe a token without decrypting it. Reads stdin when TOKEN
+                     is omitted or '-'.
+  encrypt FILE       Print FILE (.json, .toml, .yaml) with its values encrypted.
+  decrypt FILE       Print an encrypted FILE with its values decrypted.
+  edit FILE          Decrypt FILE into a private temporary directory, open it in
+                     $EDITOR and encrypt it again when it was changed. The copy is
+                     removed afterwards, unless crypt-ro is killed meanwhile.
+  encrypt-env FILE   Encrypt the plain values of a .env file in place as ENC[...].
+  help               Show this message.
+
+encrypt, decrypt and edit need the `config` feature. Commands working on files read
+the key from CRYPT_RO_KEY or from the file named by CRYPT_RO_KEY_FILE, and stretch it
+with PBKDF2 using CRYPT_RO_PBKDF2_ITERATIONS iterations (default 100000). decrypt and
+edit need the iteration count the file was encrypted with.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("inspect") => inspect(&args[1..]),
+        #[cfg(feature = "config")]
+        Some("encrypt") => config::encrypt(&args[1..]),
+        #[cfg(feature = "config")]
+        Some("decrypt") => config::decrypt(&args[1..]),
+        #[cfg(feature = "config")]
+        Some("edit") => config::edit(&args[1..]),
+        Some("encrypt-env") => encrypt_env(&args[1..]),
+        Some("help" | "-h" | "--help") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(format!("Unknown Command {command}\n\n{USAGE}").into()),
+        None => Err(USAGE.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads the argument, or stdin if it is absent or `-`.
+fn read_input(arg: Option<&String>) -> io::Result<Vec<u8>> {
+    match arg.map(String::as_str) {
+        Some("-") | None => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+        Some(arg) => Ok(arg.as_bytes().to_vec()),
+    }
+}
+
+fn inspect(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() > 1 {
+        return Err(format!("Unexpected Argument {}\n\n{USAGE}", args[1]).into());
+    }
+    let token = read_input(args.first())?;
+    println!("{}", Cryptor::inspect(&token)?);
+    Ok(())
+}
+
+/// Environment variable overriding [`DEFAULT_PBKDF2_ITERATIONS`].
+const ITERATIONS_ENV_VAR: &str = "CRYPT_RO_PBKDF2_ITERATIONS";
+
+/// PBKDF2 iterations for the keys of the CLI, which are often typed-in passwords.
+const DEFAULT_PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Configuration of the tokens in files written by the CLI.
+///
+/// Keys are stretched with PBKDF2 since they are often passwords. Every token gets its own
+/// salt, so the cost is paid per encrypted value.
+fn cryptor() -> Result<Cryptor, Box<dyn Error>> {
+    let iterations = match std::env::var(ITERATIONS_ENV_VAR) {
+        Ok(value) => value.parse().map_err(|e| format!("Invalid {ITERATIONS_ENV_VAR}: {e}"))?,
+        Err(_) => DEFAULT_PBKDF2_ITERATIONS,
+    };
+    Ok(Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations }).build()?)
+}
+
+fn encrypt_env(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [path] = args else {
+        return Err(format!("Expected One File\n\n{USAGE}").into());
+    };
+    let text = fs::read_to_string(path)?;
+    let encrypted = crypt_ro::encrypt_env(&text, &cryptor()?, &Key::from_env()?)?;
+    if encrypted != text {
+        write_replacing(Path::new(path), &encrypted)?;
+    }
+    Ok(())
+}
+
+/// Writes `contents` to a sibling of `path` and renames it into place, so a crash
+/// leaves either the old or the new file. The new file keeps the permissions of the old.
+fn write_replacing(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    let name = path.file_name().ok_or_else(|| format!("Invalid File Name {}", path.display()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&temp)?;
+        file.set_permissions(fs::metadata(path)?.permissions())?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    Ok(result?)
+}
+
+#[cfg(feature = "config")]
+mod config {
+    use std::error::Error;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use crypt_ro::{decrypt_config, encrypt_config, DocumentFormat, Key};
+    use super::{cryptor, write_replacing, USAGE};
+
+    /// Returns the single file argument and its format.
+    fn file_arg(args: &[String]) -> Result<(&Path, DocumentFormat), Box<dyn Error>> {
+        let [path] = args else {
+            return Err(format!("Expected One File\n\n{USAGE}").into());
+        };
+        let path = Path::new(path);
+        let format = DocumentFormat::from_path(path)
+            .ok_or_else(|| format!("Unknown Document Format {}", path.display()))?;
+        Ok((path, format))
+    }
+
+    pub(crate) fn encrypt(args: &[String]) -> Result<(), Box<dyn Error>> {
+        let (path, format) = file_arg(args)?;
+        print!("{}", encrypt_config(&fs::read_to_string(path)?, format, &cryptor()?, &Key::from_env()?)?);
+        Ok(())
+    }
+
+    pub(crate) fn decrypt(args: &[String]) -> Result<(), Box<dyn Error>> {
+        let (path, format) = file_arg(args)?;
+        print!("{}", decrypt_config(&fs::read_to_string(path)?, format, &cryptor()?, &Key::from_env()?)?);
+        Ok(())
+    }
+
+    /// A directory only the current user can open, holding the decrypted copy. Removed
+    /// however the edit ends, unless the process is killed.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// Creates the directory under `$XDG_RUNTIME_DIR`, which is private and usually
+        /// kept in memory, or else under the system temporary directory.
+        fn create() -> Result<Self, Box<dyn Error>> {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos();
+            let parent = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+            let path = parent.join(format!("crypt-ro-{}-{nanos}", std::process::id()));
+            let mut builder = fs::DirBuilder::new();
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder.create(&path)?;
+            Ok(TempDir(path))
+        }
+
+        /// Creates a file only the current user can read in the directory.
+        fn write(&self, name: &str, contents: &str) -> Result<PathBuf, Box<dyn Error>> {
+            let path = self.0.join(name);
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            std::io::Write::write_all(&mut options.open(&path)?, contents.as_bytes())?;
+            Ok(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Ignores Ctrl-C and Ctrl-\ while the editor runs, as git does, so they reach only
+    /// the editor and cannot end this process before the decrypted copy is removed.
+    #[cfg(unix)]
+    mod interrupts {
+        use std::io;
+        use libc::{sighandler_t, SIGINT, SIGQUIT, SIG_ERR, SIG_IGN};
+
+        /// Dispositions of SIGINT and SIGQUIT in place before [`ignore`].
+        #[derive(Clone, Copy)]
+        pub(super) struct Saved {
+            interrupt: sighandler_t,
+            quit: sighandler_t,
+        }
+
+        fn set(signum: libc::c_int, handler: sighandler_t) -> io::Result<sighandler_t> {
+            // SAFETY: `handler` is `SIG_IGN` or a disposition `signal` returned earlier, so
+            // no Rust code runs as a signal handler; `signal` is async-signal-safe
+            match unsafe { libc::signal(signum, handler) } {
+                SIG_ERR => Err(io::Error::last_os_error()),
+                previous => Ok(previous),
+            }
+        }
+
+        pub(super) fn ignore() -> io::Result<Saved> {
+            let interrupt = set(SIGINT, SIG_IGN)?;
+            match set(SIGQUIT, SIG_IGN) {
+                Ok(quit) => Ok(Saved { interrupt, quit }),
+                Err(e) => {
+                    let _ = set(SIGINT, interrupt);
+                    Err(e)
+                }
+            }
+        }
+
+        impl Saved {
+            /// Puts back the dispositions replaced by [`ignore`].
+            pub(super) fn restore(self) -> io::Result<()> {
+                set(SIGINT, self.interrupt)?;
+                set(SIGQUIT, self.quit)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Opens `path` in `$EDITOR`, which may carry arguments such as `code --wait`.
+    fn run_editor(path: &Path) -> Result<(), Box<dyn Error>> {
+        let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+        #[cfg(unix)]
+        let status = {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(format!("{editor} \"$1\"")).arg("sh").arg(path);
+            let saved = interrupts::ignore()?;
+            // SAFETY: the child only calls `signal`, which is async-signal-safe, before `exec`
+            unsafe {
+                std::os::unix::process::CommandExt::pre_exec(&mut command, move || saved.restore());
+            }
+            let status = command.status();
+            saved.restore()?;
+            status
+        };
+        #[cfg(not(unix))]
+        let status = Command::new(&editor).arg(path).status();
+        let status = status.map_err(|e| format!("Cannot Run Editor {editor}: {e}"))?;
+        if !status.success() {
+            return Err(format!("Editor Exited With {status}; File Left Unchanged").into());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn edit(args: &[String]) -> Result<(), Box<dyn Error>> {
+        let (path, format) = file_arg(args)?;
+        let (cryptor, key) = (cryptor()?, Key::from_env()?);
+        let plain = decrypt_config(&fs::read_to_string(path)?, format, &cryptor, &key)?;
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+
+        let temp = TempDir::create()?;
+        let plain_path = temp.write(&format!("plain.{extension}"), &plain)?;
+        run_editor(&plain_path)?;
+        let edited = fs::read_to_string(&plain_path)?;
+        if edited == plain {
+            eprintln!("File Unchanged");
+            return Ok(());
+        }
+        let encrypted = encrypt_config(&edited, format, &cryptor, &key)?;
+        write_replacing(path, &encrypted)
+    }
+}
@@ -0,0 +1,324 @@
+use crate::cascade::Cascade;
+use crate::counter::Chaining;
+use crate::encoding::Encoding;
+use crate::error::ConfigError;
+use crate::header::FormatVersion;
+use crate::kdf::{Kdf, PBKDF2_ITERATIONS};
+use crate::padding::PaddingPolicy;
+use crate::rounds::{Preset, MAX_ROUNDS};
+use crate::transform::Pipeline;
+use crate::Cryptor;
+
+/// Smallest matrix size [`CryptorBuilder`] accepts.
+pub const MIN_MATRIX: usize = 16;
+
+/// Largest matrix size [`CryptorBuilder`] accepts.
+pub const MAX_MATRIX: usize = 4096;
+
+/// Longest key id [`CryptorBuilder::key_id`] accepts, in bytes.
+pub const MAX_KEY_ID_LEN: usize = 255;
+
+/// Validated configuration for a [`Cryptor`].
+///
+/// Unlike [`Cryptor::set_matrix`], invalid values are reported by
+/// [`build`](CryptorBuilder::build) instead of being ignored.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Chaining, Cryptor, Encoding, Kdf, Preset};
+///
+/// let cryptor = Cryptor::builder()
+///     .matrix(64)
+///     .chaining(Chaining::Counter)
+///     .kdf(Kdf::Sha256)
+///     .preset(Preset::Balanced)
+///     .encoding(Encoding::Base64)
+///     .build()
+///     .unwrap();
+///
+/// let encrypted = cryptor.encrypt_text("message", "key").unwrap();
+/// assert_eq!(cryptor.decrypt_text(&encrypted, "key").unwrap(), "message");
+///
+/// // Weak or meaningless values are rejected
+/// assert!(Cryptor::builder().matrix(3).build().is_err());
+/// ```
+///
+/// With the `serde` feature the builder can be read from configuration files; missing
+/// fields take their defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct CryptorBuilder {
+    matrix: usize,
+    chaining: Chaining,
+    kdf: Kdf,
+    encoding: Encoding,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    format: Option<FormatVersion>,
+    rounds: u32,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pipeline: Option<Pipeline>,
+    padding: PaddingPolicy,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    key_id: Option<String>,
+    key_check: bool,
+    key_commitment: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    cascade: Option<Cascade>,
+}
+
+impl Default for CryptorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CryptorBuilder {
+    /// Creates a builder with the same defaults as [`Cryptor::new`].
+    pub fn new() -> Self {
+        Self {
+            matrix: 32,
+            chaining: Chaining::default(),
+            kdf: Kdf::default(),
+            encoding: Encoding::default(),
+            format: None,
+            rounds: 1,
+            pipeline: None,
+            padding: PaddingPolicy::default(),
+            key_id: None,
+            key_check: false,
+            key_commitment: false,
+            cascade: None,
+        }
+    }
+
+    /// Sets the matrix (block) size, a multiple of 8 between [`MIN_MATRIX`] and
+    /// [`MAX_MATRIX`]. Defaults to 32.
+    pub fn matrix(mut self, size: usize) -> Self {
+        self.matrix = size;
+        self
+    }
+
+    /// Sets how blocks are chained. Defaults to [`Chaining::Chained`].
+    pub fn chaining(mut self, chaining: Chaining) -> Self {
+        self.chaining = chaining;
+        self
+    }
+
+    /// Sets how many times the shuffle and mix pipeline is applied, each round with its
+    /// own subkey. Between 1 and [`MAX_ROUNDS`]; defaults to 1.
+    pub fn rounds(mut self, rounds: u32) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Sets the round count from a [`Preset`].
+    pub fn preset(self, preset: Preset) -> Self {
+        self.rounds(preset.rounds())
+    }
+
+    /// Replaces the stages applied in every round. Only available with
+    /// [`Chaining::Chained`]; the pipeline is recorded in the token header.
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    /// Sets how much [`Cryptor::encrypt`] pads messages to hide their length. Defaults
+    /// to [`PaddingPolicy::Minimal`].
+    pub fn padding(mut self, padding: PaddingPolicy) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Records a label identifying the key in every token, such as a key version, so that
+    /// [`Cryptor::inspect`] can tell which key a token needs. At most [`MAX_KEY_ID_LEN`]
+    /// bytes; the label is not secret.
+    pub fn key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Records the key's [`Fingerprint`](crate::Fingerprint) in every token, so that
+    /// decrypting with another key fails at once with
+    /// [`DecryptError::WrongKey`](crate::DecryptError::WrongKey). Defaults to `false`.
+    ///
+    /// Not available with [`Kdf::Pbkdf2`]: the fingerprint is computed from the key
+    /// itself, so it would let password guesses skip the slow derivation.
+    pub fn key_check(mut self, enabled: bool) -> Self {
+        self.key_check = enabled;
+        self
+    }
+
+    /// Makes every token commit to the key it was encrypted with. Defaults to `false`.
+    ///
+    /// The header records a commitment to the key and the token ends in a tag
+    /// authenticating all of it, so a token can only ever be decrypted under one key:
+    /// another key fails with [`DecryptError::WrongKey`](crate::DecryptError::WrongKey),
+    /// and modified tokens with
+    /// [`DecryptError::AuthenticationFailed`](crate::DecryptError::AuthenticationFailed).
+    /// Adds 66 bytes to each token, and [`Cryptor::decrypt_range`] has to read the whole
+    /// token to check the tag.
+    pub fn key_commitment(mut self, enabled: bool) -> Self {
+        self.key_commitment = enabled;
+        self
+    }
+
+    /// Seals every token with a standard AEAD after encrypting it. Requires the
+    /// `cascade` feature.
+    ///
+    /// [`Cryptor::encrypt`] first produces a regular token and then seals it with
+    /// `cascade`, each layer under its own key derived from the one passed in, and the
+    /// header records the cascade so [`Cryptor::decrypt`] reverses both layers. The data
+    /// stays protected by a standard cipher whatever the strength of the matrix scheme,
+    /// and modified tokens fail with
+    /// [`DecryptError::AuthenticationFailed`](crate::DecryptError::AuthenticationFailed).
+    /// Only single messages are supported: parallel, seekable and range decryption
+    /// reject cascade configurations.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "cascade")] {
+    /// use crypt_ro::{Cascade, Cryptor};
+    ///
+    /// let cryptor = Cryptor::builder().cascade(Cascade::ChaCha20Poly1305).build().unwrap();
+    /// let encrypted = cryptor.encrypt(b"audited data", "master key").unwrap();
+    /// assert_eq!(Cryptor::new().decrypt(&encrypted, "master key").unwrap(), b"audited data");
+    /// # }
+    /// ```
+    pub fn cascade(mut self, cascade: Cascade) -> Self {
+        self.cascade = Some(cascade);
+        self
+    }
+
+    /// Sets the key derivation function. Defaults to [`Kdf::Repeat`].
+    pub fn kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Sets the text encoding of [`Cryptor::encrypt_text`]. Defaults to
+    /// [`Encoding::Base64UrlNoPad`].
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Pins the token format version.
+    ///
+    /// By default the oldest version able to record the configuration is used, so the
+    /// default configuration keeps producing [`FormatVersion::V1`] tokens.
+    pub fn format(mut self, format: FormatVersion) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Validates the configuration and creates the `Cryptor`.
+    pub fn build(self) -> Result<Cryptor, ConfigError> {
+        if !(MIN_MATRIX..=MAX_MATRIX).contains(&self.matrix) || !self.matrix.is_multiple_of(8) {
+            return Err(ConfigError::MatrixSize(self.matrix));
+        }
+        if !(1..=MAX_ROUNDS).contains(&self.rounds) {
+            return Err(ConfigError::Rounds(self.rounds));
+        }
+        if let Some(pipeline) = &self.pipeline {
+            if let Some(reason) = pipeline.validate() {
+                return Err(ConfigError::Pipeline(reason));
+            }
+            if self.chaining != Chaining::Chained {
+                return Err(ConfigError::Pipeline("counter chaining uses a fixed per-block pipeline"));
+            }
+        }
+        if let Some(reason) = self.padding.validate() {
+            return Err(ConfigError::Padding(reason));
+        }
+        if let Some(key_id) = &self.key_id
+            && key_id.len() > MAX_KEY_ID_LEN
+        {
+            return Err(ConfigError::KeyId(key_id.len()));
+        }
+        if let Kdf::Pbkdf2 { iterations } = self.kdf
+            && !PBKDF2_ITERATIONS.contains(&iterations)
+        {
+            return Err(ConfigError::Pbkdf2Iterations(iterations));
+        }
+        if self.key_check && matches!(self.kdf, Kdf::Pbkdf2 { .. }) {
+            return Err(ConfigError::KeyCheck("a key check would bypass PBKDF2 stretching"));
+        }
+        if self.cascade.is_some() && !cfg!(feature = "cascade") {
+            return Err(ConfigError::Cascade("the `cascade` feature is disabled"));
+        }
+
+        let requirements = [
+            ("counter chaining", self.chaining != Chaining::Chained, FormatVersion::V2),
+            ("a key derivation function", self.kdf != Kdf::Repeat, FormatVersion::V2),
+            ("multiple rounds", self.rounds != 1, FormatVersion::V2),
+            ("a custom pipeline", self.pipeline.is_some(), FormatVersion::V2),
+            ("a padding policy", self.padding != PaddingPolicy::Minimal, FormatVersion::V2),
+            ("a key id", self.key_id.is_some(), FormatVersion::V2),
+            ("a key check", self.key_check, FormatVersion::V2),
+            ("key commitment", self.key_commitment, FormatVersion::V2),
+            ("a cascade", self.cascade.is_some(), FormatVersion::V2),
+        ];
+        let required = requirements
+            .iter()
+            .filter(|(_, used, _)| *used)
+            .map(|(_, _, version)| *version)
+            .max()
+            .unwrap_or(FormatVersion::V1);
+        let format = self.format.unwrap_or(required);
+        if let Some((option, _, required)) = requirements
+            .into_iter()
+            .find(|(_, used, version)| *used && *version > format)
+        {
+            return Err(ConfigError::FormatTooOld { option, format, required });
+        }
+        if let Some(reason) = self.pipeline.as_ref().and_then(|pipeline| pipeline.validate_format(format)) {
+            return Err(ConfigError::Pipeline(reason));
+        }
+
+        Ok(Cryptor {
+            matrix: self.matrix,
+            chaining: self.chaining,
+            kdf: self.kdf,
+            encoding: self.encoding,
+            format,
+            rounds: self.rounds,
+            pipeline: self.pipeline,
+            padding: self.padding,
+            key_id: self.key_id,
+            key_check: self.key_check,
+            key_commitment: self.key_commitment,
+            cascade: self.cascade,
+        })
+    }
+}
+
+impl From<Cryptor> for CryptorBuilder {
+    /// Returns a builder for the configuration of `cryptor`, with its format version
+    /// pinned.
+    fn from(cryptor: Cryptor) -> Self {
+        Self {
+            matrix: cryptor.matrix,
+            chaining: cryptor.chaining,
+            kdf: cryptor.kdf,
+            encoding: cryptor.encoding,
+            format: Some(cryptor.format),
+            rounds: cryptor.rounds,
+            pipeline: cryptor.pipeline,
+            padding: cryptor.padding,
+            key_id: cryptor.key_id,
+            key_check: cryptor.key_check,
+            key_commitment: cryptor.key_commitment,
+            cascade: cryptor.cascade,
+        }
+    }
+}
+
+impl TryFrom<CryptorBuilder> for Cryptor {
+    type Error = ConfigError;
+
+    fn try_from(builder: CryptorBuilder) -> Result<Self, ConfigError> {
+        builder.build()
+    }
+}
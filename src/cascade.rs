@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::fmt;
+use crate::header::Header;
+use crate::key::Key;
+use crate::Cryptor;
+
+/// Standard AEAD sealing the output of [`Cryptor::encrypt`] in cascade mode, see
+/// [`CryptorBuilder::cascade`](crate::CryptorBuilder::cascade).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Cascade {
+    /// AES-256-GCM.
+    #[cfg_attr(feature = "serde", serde(rename = "aes_256_gcm"))]
+    Aes256Gcm,
+    /// ChaCha20-Poly1305.
+    #[cfg_attr(feature = "serde", serde(rename = "chacha20_poly1305"))]
+    ChaCha20Poly1305,
+}
+
+impl Cascade {
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            Cascade::Aes256Gcm => 1,
+            Cascade::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Cascade::Aes256Gcm),
+            2 => Some(Cascade::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Cascade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Cascade::Aes256Gcm => "AES-256-GCM",
+            Cascade::ChaCha20Poly1305 => "ChaCha20-Poly1305",
+        })
+    }
+}
+
+#[cfg(feature = "cascade")]
+mod imp {
+    use std::error::Error;
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::Aes256Gcm;
+    use chacha20poly1305::ChaCha20Poly1305;
+    use crate::aead::aead_key;
+    use crate::commit::seal_commitment;
+    use crate::counter::Chaining;
+    use crate::error::DecryptError;
+    use crate::header::{Header, FLAG_CASCADE, FLAG_COUNTER};
+    use crate::kdf::Kdf;
+    use crate::key::Key;
+    use crate::rand::random_bytes;
+    use crate::{decrypt_versioned, Cryptor};
+    use super::Cascade;
+
+    /// Bytes of the random nonce in front of the sealed token.
+    pub(super) const NONCE_LEN: usize = 12;
+
+    /// Key of the inner [`Cryptor`] layer.
+    pub(super) fn inner_key(key: &Key, header: &Header) -> Key {
+        Key::new(aead_key(key, header.kdf, header.salt_bytes(), b"crypt-ro cascade inner"))
+    }
+
+    /// Key of the outer AEAD layer, independent of [`inner_key`].
+    pub(super) fn outer_key(key: &Key, header: &Header, cascade: Cascade) -> [u8; 32] {
+        let domain: &[u8] = match cascade {
+            Cascade::Aes256Gcm => b"crypt-ro cascade aes-256-gcm",
+            Cascade::ChaCha20Poly1305 => b"crypt-ro cascade chacha20-poly1305",
+        };
+        aead_key(key, header.kdf, header.salt_bytes(), domain)
+    }
+
+    fn cipher<C: KeyInit>(key: &[u8; 32]) -> C {
+        C::new_from_slice(key).expect("AEAD keys are 32 bytes")
+    }
+
+    pub(crate) fn seal(cryptor: &Cryptor, cascade: Cascade, data: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        // The inner key is uniformly random, so stretching it again would only cost time
+        let inner = Cryptor {
+            kdf: Kdf::Sha256,
+            key_id: None,
+            key_check: false,
+            key_commitment: false,
+            cascade: None,
+            ..cryptor.clone()
+        };
+        let flags = match cryptor.chaining {
+            Chaining::Counter => FLAG_CASCADE | FLAG_COUNTER,
+            Chaining::Chained => FLAG_CASCADE,
+        };
+        let header = cryptor.header(flags, key)?;
+        let token = inner.encrypt(data, &inner_key(key, &header))?;
+
+        let mut out = Vec::with_capacity(header.encoded_len() + NONCE_LEN + token.len() + 16);
+        header.write(&mut out);
+        let nonce = random_bytes(NONCE_LEN)?;
+        let payload = Payload { msg: &token, aad: &out };
+        let outer_key = outer_key(key, &header, cascade);
+        let sealed = match cascade {
+            Cascade::Aes256Gcm => cipher::<Aes256Gcm>(&outer_key).encrypt(nonce.as_slice().into(), payload),
+            Cascade::ChaCha20Poly1305 => cipher::<ChaCha20Poly1305>(&outer_key).encrypt(nonce.as_slice().into(), payload),
+        }
+        .map_err(|_| "Encryption Failed")?;
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        seal_commitment(&mut out, &header, key);
+        Ok(out)
+    }
+
+    pub(crate) fn open(header: &Header, header_bytes: &[u8], body: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        let cascade = header.cascade.ok_or("Invalid Token Header")?;
+        if body.len() < NONCE_LEN {
+            return Err("Invalid Token Matrix Length".into());
+        }
+        let (nonce, sealed) = body.split_at(NONCE_LEN);
+        let payload = Payload { msg: sealed, aad: header_bytes };
+        let outer_key = outer_key(key, header, cascade);
+        let token = match cascade {
+            Cascade::Aes256Gcm => cipher::<Aes256Gcm>(&outer_key).decrypt(nonce.into(), payload),
+            Cascade::ChaCha20Poly1305 => cipher::<ChaCha20Poly1305>(&outer_key).decrypt(nonce.into(), payload),
+        }
+        .map_err(|_| DecryptError::AuthenticationFailed)?;
+        if !Header::is_present(&token) {
+            return Err("Invalid Token Header".into());
+        }
+        let inner_key = inner_key(key, header);
+        decrypt_versioned(&token, &inner_key, None)
+    }
+}
+
+/// Encrypts `data` with `cryptor` and seals the token with `cascade`.
+///
+/// The inner token and the AEAD use independent keys derived from `key`. The outer
+/// header records the cascade and is authenticated as associated data.
+#[cfg(feature = "cascade")]
+pub(crate) fn seal_cascade(cryptor: &Cryptor, cascade: Cascade, data: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+    imp::seal(cryptor, cascade, data, key)
+}
+
+#[cfg(not(feature = "cascade"))]
+pub(crate) fn seal_cascade(_cryptor: &Cryptor, _cascade: Cascade, _data: &[u8], _key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err("Cascade Needs The cascade Feature".into())
+}
+
+/// Reverses [`seal_cascade`]. `header_bytes` is the header as it appears in the token.
+#[cfg(feature = "cascade")]
+pub(crate) fn open_cascade(header: &Header, header_bytes: &[u8], body: &[u8], key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+    imp::open(header, header_bytes, body, key)
+}
+
+#[cfg(not(feature = "cascade"))]
+pub(crate) fn open_cascade(_header: &Header, _header_bytes: &[u8], _body: &[u8], _key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err("Cascade Needs The cascade Feature".into())
+}
+
+#[cfg(all(test, feature = "cascade"))]
+mod tests {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::Aes256Gcm;
+    use crate::header::Header;
+    use crate::key::Key;
+    use crate::{Cryptor, Kdf};
+    use super::imp::{inner_key, outer_key, NONCE_LEN};
+    use super::Cascade;
+
+    #[test]
+    fn test_layers_use_independent_keys() {
+        let cryptor = Cryptor::builder().cascade(Cascade::Aes256Gcm).kdf(Kdf::Sha256).build().unwrap();
+        let key = Key::new("master key");
+        let encrypted = cryptor.encrypt(b"data", &key).unwrap();
+
+        let (header, body) = Header::read(&encrypted).unwrap();
+        let header_bytes = &encrypted[..encrypted.len() - body.len()];
+        let (nonce, sealed) = body.split_at(NONCE_LEN);
+        let token = Aes256Gcm::new_from_slice(&outer_key(&key, &header, Cascade::Aes256Gcm))
+            .unwrap()
+            .decrypt(nonce.into(), Payload { msg: sealed, aad: header_bytes })
+            .unwrap();
+
+        // The recovered inner token opens under its own key only
+        let plain = Cryptor::builder().kdf(Kdf::Sha256).build().unwrap();
+        assert!(Header::is_present(&token));
+        assert_eq!(plain.decrypt(&token, &inner_key(&key, &header)).unwrap(), b"data");
+        assert_ne!(plain.decrypt(&token, &key).ok(), Some(b"data".to_vec()));
+        assert!(Aes256Gcm::new_from_slice(&key.expand(Kdf::Sha256, &[], 32))
+            .unwrap()
+            .decrypt(nonce.into(), Payload { msg: sealed, aad: header_bytes })
+            .is_err());
+    }
+}
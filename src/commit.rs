@@ -0,0 +1,83 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::error::DecryptError;
+use crate::header::{Header, FLAG_SEEKABLE};
+use crate::kdf::Kdf;
+use crate::key::Key;
+
+pub(crate) type HmacSha256 = Hmac<Sha256>;
+
+/// Bytes of the commitment stored in the header and of the tag ending the token.
+pub(crate) const COMMITMENT_LEN: usize = 32;
+
+/// Returns an HMAC keyed by `key` for the purpose named by `domain`.
+///
+/// The secret is hashed under the expanded key, so keys that happen to expand to the
+/// same key block still get different MAC keys, and a slow [`Kdf`] cannot be skipped
+/// when testing guesses against a MAC.
+pub(crate) fn derived_mac(key: &Key, kdf: Kdf, salt: &[u8], matrix: usize, domain: &[u8]) -> HmacSha256 {
+    let expanded = key.expand(kdf, salt, matrix);
+    let mut mac = HmacSha256::new_from_slice(&expanded).expect("HMAC accepts any key length");
+    mac.update(domain);
+    mac.update(key.secret());
+    let mac_key = mac.finalize().into_bytes();
+    HmacSha256::new_from_slice(&mac_key).expect("HMAC accepts any key length")
+}
+
+/// Key committing to `key` under the header's key derivation.
+fn commit_key(key: &Key, header: &Header) -> HmacSha256 {
+    derived_mac(key, header.kdf, header.salt_bytes(), header.matrix, b"crypt-ro commit key")
+}
+
+/// Returns the commitment to `key` recorded in the header.
+pub(crate) fn commitment(key: &Key, header: &Header) -> [u8; COMMITMENT_LEN] {
+    let mut mac = commit_key(key, header);
+    mac.update(b"crypt-ro commitment");
+    mac.finalize().into_bytes().into()
+}
+
+/// Whether a token with this header ends in a tag. Seekable containers authenticate
+/// their header with every chunk instead.
+pub(crate) fn has_tag(header: &Header) -> bool {
+    header.commitment.is_some() && !header.has_flag(FLAG_SEEKABLE)
+}
+
+fn tag(key: &Key, header: &Header, token: &[u8]) -> HmacSha256 {
+    let mut mac = commit_key(key, header);
+    mac.update(b"crypt-ro tag");
+    mac.update(token);
+    mac
+}
+
+/// Appends the tag authenticating `token`, header included, if the header commits to
+/// the key.
+pub(crate) fn seal_commitment(token: &mut Vec<u8>, header: &Header, key: &Key) {
+    if has_tag(header) {
+        let tag = tag(key, header, token).finalize().into_bytes();
+        token.extend_from_slice(&tag);
+    }
+}
+
+/// Checks the commitment of a token and strips its tag from `body`.
+///
+/// Fails with [`DecryptError::WrongKey`] if the token commits to another key and with
+/// [`DecryptError::AuthenticationFailed`] if the token was modified.
+pub(crate) fn open_commitment<'a>(token: &[u8], header: &Header, body: &'a [u8], key: &Key) -> Result<&'a [u8], DecryptError> {
+    let Some(expected) = header.commitment else {
+        return Ok(body);
+    };
+    let mut mac = commit_key(key, header);
+    mac.update(b"crypt-ro commitment");
+    mac.verify_slice(&expected).map_err(|_| DecryptError::WrongKey)?;
+    if !has_tag(header) {
+        return Ok(body);
+    }
+    if body.len() < COMMITMENT_LEN {
+        return Err(DecryptError::AuthenticationFailed);
+    }
+    let (authenticated, stored) = token.split_at(token.len() - COMMITMENT_LEN);
+    tag(key, header, authenticated)
+        .verify_slice(stored)
+        .map_err(|_| DecryptError::AuthenticationFailed)?;
+    Ok(&body[..body.len() - COMMITMENT_LEN])
+}
@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::ops::Range;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use crate::header::{FormatVersion, Header};
+use crate::rand::{random_bytes, random_nonce, KeyedRng};
+use crate::sbox::SBox;
+use crate::rounds::Scheme;
+use crate::simd::{self, Backend};
+use crate::util::{derive_subkey, shuffle, shuffle_uniform, shuffle_with, subkey_seed, unshuffle, unshuffle_uniform, unshuffle_with};
+
+/// How the blocks of a message depend on each other.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Chaining, Cryptor};
+///
+/// let cryptor = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+///
+/// let encrypted = cryptor.encrypt(b"0123456789abcdef", "key").unwrap();
+/// let middle = cryptor.decrypt_range(&encrypted, "key", 4..8).unwrap();
+/// assert_eq!(middle, b"4567");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Chaining {
+    /// Every block is mixed with the previous one and shuffled with a seed taken from the
+    /// next one. This is the original scheme, the only one available in
+    /// [`FormatVersion::V1`](crate::FormatVersion::V1) tokens shared with the other language
+    /// ports. Tokens must be decrypted as a whole.
+    #[default]
+    Chained,
+    /// Every block is transformed with a key derived only from the key, a per-message
+    /// nonce and the block index. Any block can be decrypted on its own, which enables
+    /// [`Cryptor::decrypt_range`](crate::Cryptor::decrypt_range) and parallel processing.
+    /// Tokens start with a header recording this mode.
+    Counter,
+}
+
+const NONCE_LEN: usize = 8;
+const LEN_PREFIX: usize = 4;
+const SHUFFLE_DOMAIN: u64 = 0x5348_5546_464C_4532;
+
+/// A block shuffle taking the seed and step.
+type ShuffleFn = fn(&mut [u8], u64, usize);
+
+/// Generator keys of one round of [`FormatVersion::V5`] tokens, for the block shuffles
+/// and the keystream.
+struct RoundRngKeys {
+    shuffle: [u8; 32],
+    stream: [u8; 32],
+}
+
+/// Per-message key schedule for [`Chaining::Counter`], one message key per round.
+struct BlockKeys {
+    message_keys: Vec<Vec<u8>>,
+    sboxes: Option<Vec<SBox>>,
+    shuffle: ShuffleFn,
+    unshuffle: ShuffleFn,
+    /// Set when randomness comes from a [`KeyedRng`] rather than the seeded shuffles.
+    rng_keys: Option<Vec<RoundRngKeys>>,
+}
+
+impl BlockKeys {
+    fn new(key_bytes: &[u8], scheme: &Scheme, nonce: u64) -> Self {
+        let message_keys: Vec<_> = scheme.round_keys(key_bytes)
+            .iter()
+            .map(|round_key| scheme.subkey(round_key, nonce))
+            .collect();
+        let sboxes = scheme.sboxes(&message_keys);
+        let (shuffle, unshuffle): (ShuffleFn, ShuffleFn) =
+            if scheme.format >= FormatVersion::V4 {
+                (shuffle_uniform, unshuffle_uniform)
+            } else {
+                (shuffle, unshuffle)
+            };
+        let rng_keys = scheme.keyed_rng().then(|| {
+            message_keys
+                .iter()
+                .map(|key| RoundRngKeys {
+                    shuffle: KeyedRng::derive_key(key, b"counter shuffle"),
+                    stream: KeyedRng::derive_key(key, b"counter stream"),
+                })
+                .collect()
+        });
+        Self { message_keys, sboxes, shuffle, unshuffle, rng_keys }
+    }
+
+    fn shuffle_block(&self, round: usize, index: usize, block: &mut [u8]) {
+        match &self.rng_keys {
+            Some(keys) => shuffle_with(block, &mut KeyedRng::new(&keys[round].shuffle, index as u64), 2),
+            None => (self.shuffle)(block, self.shuffle_seed(round, index), 2),
+        }
+    }
+
+    fn unshuffle_block(&self, round: usize, index: usize, block: &mut [u8]) {
+        match &self.rng_keys {
+            Some(keys) => unshuffle_with(block, &mut KeyedRng::new(&keys[round].shuffle, index as u64), 2),
+            None => (self.unshuffle)(block, self.shuffle_seed(round, index), 2),
+        }
+    }
+
+    fn shuffle_seed(&self, round: usize, index: usize) -> u64 {
+        subkey_seed(&self.message_keys[round], index as u64) ^ SHUFFLE_DOMAIN
+    }
+
+    /// XORs `block` with the keystream of block `index`.
+    fn apply_stream(&self, round: usize, index: usize, block: &mut [u8]) {
+        let stream = match &self.rng_keys {
+            Some(keys) => {
+                let mut stream = vec![0u8; block.len()];
+                KeyedRng::new(&keys[round].stream, index as u64).fill_bytes(&mut stream);
+                stream
+            }
+            None => derive_subkey(&self.message_keys[round], index as u64),
+        };
+        simd::xor_into(Backend::detect(), block, &stream);
+    }
+
+    fn encrypt_block(&self, index: usize, block: &mut [u8]) {
+        for round in 0..self.message_keys.len() {
+            self.shuffle_block(round, index, block);
+            if let Some(sboxes) = &self.sboxes {
+                sboxes[round].substitute(block);
+            }
+            self.apply_stream(round, index, block);
+        }
+    }
+
+    fn decrypt_block(&self, index: usize, block: &mut [u8]) {
+        for round in (0..self.message_keys.len()).rev() {
+            self.apply_stream(round, index, block);
+            if let Some(sboxes) = &self.sboxes {
+                sboxes[round].invert(block);
+            }
+            self.unshuffle_block(round, index, block);
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn for_each_block(matrix_size: usize, buf: &mut [u8], f: impl Fn(usize, &mut [u8]) + Sync) {
+    buf.par_chunks_exact_mut(matrix_size)
+        .enumerate()
+        .for_each(|(i, block)| f(i, block));
+}
+
+#[cfg(not(feature = "parallel"))]
+fn for_each_block(matrix_size: usize, buf: &mut [u8], f: impl Fn(usize, &mut [u8])) {
+    buf.chunks_exact_mut(matrix_size)
+        .enumerate()
+        .for_each(|(i, block)| f(i, block));
+}
+
+/// Encrypts `data` in [`Chaining::Counter`] mode, header included.
+///
+/// Layout: `header | nonce (8) | blocks`, where the blocks hold
+/// `length (4, big endian) | data | random padding`.
+pub(crate) fn seal_counter(header: &Header, data: &[u8], key_bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let matrix_size = header.matrix;
+    let data_len = u32::try_from(data.len()).map_err(|_| "Data too Big")?;
+    let nonce = random_nonce()?;
+    let padded_len = header.padding.padded_len(data.len())?;
+    let pad = padded_len - data.len() + (matrix_size - ((LEN_PREFIX + padded_len) % matrix_size)) % matrix_size;
+
+    let mut out = Vec::with_capacity(header.encoded_len() + NONCE_LEN + LEN_PREFIX + data.len() + pad);
+    header.write(&mut out);
+    out.extend_from_slice(&nonce.to_be_bytes());
+    let body_start = out.len();
+    out.extend_from_slice(&data_len.to_be_bytes());
+    out.extend_from_slice(data);
+    out.extend_from_slice(&random_bytes(pad)?);
+
+    let keys = BlockKeys::new(key_bytes, &header.scheme(), nonce);
+    for_each_block(matrix_size, &mut out[body_start..], |i, block| keys.encrypt_block(i, block));
+    Ok(out)
+}
+
+/// Splits a counter-mode body into its key schedule and blocks.
+fn read_body<'a>(scheme: &Scheme, body: &'a [u8], key_bytes: &[u8]) -> Result<(BlockKeys, &'a [u8]), Box<dyn Error>> {
+    let matrix_size = scheme.matrix;
+    if body.len() < NONCE_LEN {
+        return Err("Invalid Token Matrix Length".into());
+    }
+    let (nonce, blocks) = body.split_at(NONCE_LEN);
+    if blocks.is_empty() || blocks.len() % matrix_size != 0 || blocks.len() < LEN_PREFIX {
+        return Err("Invalid Token Matrix Length".into());
+    }
+    let nonce = u64::from_be_bytes(nonce.try_into().unwrap());
+    Ok((BlockKeys::new(key_bytes, scheme, nonce), blocks))
+}
+
+/// Reverses [`seal_counter`] given the bytes following the header.
+pub(crate) fn open_counter(scheme: &Scheme, body: &[u8], key_bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let matrix_size = scheme.matrix;
+    let (keys, blocks) = read_body(scheme, body, key_bytes)?;
+    let mut decoded = blocks.to_vec();
+    for_each_block(matrix_size, &mut decoded, |i, block| keys.decrypt_block(i, block));
+
+    let data_size = u32::from_be_bytes([decoded[0], decoded[1], decoded[2], decoded[3]]) as usize;
+    if decoded.len() < data_size + LEN_PREFIX {
+        return Err("Invalid Token Matrix Length".into());
+    }
+    decoded.truncate(data_size + LEN_PREFIX);
+    decoded.drain(..LEN_PREFIX);
+    Ok(decoded)
+}
+
+/// Decrypts only the blocks covering `range` of the plaintext.
+///
+/// The range is clamped to the plaintext length.
+pub(crate) fn open_counter_range(
+    scheme: &Scheme,
+    body: &[u8],
+    key_bytes: &[u8],
+    range: Range<usize>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let matrix_size = scheme.matrix;
+    let (keys, blocks) = read_body(scheme, body, key_bytes)?;
+    let decrypt_span = |span: Range<usize>| {
+        let first = span.start / matrix_size;
+        let last = span.end.div_ceil(matrix_size);
+        let mut decoded = blocks[first * matrix_size..last * matrix_size].to_vec();
+        for (i, block) in decoded.chunks_exact_mut(matrix_size).enumerate() {
+            keys.decrypt_block(first + i, block);
+        }
+        let offset = first * matrix_size;
+        decoded[span.start - offset..span.end - offset].to_vec()
+    };
+
+    let prefix = decrypt_span(0..LEN_PREFIX);
+    let data_size = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+    if blocks.len() < data_size + LEN_PREFIX {
+        return Err("Invalid Token Matrix Length".into());
+    }
+
+    let end = range.end.min(data_size);
+    let start = range.start.min(end);
+    if start == end {
+        return Ok(Vec::new());
+    }
+    Ok(decrypt_span(LEN_PREFIX + start..LEN_PREFIX + end))
+}
@@ -0,0 +1,156 @@
+use std::error::Error;
+use std::path::Path;
+use serde_json::{Map, Value};
+use hmac::Mac;
+use crate::commit::{derived_mac, HmacSha256};
+use crate::error::DecryptError;
+use crate::encoding::Encoding;
+use crate::json::{decrypt_leaf, encrypt_leaf, Visitor};
+use crate::key::{AsKey, Key};
+use crate::Cryptor;
+
+/// Top-level member holding the metadata of an encrypted document.
+pub const DOCUMENT_METADATA_KEY: &str = "crypt_ro";
+
+/// Syntax of a configuration document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocumentFormat {
+    /// JSON.
+    Json,
+    /// TOML.
+    Toml,
+    /// YAML.
+    Yaml,
+}
+
+impl DocumentFormat {
+    /// Picks the format from a `.json`, `.toml`, `.yaml` or `.yml` file extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(DocumentFormat::Json),
+            "toml" => Some(DocumentFormat::Toml),
+            "yaml" | "yml" => Some(DocumentFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Parses `text` into a JSON value.
+    pub fn parse(self, text: &str) -> Result<Value, Box<dyn Error>> {
+        Ok(match self {
+            DocumentFormat::Json => serde_json::from_str(text)?,
+            DocumentFormat::Toml => toml::from_str(text)?,
+            DocumentFormat::Yaml => serde_yaml::from_str(text)?,
+        })
+    }
+
+    /// Writes `value` in this format.
+    pub fn render(self, value: &Value) -> Result<String, Box<dyn Error>> {
+        Ok(match self {
+            DocumentFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            DocumentFormat::Toml => toml::to_string_pretty(value)?,
+            DocumentFormat::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+/// Encrypts every value of a key-value document, leaving the keys readable.
+///
+/// Like [`encrypt_json_paths`](crate::encrypt_json_paths) with the path `$..*`: every
+/// string, number, boolean and null becomes a `cro:` token of its JSON text. A MAC over
+/// the encrypted document, keys included, is stored under [`DOCUMENT_METADATA_KEY`], so
+/// [`decrypt_document`] detects changed, added, removed or moved entries. `document`
+/// must be an object.
+///
+/// # Example
+/// ```
+/// use crypt_ro::{decrypt_document, encrypt_document, Cryptor};
+/// use serde_json::json;
+///
+/// let cryptor = Cryptor::new();
+/// let mut config = json!({"database": {"user": "app", "password": "hunter2", "port": 5432}});
+///
+/// encrypt_document(&mut config, &cryptor, "key").unwrap();
+/// assert!(config["database"]["password"].as_str().unwrap().starts_with("cro:"));
+///
+/// decrypt_document(&mut config, &cryptor, "key").unwrap();
+/// assert_eq!(config, json!({"database": {"user": "app", "password": "hunter2", "port": 5432}}));
+/// ```
+pub fn encrypt_document<K: AsKey + ?Sized>(document: &mut Value, cryptor: &Cryptor, key: &K) -> Result<(), Box<dyn Error>> {
+    let key = key.as_key();
+    let map = document.as_object_mut().ok_or("Document Must Be a Map")?;
+    if map.contains_key(DOCUMENT_METADATA_KEY) {
+        return Err("Document Is Already Encrypted".into());
+    }
+    for_each_leaf(document, &mut |leaf| encrypt_leaf(leaf, cryptor, &key))?;
+    let salt = cryptor.kdf().new_salt()?;
+    let salt = salt.as_ref().map_or(&[][..], |salt| salt);
+    let mac = document_mac(document, cryptor, &key, salt)?.finalize().into_bytes();
+    let mut metadata = Map::new();
+    metadata.insert("mac".to_string(), Value::String(Encoding::Hex.encode(&mac)));
+    if !salt.is_empty() {
+        metadata.insert("salt".to_string(), Value::String(Encoding::Hex.encode(salt)));
+    }
+    document.as_object_mut().unwrap().insert(DOCUMENT_METADATA_KEY.to_string(), Value::Object(metadata));
+    Ok(())
+}
+
+/// Verifies and decrypts a document encrypted by [`encrypt_document`].
+///
+/// `cryptor` must use the key derivation and matrix size the document was encrypted
+/// with, which key the MAC. Fails with
+/// [`DecryptError::AuthenticationFailed`](crate::DecryptError::AuthenticationFailed)
+/// before decrypting anything if the document was modified or the key is wrong.
+pub fn decrypt_document<K: AsKey + ?Sized>(document: &mut Value, cryptor: &Cryptor, key: &K) -> Result<(), Box<dyn Error>> {
+    let key = key.as_key();
+    let map = document.as_object_mut().ok_or("Document Must Be a Map")?;
+    let metadata = map.remove(DOCUMENT_METADATA_KEY).ok_or("Document Is Not Encrypted")?;
+    let stored = metadata.get("mac").and_then(Value::as_str).ok_or("Invalid Document Metadata")?;
+    let stored = Encoding::Hex.decode(stored).map_err(|_| "Invalid Document Metadata")?;
+    let salt = match metadata.get("salt") {
+        Some(salt) => salt.as_str().and_then(|salt| Encoding::Hex.decode(salt).ok()).ok_or("Invalid Document Metadata")?,
+        None => Vec::new(),
+    };
+    if salt.len() != cryptor.kdf().salt_len() {
+        return Err("Invalid Document Metadata".into());
+    }
+    if document_mac(document, cryptor, &key, &salt)?.verify_slice(&stored).is_err() {
+        document.as_object_mut().unwrap().insert(DOCUMENT_METADATA_KEY.to_string(), metadata);
+        return Err(DecryptError::AuthenticationFailed.into());
+    }
+    for_each_leaf(document, &mut |leaf| decrypt_leaf(leaf, cryptor, &key).map(drop))
+}
+
+/// Encrypts the values of a configuration file's text.
+///
+/// See [`encrypt_document`]; the result is written in the same format.
+pub fn encrypt_config<K: AsKey + ?Sized>(text: &str, format: DocumentFormat, cryptor: &Cryptor, key: &K) -> Result<String, Box<dyn Error>> {
+    let mut document = format.parse(text)?;
+    encrypt_document(&mut document, cryptor, key)?;
+    format.render(&document)
+}
+
+/// Verifies and decrypts a configuration file encrypted by [`encrypt_config`].
+pub fn decrypt_config<K: AsKey + ?Sized>(text: &str, format: DocumentFormat, cryptor: &Cryptor, key: &K) -> Result<String, Box<dyn Error>> {
+    let mut document = format.parse(text)?;
+    decrypt_document(&mut document, cryptor, key)?;
+    format.render(&document)
+}
+
+/// MAC of an encrypted document without its metadata. `salt` is stored in the metadata
+/// when the key derivation takes one.
+fn document_mac(document: &Value, cryptor: &Cryptor, key: &Key, salt: &[u8]) -> Result<HmacSha256, Box<dyn Error>> {
+    let mut mac = derived_mac(key, cryptor.kdf(), salt, cryptor.matrix(), b"crypt-ro document mac");
+    // Maps are ordered by key, so the JSON text is canonical
+    mac.update(serde_json::to_string(document)?.as_bytes());
+    Ok(mac)
+}
+
+/// Calls `f` on every value that is not an object or array.
+fn for_each_leaf(value: &mut Value, f: &mut Visitor<'_>) -> Result<(), Box<dyn Error>> {
+    match value {
+        Value::Object(map) => map.values_mut().try_for_each(|child| for_each_leaf(child, f)),
+        Value::Array(items) => items.iter_mut().try_for_each(|child| for_each_leaf(child, f)),
+        leaf => f(leaf),
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use crate::key::{AsKey, Key};
+use crate::Cryptor;
+
+/// Opens an encrypted value in a dotenv file; the token follows until [`ENV_SUFFIX`].
+pub const ENV_PREFIX: &str = "ENC[";
+
+/// Closes an encrypted value opened by [`ENV_PREFIX`].
+pub const ENV_SUFFIX: &str = "]";
+
+/// An assignment in a dotenv file.
+struct Entry {
+    name: String,
+    /// The value with quotes and escapes resolved.
+    value: String,
+    /// Whether the value was quoted, which makes it plain text.
+    quoted: bool,
+    /// Bytes of the line holding the value as written, quotes included.
+    span: Range<usize>,
+}
+
+/// Parses one line of a dotenv file; comments and blank lines hold no entry.
+///
+/// Accepts `NAME=value`, an optional `export ` prefix, single-quoted literal values,
+/// double-quoted values with `\n`, `\"` and `\\` escapes, and ` #` comments after
+/// unquoted values.
+fn parse_line(line: &str, number: usize) -> Result<Option<Entry>, Box<dyn Error>> {
+    let invalid = || format!("Invalid Env Line {number}");
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+    let assignment = trimmed.strip_prefix("export ").map_or(trimmed, str::trim_start);
+    let (name, _) = assignment.split_once('=').ok_or_else(invalid)?;
+    let name = name.trim_end();
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if !valid_name {
+        return Err(invalid().into());
+    }
+
+    let value_start = line.len() - assignment.len() + assignment.find('=').unwrap() + 1;
+    let raw = &line[value_start..];
+    let leading = raw.len() - raw.trim_start().len();
+    let raw = raw.trim_start();
+    let offset = value_start + leading;
+
+    let quoted = raw.starts_with(['\'', '"']);
+    let (value, len) = match raw.chars().next() {
+        Some('\'') => {
+            let end = raw[1..].find('\'').ok_or_else(invalid)?;
+            (raw[1..=end].to_string(), end + 2)
+        }
+        Some('"') => {
+            let mut value = String::new();
+            let mut chars = raw.char_indices().skip(1);
+            let len = loop {
+                match chars.next().ok_or_else(invalid)? {
+                    (i, '"') => break i + 1,
+                    (_, '\\') => match chars.next().ok_or_else(invalid)?.1 {
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        't' => value.push('\t'),
+                        other => value.push(other),
+                    },
+                    (_, c) => value.push(c),
+                }
+            };
+            (value, len)
+        }
+        _ => {
+            let end = raw.find(" #").unwrap_or(raw.len());
+            let value = raw[..end].trim_end();
+            (value.to_string(), value.len())
+        }
+    };
+    let rest = raw[len..].trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(invalid().into());
+    }
+    Ok(Some(Entry { name: name.to_string(), value, quoted, span: offset..offset + len }))
+}
+
+/// Returns the token of an encrypted value: an unquoted `ENC[...]` holding text in the
+/// encoding of `cryptor`. Anything else, quoted values included, is plain text.
+fn env_token<'a>(entry: &'a Entry, cryptor: &Cryptor) -> Option<&'a str> {
+    if entry.quoted {
+        return None;
+    }
+    let token = entry.value.strip_prefix(ENV_PREFIX)?.strip_suffix(ENV_SUFFIX)?;
+    cryptor.encoding().decode(token).is_ok().then_some(token)
+}
+
+/// Encrypts every plain value of a dotenv file, keeping names, comments and layout.
+///
+/// Each value becomes `ENC[token]` with the [`Cryptor::encrypt_text`] token of the
+/// value. Unquoted `ENC[...]` values holding a token are already encrypted and left
+/// alone, so new plain entries can be added to an encrypted file and the file encrypted
+/// again. Quoted values are always plain text.
+///
+/// # Example
+/// ```
+/// use crypt_ro::{decrypt_env, encrypt_env, Cryptor};
+///
+/// let cryptor = Cryptor::new();
+/// let encrypted = encrypt_env("# database\nDB_PASSWORD=hunter2\n", &cryptor, "key").unwrap();
+/// assert!(encrypted.starts_with("# database\nDB_PASSWORD=ENC["));
+///
+/// let vars = decrypt_env(&encrypted, &cryptor, "key").unwrap();
+/// assert_eq!(vars["DB_PASSWORD"], "hunter2");
+/// ```
+pub fn encrypt_env<K: AsKey + ?Sized>(text: &str, cryptor: &Cryptor, key: &K) -> Result<String, Box<dyn Error>> {
+    let key = key.as_key();
+    let mut out = String::with_capacity(text.len() * 2);
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        match parse_line(content, i + 1)? {
+            Some(entry) if env_token(&entry, cryptor).is_none() => {
+                let token = cryptor.encrypt_text(&entry.value, &*key)?;
+                out.push_str(&content[..entry.span.start]);
+                out.push_str(&format!("{ENV_PREFIX}{token}{ENV_SUFFIX}"));
+                out.push_str(&line[entry.span.end..]);
+            }
+            _ => out.push_str(line),
+        }
+    }
+    Ok(out)
+}
+
+/// Parses a dotenv file and decrypts its `ENC[...]` values.
+///
+/// Plain values, including quoted ones that look like `ENC[...]`, are returned as
+/// written, so a file may mix both. Later assignments of a name replace earlier ones.
+pub fn decrypt_env<K: AsKey + ?Sized>(text: &str, cryptor: &Cryptor, key: &K) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let key = key.as_key();
+    let mut vars = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let Some(entry) = parse_line(line, i + 1)? else {
+            continue;
+        };
+        let value = match env_token(&entry, cryptor) {
+            Some(token) => cryptor
+                .decrypt_text(token, &*key)
+                .map_err(|e| format!("Cannot Decrypt {}: {e}", entry.name))?,
+            None => entry.value,
+        };
+        vars.insert(entry.name, value);
+    }
+    Ok(vars)
+}
+
+/// Reads and decrypts the dotenv file at `path` with the key from
+/// [`Key::from_env`].
+pub fn load_env_file(path: impl AsRef<Path>, cryptor: &Cryptor) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot Read {}: {e}", path.display()))?;
+    decrypt_env(&text, cryptor, &Key::from_env()?)
+}
+
+/// Like [`load_env_file`], then sets the variables in the process environment.
+///
+/// Variables that are already set are kept, so the real environment wins over the
+/// file. Returns the names that were set.
+///
+/// # Safety
+///
+/// Setting environment variables is only sound while no other thread reads or writes
+/// the environment, see [`std::env::set_var`]. Call this early in `main`, before
+/// starting threads.
+pub unsafe fn apply_env_file(path: impl AsRef<Path>, cryptor: &Cryptor) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut set = Vec::new();
+    for (name, value) in load_env_file(path, cryptor)? {
+        if std::env::var_os(&name).is_none() {
+            // SAFETY: the caller guarantees no other thread uses the environment
+            unsafe { std::env::set_var(&name, value) };
+            set.push(name);
+        }
+    }
+    Ok(set)
+}
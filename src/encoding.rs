@@ -0,0 +1,61 @@
+use std::error::Error;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use base64::Engine as _;
+use crate::util::{decode_text, encode_text};
+
+/// Text encoding used by [`Cryptor::encrypt_text`](crate::Cryptor::encrypt_text) and
+/// [`Cryptor::decrypt_text`](crate::Cryptor::decrypt_text).
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, Encoding};
+///
+/// let cryptor = Cryptor::builder().encoding(Encoding::Hex).build().unwrap();
+/// let encrypted = cryptor.encrypt_text("message", "key").unwrap();
+/// assert!(encrypted.chars().all(|c| c.is_ascii_hexdigit()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Encoding {
+    /// URL-safe base64 without padding (the original encoding). Padded input is also
+    /// accepted when decoding.
+    #[default]
+    Base64UrlNoPad,
+    /// URL-safe base64 with padding.
+    Base64Url,
+    /// Standard base64 with padding.
+    Base64,
+    /// Lowercase hexadecimal.
+    Hex,
+}
+
+impl Encoding {
+    /// Encodes bytes as text.
+    pub fn encode(self, data: &[u8]) -> String {
+        match self {
+            Encoding::Base64UrlNoPad => encode_text(data),
+            Encoding::Base64Url => URL_SAFE.encode(data),
+            Encoding::Base64 => STANDARD.encode(data),
+            Encoding::Hex => data.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+
+    /// Decodes text produced by [`Encoding::encode`].
+    pub fn decode(self, encoded: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Encoding::Base64UrlNoPad => decode_text(encoded),
+            Encoding::Base64Url => Ok(URL_SAFE.decode(encoded)?),
+            Encoding::Base64 => Ok(STANDARD.decode(encoded)?),
+            Encoding::Hex => {
+                if !encoded.len().is_multiple_of(2) || !encoded.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err("Invalid Hex Token".into());
+                }
+                (0..encoded.len())
+                    .step_by(2)
+                    .map(|i| Ok(u8::from_str_radix(&encoded[i..i + 2], 16)?))
+                    .collect()
+            }
+        }
+    }
+}
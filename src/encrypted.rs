@@ -0,0 +1,173 @@
+//! Encrypted fields for serde.
+//!
+//! [`Encrypted<T>`] serializes its value to JSON and encrypts it into a text token, and
+//! the [`string`] and [`bytes`] modules encrypt plain fields through
+//! `#[serde(with = "...")]`. Serde gives no way to pass arguments to a field, so the
+//! [`Cryptor`] and key come from [`with_key`], which sets them for the current thread.
+//!
+//! # Example
+//! ```
+//! use crypt_ro::Cryptor;
+//! use crypt_ro::encrypted::{self, Encrypted};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct User {
+//!     name: String,
+//!     #[serde(with = "encrypted::string")]
+//!     email: String,
+//!     card: Encrypted<Vec<u32>>,
+//! }
+//!
+//! let cryptor = Cryptor::new();
+//! let user = User { name: "ann".into(), email: "ann@example.com".into(), card: Encrypted(vec![4, 2]) };
+//! let json = encrypted::with_key(&cryptor, "key", || serde_json::to_string(&user)).unwrap();
+//! assert!(!json.contains("example.com"));
+//!
+//! let user: User = encrypted::with_key(&cryptor, "key", || serde_json::from_str(&json)).unwrap();
+//! assert_eq!(user.email, "ann@example.com");
+//! assert_eq!(*user.card, [4, 2]);
+//! ```
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use serde::de::{self, DeserializeOwned};
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+use crate::key::{AsKey, Key};
+use crate::Cryptor;
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<(Cryptor, Key)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes the innermost context when [`with_key`] returns or unwinds.
+struct ContextGuard;
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        CONTEXT.with(|context| context.borrow_mut().pop());
+    }
+}
+
+/// Runs `f` with `cryptor` and `key` encrypting and decrypting the fields of this module
+/// on the current thread.
+///
+/// Calls can be nested; the innermost one applies.
+pub fn with_key<K: AsKey + ?Sized, R>(cryptor: &Cryptor, key: &K, f: impl FnOnce() -> R) -> R {
+    let key = key.as_key().into_owned();
+    CONTEXT.with(|context| context.borrow_mut().push((cryptor.clone(), key)));
+    let _guard = ContextGuard;
+    f()
+}
+
+/// Encrypts `plain` with the current context into a token in its
+/// [`Encoding`](crate::Encoding).
+fn encrypt_token(plain: &[u8]) -> Result<String, Box<dyn Error>> {
+    CONTEXT.with(|context| {
+        let context = context.borrow();
+        let (cryptor, key) = context.last().ok_or("No Encryption Key Set")?;
+        Ok(cryptor.encoding().encode(&cryptor.encrypt(plain, key)?))
+    })
+}
+
+/// Decrypts a token produced by [`encrypt_token`].
+fn decrypt_token(token: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    CONTEXT.with(|context| {
+        let context = context.borrow();
+        let (cryptor, key) = context.last().ok_or("No Encryption Key Set")?;
+        cryptor.decrypt(&cryptor.encoding().decode(token)?, key)
+    })
+}
+
+/// A value serialized as an encrypted text token.
+///
+/// The value is serialized to JSON, encrypted and written as a string, so any
+/// serializable type can be stored encrypted in any serde format. Requires a key set with
+/// [`with_key`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Encrypted<T>(pub T);
+
+impl<T> Encrypted<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Encrypted<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Encrypted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Encrypted<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Encrypted(..)")
+    }
+}
+
+impl<T: Serialize> Serialize for Encrypted<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let plain = serde_json::to_vec(&self.0).map_err(ser::Error::custom)?;
+        serializer.serialize_str(&encrypt_token(&plain).map_err(ser::Error::custom)?)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Encrypted<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = String::deserialize(deserializer)?;
+        let plain = decrypt_token(&token).map_err(de::Error::custom)?;
+        serde_json::from_slice(&plain).map(Encrypted).map_err(de::Error::custom)
+    }
+}
+
+/// Encrypts a string field, for `#[serde(with = "crypt_ro::encrypted::string")]`.
+///
+/// The field is written as [`Cryptor::encrypt_text`] would encrypt it.
+pub mod string {
+    use super::*;
+
+    /// Serializes `value` as an encrypted token.
+    pub fn serialize<T: AsRef<str> + ?Sized, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encrypt_token(value.as_ref().as_bytes()).map_err(ser::Error::custom)?)
+    }
+
+    /// Deserializes and decrypts a token.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        let token = String::deserialize(deserializer)?;
+        let plain = decrypt_token(&token).map_err(de::Error::custom)?;
+        String::from_utf8(plain).map_err(de::Error::custom)
+    }
+}
+
+/// Encrypts a byte field, for `#[serde(with = "crypt_ro::encrypted::bytes")]`.
+pub mod bytes {
+    use super::*;
+
+    /// Serializes `value` as an encrypted token.
+    pub fn serialize<T: AsRef<[u8]> + ?Sized, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encrypt_token(value.as_ref()).map_err(ser::Error::custom)?)
+    }
+
+    /// Deserializes and decrypts a token.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let token = String::deserialize(deserializer)?;
+        decrypt_token(&token).map_err(de::Error::custom)
+    }
+}
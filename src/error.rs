@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::fmt;
+use crate::header::FormatVersion;
+
+/// A rejected [`CryptorBuilder`](crate::CryptorBuilder) configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The matrix size is outside
+    /// [`MIN_MATRIX`](crate::MIN_MATRIX)..=[`MAX_MATRIX`](crate::MAX_MATRIX) or not a multiple of 8.
+    MatrixSize(usize),
+    /// The round count is 0 or above [`MAX_ROUNDS`](crate::MAX_ROUNDS).
+    Rounds(u32),
+    /// The custom [`Pipeline`](crate::Pipeline) cannot be used; the message says why.
+    Pipeline(&'static str),
+    /// The [`PaddingPolicy`](crate::PaddingPolicy) cannot be used; the message says why.
+    Padding(&'static str),
+    /// The key id is longer than [`MAX_KEY_ID_LEN`](crate::MAX_KEY_ID_LEN) bytes.
+    KeyId(usize),
+    /// The key check cannot be used; the message says why.
+    KeyCheck(&'static str),
+    /// The [`Cascade`](crate::Cascade) cannot be used; the message says why.
+    Cascade(&'static str),
+    /// PBKDF2 iterations outside [`MIN_PBKDF2_ITERATIONS`](crate::MIN_PBKDF2_ITERATIONS) to
+    /// [`MAX_PBKDF2_ITERATIONS`](crate::MAX_PBKDF2_ITERATIONS).
+    Pbkdf2Iterations(u32),
+    /// An option cannot be recorded in the requested format version.
+    FormatTooOld {
+        /// The offending option.
+        option: &'static str,
+        /// The format version that was requested.
+        format: FormatVersion,
+        /// The oldest format version supporting the option.
+        required: FormatVersion,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MatrixSize(size) => write!(
+                f,
+                "matrix size {size} must be a multiple of 8 between {} and {}",
+                crate::MIN_MATRIX,
+                crate::MAX_MATRIX
+            ),
+            ConfigError::Rounds(rounds) => write!(
+                f,
+                "{rounds} rounds is outside the supported range 1 to {}",
+                crate::MAX_ROUNDS
+            ),
+            ConfigError::Pipeline(reason) => write!(f, "invalid pipeline: {reason}"),
+            ConfigError::Padding(reason) => write!(f, "invalid padding: {reason}"),
+            ConfigError::KeyId(len) => write!(
+                f,
+                "key id of {len} bytes is longer than {}",
+                crate::MAX_KEY_ID_LEN
+            ),
+            ConfigError::KeyCheck(reason) => write!(f, "invalid key check: {reason}"),
+            ConfigError::Cascade(reason) => write!(f, "invalid cascade: {reason}"),
+            ConfigError::Pbkdf2Iterations(iterations) => write!(
+                f,
+                "{iterations} PBKDF2 iterations is outside the range {} to {}",
+                crate::MIN_PBKDF2_ITERATIONS,
+                crate::MAX_PBKDF2_ITERATIONS
+            ),
+            ConfigError::FormatTooOld { option, format, required } => write!(
+                f,
+                "{option} requires format {required:?} but {format:?} was requested"
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// A token that cannot be decrypted with the given key.
+///
+/// Returned inside the boxed error of the decrypt functions; downcast the error to tell
+/// it apart from malformed tokens.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, DecryptError};
+///
+/// let cryptor = Cryptor::builder().key_check(true).build().unwrap();
+/// let encrypted = cryptor.encrypt(b"data", "key").unwrap();
+/// let err = cryptor.decrypt(&encrypted, "wrong key").unwrap_err();
+/// assert_eq!(err.downcast_ref::<DecryptError>(), Some(&DecryptError::WrongKey));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptError {
+    /// The token records the fingerprint of, or commits to, a different key.
+    WrongKey,
+    /// The tag of a key-committing token does not match its contents, or the token lacks
+    /// the authentication the [`Cryptor`](crate::Cryptor) requires.
+    AuthenticationFailed,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptError::WrongKey => f.write_str("Wrong Key"),
+            DecryptError::AuthenticationFailed => f.write_str("Authentication Failed"),
+        }
+    }
+}
+
+impl Error for DecryptError {}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use crate::key::Key;
+use crate::Cryptor;
+
+/// Values whose sensitive parts can be encrypted in place.
+///
+/// `String` fields are replaced by their [`Cryptor::encrypt_text`] token, `Vec<u8>`
+/// fields by their [`Cryptor::encrypt`] bytes, and `Option`s are encrypted when present.
+/// With the `derive` feature, `#[derive(EncryptFields)]` implements the trait for a
+/// struct by encrypting every field marked `#[encrypt]`, including nested structs that
+/// derive it too.
+///
+/// A derived implementation changes either every marked field or none: if one fails,
+/// the fields before it are turned back, so a wrong key or a damaged token never leaves
+/// a struct half encrypted. Fields turned back after a failed decryption are encrypted
+/// again into new tokens. Every call encrypts once more, so calling `encrypt_fields`
+/// twice needs two calls to `decrypt_fields`.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use crypt_ro::{Cryptor, EncryptFields, Key};
+///
+/// #[derive(EncryptFields)]
+/// struct Customer {
+///     name: String,
+///     #[encrypt]
+///     ssn: String,
+///     #[encrypt]
+///     phone: Option<String>,
+/// }
+///
+/// let (cryptor, key) = (Cryptor::new(), Key::new("key"));
+/// let mut customer = Customer { name: "Ann".into(), ssn: "123-45-6789".into(), phone: None };
+/// customer.encrypt_fields(&cryptor, &key).unwrap();
+/// assert_ne!(customer.ssn, "123-45-6789");
+///
+/// customer.decrypt_fields(&cryptor, &key).unwrap();
+/// assert_eq!(customer.ssn, "123-45-6789");
+/// # }
+/// ```
+pub trait EncryptFields {
+    /// Encrypts the sensitive parts of `self` in place.
+    fn encrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>>;
+
+    /// Reverses [`EncryptFields::encrypt_fields`].
+    fn decrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>>;
+}
+
+/// Encrypts `fields` in order, decrypting the ones before a failing field again.
+#[doc(hidden)]
+#[cfg(feature = "derive")]
+pub fn encrypt_each(fields: &mut [&mut dyn EncryptFields], cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+    for i in 0..fields.len() {
+        if let Err(e) = fields[i].encrypt_fields(cryptor, key) {
+            for field in fields[..i].iter_mut().rev() {
+                field.decrypt_fields(cryptor, key)?;
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Decrypts `fields` in order, encrypting the ones before a failing field again.
+#[doc(hidden)]
+#[cfg(feature = "derive")]
+pub fn decrypt_each(fields: &mut [&mut dyn EncryptFields], cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+    for i in 0..fields.len() {
+        if let Err(e) = fields[i].decrypt_fields(cryptor, key) {
+            for field in fields[..i].iter_mut().rev() {
+                field.encrypt_fields(cryptor, key)?;
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+impl EncryptFields for String {
+    fn encrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        *self = cryptor.encrypt_text(self, key)?;
+        Ok(())
+    }
+
+    fn decrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        *self = cryptor.decrypt_text(self, key)?;
+        Ok(())
+    }
+}
+
+impl EncryptFields for Vec<u8> {
+    fn encrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        *self = cryptor.encrypt(self, key)?;
+        Ok(())
+    }
+
+    fn decrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        *self = cryptor.decrypt(self, key)?;
+        Ok(())
+    }
+}
+
+impl<T: EncryptFields> EncryptFields for Option<T> {
+    fn encrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        match self {
+            Some(value) => value.encrypt_fields(cryptor, key),
+            None => Ok(()),
+        }
+    }
+
+    fn decrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        match self {
+            Some(value) => value.decrypt_fields(cryptor, key),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: EncryptFields + ?Sized> EncryptFields for Box<T> {
+    fn encrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        (**self).encrypt_fields(cryptor, key)
+    }
+
+    fn decrypt_fields(&mut self, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+        (**self).decrypt_fields(cryptor, key)
+    }
+}
@@ -0,0 +1,152 @@
+use std::error::Error;
+use crate::builder::CryptorBuilder;
+use crate::error::ConfigError;
+use crate::key::AsKey;
+use crate::Cryptor;
+
+/// A [`Cryptor`] whose matrix size is fixed at compile time.
+///
+/// `FixedCryptor<N>` runs the same encryption core as a `Cryptor` with a matrix size of
+/// `N`, so the two produce interchangeable tokens and every format, key derivation and
+/// padding option works the same way. The size is part of the type, so values of
+/// different sizes cannot be mixed up, and a zero matrix size is rejected at compile
+/// time.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, FixedCryptor};
+///
+/// let fixed = FixedCryptor::<32>::new();
+/// let encrypted = fixed.encrypt_text("secret message", "password").unwrap();
+///
+/// // Tokens are interchangeable with the dynamic `Cryptor` of the same size
+/// let decrypted = Cryptor::new().decrypt_text(&encrypted, "password").unwrap();
+/// assert_eq!(decrypted, "secret message");
+/// ```
+///
+/// ```compile_fail
+/// use crypt_ro::FixedCryptor;
+///
+/// let cryptor = FixedCryptor::<0>::new();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedCryptor<const N: usize> {
+    cryptor: Cryptor,
+}
+
+impl<const N: usize> Default for FixedCryptor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FixedCryptor<N> {
+    const MATRIX_IS_VALID: () = assert!(N > 0, "matrix size must be non-zero");
+
+    /// Creates a new `FixedCryptor` with the default configuration and a matrix size of
+    /// `N`, like `Cryptor::new` followed by `set_matrix(N)`.
+    pub fn new() -> Self {
+        let () = Self::MATRIX_IS_VALID;
+        let mut cryptor = Cryptor::new();
+        cryptor.set_matrix(N);
+        Self { cryptor }
+    }
+
+    /// Builds the configuration of `builder` with a matrix size of `N`.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::{Cryptor, FixedCryptor, Kdf};
+    ///
+    /// let fixed = FixedCryptor::<64>::from_builder(Cryptor::builder().kdf(Kdf::Sha256)).unwrap();
+    /// let encrypted = fixed.encrypt(b"data", "key").unwrap();
+    /// assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"data");
+    /// ```
+    pub fn from_builder(builder: CryptorBuilder) -> Result<Self, ConfigError> {
+        let () = Self::MATRIX_IS_VALID;
+        Ok(Self { cryptor: builder.matrix(N).build()? })
+    }
+
+    /// Returns the matrix size `N`.
+    pub const fn matrix(&self) -> usize {
+        N
+    }
+
+    /// Returns the underlying [`Cryptor`].
+    pub fn cryptor(&self) -> &Cryptor {
+        &self.cryptor
+    }
+
+    /// Encrypts raw bytes using the provided key.
+    ///
+    /// # Arguments
+    /// * `data` - The bytes to encrypt
+    /// * `key` - The encryption key
+    ///
+    /// # Returns
+    /// A `Result` containing the encrypted bytes or an error if encryption fails.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::FixedCryptor;
+    ///
+    /// let cryptor = FixedCryptor::<16>::new();
+    /// let encrypted = cryptor.encrypt(b"secret data", "key123").unwrap();
+    /// assert!(!encrypted.is_empty());
+    /// ```
+    pub fn encrypt<K: AsKey + ?Sized>(&self, data: &[u8], key: &K) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.cryptor.encrypt(data, key)
+    }
+
+    /// Encrypts text into a URL-safe base64 string without padding.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::FixedCryptor;
+    ///
+    /// let cryptor = FixedCryptor::<32>::new();
+    /// let encrypted = cryptor.encrypt_text("secret message", "password").unwrap();
+    /// assert!(!encrypted.contains('/'));  // URL-safe
+    /// ```
+    pub fn encrypt_text<K: AsKey + ?Sized>(&self, text: &str, key: &K) -> Result<String, Box<dyn Error>> {
+        self.cryptor.encrypt_text(text, key)
+    }
+
+    /// Decrypts bytes using the provided key.
+    ///
+    /// # Arguments
+    /// * `encoded` - The encrypted bytes to decrypt
+    /// * `key` - The decryption key
+    ///
+    /// # Returns
+    /// A `Result` containing the decrypted bytes or an error if decryption fails.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::FixedCryptor;
+    ///
+    /// let cryptor = FixedCryptor::<32>::new();
+    /// let encrypted = cryptor.encrypt(b"data", "key").unwrap();
+    /// let decrypted = cryptor.decrypt(&encrypted, "key").unwrap();
+    /// assert_eq!(decrypted, b"data");
+    /// ```
+    pub fn decrypt<K: AsKey + ?Sized>(&self, encoded: &[u8], key: &K) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.cryptor.decrypt(encoded, key)
+    }
+
+    /// Decrypts a URL-safe base64 encoded string using the provided key.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::FixedCryptor;
+    ///
+    /// let cryptor = FixedCryptor::<32>::new();
+    /// let encrypted = cryptor.encrypt_text("message", "pass").unwrap();
+    /// let decrypted = cryptor.decrypt_text(&encrypted, "pass").unwrap();
+    /// assert_eq!(decrypted, "message");
+    /// ```
+    pub fn decrypt_text<K: AsKey + ?Sized>(&self, encoded: &str, key: &K) -> Result<String, Box<dyn Error>> {
+        self.cryptor.decrypt_text(encoded, key)
+    }
+}
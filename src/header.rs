@@ -0,0 +1,350 @@
+use std::error::Error;
+use std::io::{self, Read};
+use std::sync::Arc;
+use crate::cascade::Cascade;
+use crate::commit::COMMITMENT_LEN;
+use crate::error::DecryptError;
+use crate::kdf::{Kdf, Salt};
+use crate::key::{Fingerprint, Key};
+use crate::padding::PaddingPolicy;
+use crate::rounds::{Scheme, MAX_ROUNDS};
+use crate::transform::Pipeline;
+
+/// Marks a token that starts with a [`Header`]; legacy tokens carry no header.
+pub const MAGIC: [u8; 3] = *b"CRO";
+
+/// The payload is split into independently keyed segments.
+pub const FLAG_SEGMENTED: u8 = 0b0000_0001;
+
+/// Blocks are transformed independently in [`Chaining::Counter`](crate::Chaining::Counter) mode.
+pub const FLAG_COUNTER: u8 = 0b0000_0010;
+
+/// The payload is a chunked container written by [`SeekableEncryptWriter`](crate::SeekableEncryptWriter).
+pub const FLAG_SEEKABLE: u8 = 0b0000_0100;
+
+/// The payload is a token sealed with the AEAD named in the header, see [`Cascade`].
+pub const FLAG_CASCADE: u8 = 0b0000_1000;
+
+const FIELD_KDF: u8 = 1;
+const FIELD_ROUNDS: u8 = 2;
+const FIELD_PIPELINE: u8 = 3;
+const FIELD_PADDING: u8 = 4;
+const FIELD_KEY_ID: u8 = 5;
+const FIELD_KEY_CHECK: u8 = 6;
+const FIELD_COMMITMENT: u8 = 7;
+const FIELD_CASCADE: u8 = 8;
+
+/// Revision of the token format.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Chaining, Cryptor, FormatVersion};
+///
+/// // The oldest version supporting the configuration is picked by default
+/// let cryptor = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+/// assert_eq!(cryptor.format(), FormatVersion::V2);
+///
+/// // Asking for an older one is an error
+/// assert!(Cryptor::builder().chaining(Chaining::Counter).format(FormatVersion::V1).build().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum FormatVersion {
+    /// The original headerless format shared with the other language ports. Supports
+    /// only chained blocks and the repeating key schedule, and decryption must use the
+    /// same matrix size as encryption.
+    #[default]
+    V1 = 1,
+    /// Tokens start with a header recording the matrix size, chaining mode, key
+    /// derivation function and round count.
+    V2 = 2,
+    /// Adds a key-dependent byte substitution ([`SBox`](crate::SBox)) to every round,
+    /// making the pipeline non-linear.
+    V3 = 3,
+    /// Replaces the floating point shuffles, which never leave an element in place and
+    /// are slightly biased, with an unbiased integer Fisher-Yates shuffle, for the
+    /// message, the blocks and the S-box.
+    V4 = 4,
+    /// Draws the shuffles, S-boxes, subkeys and counter keystream from a ChaCha20-based
+    /// generator keyed by the round key instead of the 64-bit linear congruential
+    /// generator, whose state leaks through a few outputs, and takes the random prefix
+    /// from the operating system.
+    V5 = 5,
+}
+
+impl FormatVersion {
+    /// The newest format version this crate can read and write.
+    pub const LATEST: FormatVersion = FormatVersion::V5;
+
+    fn from_u8(version: u8) -> Option<Self> {
+        match version {
+            2 => Some(FormatVersion::V2),
+            3 => Some(FormatVersion::V3),
+            4 => Some(FormatVersion::V4),
+            5 => Some(FormatVersion::V5),
+            _ => None,
+        }
+    }
+}
+
+/// Self-describing prefix of versioned tokens.
+///
+/// Layout: `MAGIC (3) | version (1) | flags (1) | matrix (2) | fields length (2) | fields`,
+/// integers big endian. Fields are `tag (1) | length (1) | value` entries; a field left at
+/// its default value is omitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: FormatVersion,
+    pub flags: u8,
+    pub matrix: usize,
+    pub kdf: Kdf,
+    /// Random salt of a [`Kdf::Pbkdf2`] derivation, stored with the KDF parameters.
+    pub salt: Option<Salt>,
+    pub rounds: u32,
+    pub pipeline: Option<Pipeline>,
+    /// Informational: decryption does not depend on the sender's padding.
+    pub padding: PaddingPolicy,
+    /// Informational label of the key, see [`CryptorBuilder::key_id`](crate::CryptorBuilder::key_id).
+    pub key_id: Option<String>,
+    /// Fingerprint of the key, see [`CryptorBuilder::key_check`](crate::CryptorBuilder::key_check).
+    pub key_check: Option<Fingerprint>,
+    /// Commitment to the key, see [`CryptorBuilder::key_commitment`](crate::CryptorBuilder::key_commitment).
+    pub commitment: Option<[u8; COMMITMENT_LEN]>,
+    /// AEAD sealing the payload, set together with [`FLAG_CASCADE`].
+    pub cascade: Option<Cascade>,
+}
+
+impl Header {
+    const FIXED_LEN: usize = MAGIC.len() + 6;
+
+    /// Creates a header for `scheme`, written as at least [`FormatVersion::V2`].
+    pub fn new(flags: u8, scheme: Scheme, kdf: Kdf) -> Result<Self, Box<dyn Error>> {
+        let Scheme { format, matrix, rounds, pipeline, padding } = scheme;
+        let version = format.max(FormatVersion::V2);
+        if matrix == 0 || matrix > u16::MAX as usize {
+            return Err("Matrix Size Not Representable In Header".into());
+        }
+        let pipeline = pipeline.filter(|pipeline| *pipeline != Pipeline::standard(version));
+        let salt = kdf.new_salt()?;
+        Ok(Self { version, flags, matrix, kdf, salt, rounds, pipeline, padding, key_id: None, key_check: None, commitment: None, cascade: None })
+    }
+
+    /// Returns `true` if `data` starts with the header magic.
+    pub fn is_present(data: &[u8]) -> bool {
+        data.starts_with(&MAGIC)
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        Scheme {
+            format: self.version,
+            matrix: self.matrix,
+            rounds: self.rounds,
+            pipeline: self.pipeline.clone(),
+            padding: self.padding,
+        }
+    }
+
+    /// Fails with [`DecryptError::WrongKey`] if the header records the fingerprint of
+    /// another key.
+    pub fn check_key(&self, key: &Key) -> Result<(), DecryptError> {
+        match self.key_check {
+            Some(check) if check != key.fingerprint() => Err(DecryptError::WrongKey),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Returns the salt of the key derivation, empty if it takes none.
+    pub fn salt_bytes(&self) -> &[u8] {
+        self.salt.as_ref().map_or(&[], |salt| salt)
+    }
+
+    /// Returns the key block `key` expands to under this header's key derivation.
+    pub fn key_bytes(&self, key: &Key) -> Arc<[u8]> {
+        key.expand(self.kdf, self.salt_bytes(), self.matrix)
+    }
+
+    fn fields(&self) -> Vec<u8> {
+        let mut fields = Vec::new();
+        if self.kdf != Kdf::default() {
+            let kdf = self.kdf.to_bytes(self.salt.as_ref());
+            fields.extend_from_slice(&[FIELD_KDF, kdf.len() as u8]);
+            fields.extend_from_slice(&kdf);
+        }
+        if self.rounds != 1 {
+            fields.extend_from_slice(&[FIELD_ROUNDS, 1, self.rounds as u8]);
+        }
+        if let Some(pipeline) = &self.pipeline {
+            let pipeline = pipeline.to_bytes();
+            fields.extend_from_slice(&[FIELD_PIPELINE, pipeline.len() as u8]);
+            fields.extend_from_slice(&pipeline);
+        }
+        if self.padding != PaddingPolicy::Minimal {
+            let padding = self.padding.to_bytes();
+            fields.extend_from_slice(&[FIELD_PADDING, padding.len() as u8]);
+            fields.extend_from_slice(&padding);
+        }
+        if let Some(key_id) = &self.key_id {
+            fields.extend_from_slice(&[FIELD_KEY_ID, key_id.len() as u8]);
+            fields.extend_from_slice(key_id.as_bytes());
+        }
+        if let Some(key_check) = &self.key_check {
+            fields.extend_from_slice(&[FIELD_KEY_CHECK, key_check.as_bytes().len() as u8]);
+            fields.extend_from_slice(key_check.as_bytes());
+        }
+        if let Some(commitment) = &self.commitment {
+            fields.extend_from_slice(&[FIELD_COMMITMENT, COMMITMENT_LEN as u8]);
+            fields.extend_from_slice(commitment);
+        }
+        if let Some(cascade) = self.cascade {
+            fields.extend_from_slice(&[FIELD_CASCADE, 1, cascade.to_u8()]);
+        }
+        fields
+    }
+
+    /// Returns the serialized length of this header.
+    pub fn encoded_len(&self) -> usize {
+        Self::FIXED_LEN + self.fields().len()
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let fields = self.fields();
+        out.extend_from_slice(&MAGIC);
+        out.push(self.version as u8);
+        out.push(self.flags);
+        out.extend_from_slice(&(self.matrix as u16).to_be_bytes());
+        out.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+        out.extend_from_slice(&fields);
+    }
+
+    /// Parses the header at the start of `data`, returning it with the remaining bytes.
+    pub fn read(data: &[u8]) -> Result<(Self, &[u8]), Box<dyn Error>> {
+        if data.len() < Self::FIXED_LEN || !Self::is_present(data) {
+            return Err("Invalid Token Header".into());
+        }
+        let version = FormatVersion::from_u8(data[3])
+            .ok_or_else(|| format!("Unsupported Token Format Version {}", data[3]))?;
+        let flags = data[4];
+        let matrix = u16::from_be_bytes([data[5], data[6]]) as usize;
+        let fields_len = u16::from_be_bytes([data[7], data[8]]) as usize;
+        if matrix == 0 || data.len() < Self::FIXED_LEN + fields_len {
+            return Err("Invalid Token Header".into());
+        }
+
+        let mut header = Self { version, flags, matrix, kdf: Kdf::default(), salt: None, rounds: 1, pipeline: None, padding: PaddingPolicy::Minimal, key_id: None, key_check: None, commitment: None, cascade: None };
+        let mut fields = &data[Self::FIXED_LEN..Self::FIXED_LEN + fields_len];
+        while !fields.is_empty() {
+            let [tag, len, rest @ ..] = fields else {
+                return Err("Invalid Token Header".into());
+            };
+            let len = *len as usize;
+            if rest.len() < len {
+                return Err("Invalid Token Header".into());
+            }
+            let (value, rest) = rest.split_at(len);
+            match *tag {
+                FIELD_KDF => (header.kdf, header.salt) = Kdf::from_bytes(value)?,
+                FIELD_ROUNDS => match value {
+                    &[rounds] if (1..=MAX_ROUNDS).contains(&(rounds as u32)) => header.rounds = rounds as u32,
+                    _ => return Err("Invalid Round Count".into()),
+                },
+                FIELD_PIPELINE => header.pipeline = Some(Pipeline::from_bytes(value)?),
+                FIELD_PADDING => header.padding = PaddingPolicy::from_bytes(value)?,
+                FIELD_KEY_ID => {
+                    let key_id = std::str::from_utf8(value).map_err(|_| "Invalid Key Id")?;
+                    header.key_id = Some(key_id.to_string());
+                }
+                FIELD_KEY_CHECK => {
+                    header.key_check = Some(Fingerprint::from_bytes(value).ok_or("Invalid Key Check")?);
+                }
+                FIELD_COMMITMENT => {
+                    header.commitment = Some(value.try_into().map_err(|_| "Invalid Key Commitment")?);
+                }
+                FIELD_CASCADE => match value {
+                    &[id] => header.cascade = Some(Cascade::from_u8(id).ok_or("Unknown Cascade Cipher")?),
+                    _ => return Err("Unknown Cascade Cipher".into()),
+                },
+                tag => return Err(format!("Unknown Header Field {tag}").into()),
+            }
+            fields = rest;
+        }
+        if header.pipeline.as_ref().is_some_and(|pipeline| pipeline.validate_format(version).is_some()) {
+            return Err("Invalid Pipeline Description".into());
+        }
+        Ok((header, &data[Self::FIXED_LEN + fields_len..]))
+    }
+
+    /// Reads the raw header bytes from the start of a stream.
+    pub fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; Self::FIXED_LEN];
+        reader.read_exact(&mut bytes)?;
+        let fields_len = u16::from_be_bytes([bytes[7], bytes[8]]) as usize;
+        bytes.resize(Self::FIXED_LEN + fields_len, 0);
+        reader.read_exact(&mut bytes[Self::FIXED_LEN..])?;
+        Ok(bytes)
+    }
+}
+
+/// Segment lengths following a [`FLAG_SEGMENTED`] header.
+///
+/// Layout: `segment size (4) | count (4) | count x length (4)`, all big endian,
+/// followed by the concatenated segments.
+pub struct SegmentTable<'a> {
+    pub segment_size: usize,
+    pub segments: Vec<&'a [u8]>,
+}
+
+impl<'a> SegmentTable<'a> {
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    pub fn write(segment_size: usize, segments: &[Vec<u8>], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        let count = u32::try_from(segments.len()).map_err(|_| "Data too Big")?;
+        out.extend_from_slice(&(segment_size as u32).to_be_bytes());
+        out.extend_from_slice(&count.to_be_bytes());
+        for segment in segments {
+            out.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+        }
+        for segment in segments {
+            out.extend_from_slice(segment);
+        }
+        Ok(())
+    }
+
+    pub fn read(body: &'a [u8]) -> Result<Self, Box<dyn Error>> {
+        if body.len() < 8 {
+            return Err("Invalid Segment Table".into());
+        }
+        let segment_size = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+        let count = u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize;
+        let table_end = count
+            .checked_mul(4)
+            .and_then(|table| table.checked_add(8))
+            .filter(|&end| end <= body.len())
+            .ok_or("Invalid Segment Table")?;
+
+        let mut segments = Vec::with_capacity(count);
+        let mut offset = table_end;
+        for entry in body[8..table_end].chunks_exact(4) {
+            let len = u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]) as usize;
+            let end = offset.checked_add(len).filter(|&end| end <= body.len()).ok_or("Invalid Segment Table")?;
+            segments.push(&body[offset..end]);
+            offset = end;
+        }
+        if offset != body.len() {
+            return Err("Invalid Segment Table".into());
+        }
+        Ok(Self { segment_size, segments })
+    }
+
+    /// Checks the decrypted length of segment `index`; only the last one may be short.
+    pub fn check_plain_len(&self, index: usize, len: usize) -> Result<(), &'static str> {
+        let is_last = index + 1 == self.segments.len();
+        if len > self.segment_size || (!is_last && len != self.segment_size) {
+            return Err("Invalid Segment Length");
+        }
+        Ok(())
+    }
+}
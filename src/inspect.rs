@@ -0,0 +1,298 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use crate::cascade::Cascade;
+use crate::commit::{has_tag, COMMITMENT_LEN};
+use crate::encoding::Encoding;
+use crate::header::{FormatVersion, Header, SegmentTable, FLAG_CASCADE, FLAG_COUNTER, FLAG_SEEKABLE, FLAG_SEGMENTED};
+use crate::kdf::Kdf;
+use crate::key::Fingerprint;
+use crate::padding::PaddingPolicy;
+use crate::seekable::{sealed_len, PARAMS_LEN, TAG_LEN};
+use crate::Cryptor;
+
+/// Bytes of length and random prefix in front of a chained message.
+const CHAINED_OVERHEAD: usize = 10;
+/// Bytes of length prefix in front of a counter-mode message.
+const COUNTER_OVERHEAD: usize = 4;
+/// Bytes of nonce in front of the counter-mode blocks.
+const COUNTER_NONCE: usize = 8;
+/// Bytes of nonce and tag the AEAD of a cascade adds around the inner token.
+const CASCADE_OVERHEAD: usize = 12 + 16;
+
+/// How the payload of a token is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenLayout {
+    /// A single message of [`Chaining::Chained`](crate::Chaining::Chained) blocks.
+    Chained,
+    /// A single message of [`Chaining::Counter`](crate::Chaining::Counter) blocks.
+    Counter,
+    /// Independently keyed segments written by `Cryptor::encrypt_parallel`.
+    Segmented {
+        /// Number of segments.
+        segments: usize,
+    },
+    /// A chunked container written by [`SeekableEncryptWriter`](crate::SeekableEncryptWriter).
+    Seekable {
+        /// Plaintext bytes per chunk.
+        chunk_size: usize,
+    },
+}
+
+/// What a token is, as reported by [`Cryptor::inspect`].
+///
+/// Headerless [`FormatVersion::V1`] tokens do not record their parameters, so only
+/// the format and the payload length bounds are known for them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TokenInfo {
+    /// Format version of the token.
+    pub format: FormatVersion,
+    /// Text encoding the token was given in, or `None` for raw bytes.
+    pub encoding: Option<Encoding>,
+    /// Payload layout.
+    pub layout: TokenLayout,
+    /// Matrix (block) size, if recorded.
+    pub matrix: Option<usize>,
+    /// Number of transformation rounds, if recorded.
+    pub rounds: Option<u32>,
+    /// Key derivation function and its parameters, if recorded.
+    pub kdf: Option<Kdf>,
+    /// Padding policy the sender used.
+    pub padding: PaddingPolicy,
+    /// Key identifier set with [`CryptorBuilder::key_id`](crate::CryptorBuilder::key_id).
+    pub key_id: Option<String>,
+    /// Fingerprint of the key, recorded with
+    /// [`CryptorBuilder::key_check`](crate::CryptorBuilder::key_check).
+    pub key_fingerprint: Option<Fingerprint>,
+    /// Whether the token commits to its key, see
+    /// [`CryptorBuilder::key_commitment`](crate::CryptorBuilder::key_commitment).
+    pub key_commitment: bool,
+    /// AEAD sealing the token, see [`CryptorBuilder::cascade`](crate::CryptorBuilder::cascade).
+    pub cascade: Option<Cascade>,
+    /// Smallest and largest plaintext length consistent with the token size.
+    pub payload_len: RangeInclusive<usize>,
+}
+
+impl Cryptor {
+    /// Describes a token without decrypting it.
+    ///
+    /// `token` may be raw bytes or text in any [`Encoding`]; the encoding is detected.
+    /// Only the header and the token size are examined, so no key is needed.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::{Cryptor, Encoding, FormatVersion, Kdf};
+    ///
+    /// let cryptor = Cryptor::builder().kdf(Kdf::Sha256).key_id("2024-06").build().unwrap();
+    /// let token = cryptor.encrypt_text("message", "key").unwrap();
+    ///
+    /// let info = Cryptor::inspect(token.as_bytes()).unwrap();
+    /// assert_eq!(info.format, FormatVersion::V2);
+    /// assert_eq!(info.encoding, Some(Encoding::Base64UrlNoPad));
+    /// assert_eq!(info.kdf, Some(Kdf::Sha256));
+    /// assert_eq!(info.key_id.as_deref(), Some("2024-06"));
+    /// assert!(info.payload_len.contains(&7));
+    /// ```
+    pub fn inspect(token: &[u8]) -> Result<TokenInfo, Box<dyn Error>> {
+        let (encoding, bytes) = detect_encoding(token);
+        if !Header::is_present(&bytes) {
+            return legacy_info(encoding, &bytes);
+        }
+
+        let (header, body) = Header::read(&bytes)?;
+        let body = match has_tag(&header) {
+            true => &body[..body.len().checked_sub(COMMITMENT_LEN).ok_or("Invalid Token Matrix Length")?],
+            false => body,
+        };
+        // The bounds of a cascade follow from the inner token, whose header mirrors the
+        // outer one without the key fields
+        let body = match header.cascade.filter(|_| header.has_flag(FLAG_CASCADE)) {
+            Some(_) => {
+                let inner = Header::new(header.flags & FLAG_COUNTER, header.scheme(), Kdf::Sha256)?;
+                body.get(CASCADE_OVERHEAD + inner.encoded_len()..).ok_or("Invalid Token Matrix Length")?
+            }
+            None => body,
+        };
+        let (layout, payload_len) = if header.has_flag(FLAG_COUNTER) {
+            let blocks = body.len().checked_sub(COUNTER_NONCE).ok_or("Invalid Token Matrix Length")?;
+            (TokenLayout::Counter, message_bounds(blocks, COUNTER_OVERHEAD, &header)?)
+        } else if header.has_flag(FLAG_SEEKABLE) {
+            seekable_bounds(body, &header)?
+        } else if header.has_flag(FLAG_SEGMENTED) {
+            let table = SegmentTable::read(body)?;
+            let mut min = 0;
+            let mut max = 0;
+            for (i, segment) in table.segments.iter().enumerate() {
+                let bounds = chained_bounds(segment.len(), &header)?;
+                if i + 1 < table.segments.len() {
+                    min = table.segment_size.saturating_add(min);
+                    max = table.segment_size.saturating_add(max);
+                } else {
+                    min += bounds.start();
+                    max += bounds.end();
+                }
+            }
+            (TokenLayout::Segmented { segments: table.segments.len() }, min..=max)
+        } else {
+            (TokenLayout::Chained, chained_bounds(body.len(), &header)?)
+        };
+
+        Ok(TokenInfo {
+            format: header.version,
+            encoding,
+            layout,
+            matrix: Some(header.matrix),
+            rounds: Some(header.rounds),
+            kdf: Some(header.kdf),
+            padding: header.padding,
+            key_id: header.key_id,
+            key_fingerprint: header.key_check,
+            key_commitment: header.commitment.is_some(),
+            cascade: header.cascade,
+            payload_len,
+        })
+    }
+}
+
+/// Returns the encoding `token` is most likely given in and the decoded bytes.
+///
+/// Text decoding to a token with a header wins; otherwise the first encoding that
+/// decodes is assumed, and anything else is taken as raw bytes.
+fn detect_encoding(token: &[u8]) -> (Option<Encoding>, Vec<u8>) {
+    if Header::is_present(token) {
+        return (None, token.to_vec());
+    }
+    let Ok(text) = std::str::from_utf8(token) else {
+        return (None, token.to_vec());
+    };
+    let text = text.trim();
+    let candidates = [Encoding::Hex, Encoding::Base64UrlNoPad, Encoding::Base64Url, Encoding::Base64];
+    let decoded: Vec<_> = candidates
+        .into_iter()
+        .filter(|&encoding| matches_alphabet(encoding, text))
+        .filter_map(|encoding| Some((encoding, encoding.decode(text).ok()?)))
+        .collect();
+    let best = decoded
+        .iter()
+        .position(|(_, bytes)| Header::is_present(bytes))
+        .or((!decoded.is_empty()).then_some(0));
+    match best {
+        Some(i) => {
+            let (encoding, bytes) = decoded.into_iter().nth(i).unwrap();
+            (Some(encoding), bytes)
+        }
+        None => (None, token.to_vec()),
+    }
+}
+
+/// Returns `true` if `text` only uses the characters `encoding` produces.
+fn matches_alphabet(encoding: Encoding, text: &str) -> bool {
+    let padded = text.trim_end_matches('=');
+    let body_matches = |extra: [u8; 2]| {
+        !padded.is_empty() && padded.bytes().all(|b| b.is_ascii_alphanumeric() || extra.contains(&b))
+    };
+    match encoding {
+        Encoding::Hex => !text.is_empty() && text.bytes().all(|b| b.is_ascii_hexdigit()),
+        Encoding::Base64UrlNoPad => padded.len() == text.len() && body_matches(*b"-_"),
+        Encoding::Base64Url => padded.len() != text.len() && body_matches(*b"-_"),
+        Encoding::Base64 => body_matches(*b"+/"),
+    }
+}
+
+/// Describes a headerless token, whose matrix size is unknown.
+fn legacy_info(encoding: Option<Encoding>, bytes: &[u8]) -> Result<TokenInfo, Box<dyn Error>> {
+    let max = bytes.len().checked_sub(2 + CHAINED_OVERHEAD).ok_or("Invalid Token Matrix Length")?;
+    Ok(TokenInfo {
+        format: FormatVersion::V1,
+        encoding,
+        layout: TokenLayout::Chained,
+        matrix: None,
+        rounds: None,
+        kdf: None,
+        padding: PaddingPolicy::Minimal,
+        key_id: None,
+        key_fingerprint: None,
+        key_commitment: false,
+        cascade: None,
+        payload_len: 0..=max,
+    })
+}
+
+/// Bounds of a chained message of `len` bytes, seed included.
+fn chained_bounds(len: usize, header: &Header) -> Result<RangeInclusive<usize>, Box<dyn Error>> {
+    let blocks = len.checked_sub(2).ok_or("Invalid Token Matrix Length")?;
+    message_bounds(blocks, CHAINED_OVERHEAD, header)
+}
+
+/// Finds the plaintext lengths that pad to exactly `blocks` bytes.
+fn message_bounds(blocks: usize, overhead: usize, header: &Header) -> Result<RangeInclusive<usize>, Box<dyn Error>> {
+    let matrix = header.matrix;
+    let max = blocks.checked_sub(overhead).ok_or("Invalid Token Matrix Length")?;
+    // Sealed size of a plaintext of `len` bytes, non-decreasing in `len`
+    let sealed = |len: usize| {
+        header.padding
+            .padded_len(len)
+            .map(|padded| (overhead + padded).div_ceil(matrix) * matrix)
+            .unwrap_or(usize::MAX)
+    };
+    let min = partition_point(max + 1, |len| sealed(len) < blocks);
+    let end = partition_point(max + 1, |len| sealed(len) <= blocks);
+    if !blocks.is_multiple_of(matrix) || min >= end {
+        return Err("Invalid Token Matrix Length".into());
+    }
+    Ok(min..=end - 1)
+}
+
+/// Returns the first value in `0..len` for which `pred` is false, or `len`; `pred` must
+/// hold for a prefix of the range.
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Layout and bounds of a seekable container body.
+fn seekable_bounds(body: &[u8], header: &Header) -> Result<(TokenLayout, RangeInclusive<usize>), Box<dyn Error>> {
+    if body.len() < PARAMS_LEN {
+        return Err("Invalid Token Header".into());
+    }
+    let chunk_size = u32::from_be_bytes(body[..4].try_into().unwrap()) as usize;
+    if chunk_size == 0 {
+        return Err("Invalid Chunk Size".into());
+    }
+    let stored = body.len() - PARAMS_LEN;
+    let stored_chunk_len = sealed_len(header.matrix, chunk_size) + TAG_LEN;
+    let full_chunks = stored.div_ceil(stored_chunk_len).max(1) - 1;
+    let last = stored - full_chunks * stored_chunk_len;
+    let last = chained_bounds(last.checked_sub(TAG_LEN).ok_or("Truncated Chunk")?, header)?;
+    let full = full_chunks * chunk_size;
+    Ok((TokenLayout::Seekable { chunk_size }, full + last.start()..=full + last.end()))
+}
+
+impl fmt::Display for TokenInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn or_unknown<T: fmt::Debug>(value: &Option<T>) -> String {
+            value.as_ref().map_or_else(|| "unknown".to_string(), |value| format!("{value:?}"))
+        }
+        writeln!(f, "format:      {:?}", self.format)?;
+        writeln!(f, "encoding:    {}", self.encoding.map_or_else(|| "raw bytes".to_string(), |e| format!("{e:?}")))?;
+        writeln!(f, "layout:      {:?}", self.layout)?;
+        writeln!(f, "matrix:      {}", or_unknown(&self.matrix))?;
+        writeln!(f, "rounds:      {}", or_unknown(&self.rounds))?;
+        writeln!(f, "kdf:         {}", or_unknown(&self.kdf))?;
+        writeln!(f, "padding:     {:?}", self.padding)?;
+        writeln!(f, "key id:      {}", self.key_id.as_deref().unwrap_or("none"))?;
+        writeln!(f, "fingerprint: {}", self.key_fingerprint.map_or_else(|| "none".to_string(), |f| f.to_string()))?;
+        writeln!(f, "committing:  {}", if self.key_commitment { "yes" } else { "no" })?;
+        writeln!(f, "cascade:     {}", self.cascade.map_or_else(|| "none".to_string(), |c| c.to_string()))?;
+        write!(f, "payload:     {} to {} bytes", self.payload_len.start(), self.payload_len.end())
+    }
+}
@@ -0,0 +1,185 @@
+use std::error::Error;
+use serde_json::Value;
+use crate::key::{AsKey, Key};
+use crate::Cryptor;
+
+/// Prefix marking a JSON string as a leaf encrypted by [`encrypt_json_paths`].
+pub const JSON_PREFIX: &str = "cro:";
+
+/// One step of a JSON path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A member of an object.
+    Member(String),
+    /// An element of an array.
+    Index(usize),
+    /// Every member of an object or element of an array.
+    Wildcard,
+}
+
+/// Parses paths like `$.user.email`, `$.cards[*].number`, `$['odd key'][0]` or `$.*`.
+fn parse_path(path: &str) -> Result<Vec<Segment>, Box<dyn Error>> {
+    let invalid = || format!("Invalid JSON Path {path}");
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let name = &after[..end];
+            segments.push(match name {
+                "" => return Err(invalid().into()),
+                "*" => Segment::Wildcard,
+                name => Segment::Member(name.to_string()),
+            });
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            segments.push(match (inner, quoted) {
+                (_, Some(name)) => Segment::Member(name.to_string()),
+                ("*", None) => Segment::Wildcard,
+                (index, None) => Segment::Index(index.parse().map_err(|_| invalid())?),
+            });
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid().into());
+        }
+    }
+    Ok(segments)
+}
+
+/// Callback receiving each value a path selects.
+pub(crate) type Visitor<'a> = dyn FnMut(&mut Value) -> Result<(), Box<dyn Error>> + 'a;
+
+/// Calls `f` on every value `segments` selects. Missing members and elements select
+/// nothing.
+fn visit(value: &mut Value, segments: &[Segment], f: &mut Visitor<'_>) -> Result<(), Box<dyn Error>> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return f(value);
+    };
+    match (segment, value) {
+        (Segment::Member(name), Value::Object(map)) => match map.get_mut(name) {
+            Some(child) => visit(child, rest, f),
+            None => Ok(()),
+        },
+        (Segment::Index(index), Value::Array(items)) => match items.get_mut(*index) {
+            Some(child) => visit(child, rest, f),
+            None => Ok(()),
+        },
+        (Segment::Wildcard, Value::Object(map)) => map.values_mut().try_for_each(|child| visit(child, rest, f)),
+        (Segment::Wildcard, Value::Array(items)) => items.iter_mut().try_for_each(|child| visit(child, rest, f)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the token of an encrypted leaf.
+pub(crate) fn token(value: &Value) -> Option<&str> {
+    value.as_str()?.strip_prefix(JSON_PREFIX)
+}
+
+/// Returns `true` if `a` and `b` can select the same value, or one a value inside the
+/// other's.
+fn overlaps(a: &[Segment], b: &[Segment]) -> bool {
+    a.iter().zip(b).all(|pair| match pair {
+        (Segment::Wildcard, _) | (_, Segment::Wildcard) => true,
+        (a, b) => a == b,
+    })
+}
+
+/// Applies `f` to every leaf selected by one of `paths`, returning how many it changed.
+///
+/// Paths that can select the same value, or a value and one inside it, are rejected:
+/// encrypting the outer value would hide the inner token from decryption.
+fn apply(
+    value: &mut Value,
+    paths: &[&str],
+    mut f: impl FnMut(&mut Value) -> Result<bool, Box<dyn Error>>,
+) -> Result<usize, Box<dyn Error>> {
+    let parsed = paths.iter().map(|path| parse_path(path)).collect::<Result<Vec<_>, _>>()?;
+    for (i, a) in parsed.iter().enumerate() {
+        if let Some(j) = parsed[i + 1..].iter().position(|b| overlaps(a, b)) {
+            return Err(format!("Overlapping JSON Paths {} and {}", paths[i], paths[i + 1 + j]).into());
+        }
+    }
+    let mut changed = 0;
+    for segments in &parsed {
+        visit(value, segments, &mut |leaf| {
+            changed += f(leaf)? as usize;
+            Ok(())
+        })?;
+    }
+    Ok(changed)
+}
+
+/// Encrypts the values at `paths` in place.
+///
+/// Every selected value, whether a string, number, object or array, is replaced by a
+/// string holding [`JSON_PREFIX`] and the [`Cryptor::encrypt_text`] token of its JSON
+/// text, so [`decrypt_json_paths`] restores it with its type. Paths start at `$` and
+/// select members with `.name` or `['name']`, array elements with `[0]`, and all
+/// members or elements with `.*` or `[*]`. Paths matching nothing are ignored, and
+/// values already encrypted with `cryptor` and `key` are left alone, so partly encrypted
+/// documents can be encrypted again. Other strings starting with [`JSON_PREFIX`] are
+/// encrypted like any value. Paths that can select the same value, or a value and one
+/// nested in it, such as `$.user` and `$.user.email`, are rejected.
+///
+/// Returns the number of values encrypted.
+///
+/// # Example
+/// ```
+/// use crypt_ro::{decrypt_json_paths, encrypt_json_paths, Cryptor};
+/// use serde_json::json;
+///
+/// let cryptor = Cryptor::new();
+/// let mut payload = json!({"user": {"email": "ann@example.com"}, "cards": [{"number": 4111}]});
+/// let paths = ["$.user.email", "$.cards[*].number"];
+///
+/// assert_eq!(encrypt_json_paths(&mut payload, &paths, &cryptor, "key").unwrap(), 2);
+/// assert!(payload["user"]["email"].as_str().unwrap().starts_with("cro:"));
+///
+/// decrypt_json_paths(&mut payload, &paths, &cryptor, "key").unwrap();
+/// assert_eq!(payload, json!({"user": {"email": "ann@example.com"}, "cards": [{"number": 4111}]}));
+/// ```
+pub fn encrypt_json_paths<K: AsKey + ?Sized>(value: &mut Value, paths: &[&str], cryptor: &Cryptor, key: &K) -> Result<usize, Box<dyn Error>> {
+    let key = key.as_key();
+    apply(value, paths, |leaf| match is_encrypted(leaf, cryptor, &key) {
+        true => Ok(false),
+        false => encrypt_leaf(leaf, cryptor, &key).map(|()| true),
+    })
+}
+
+/// Decrypts the values at `paths` encrypted by [`encrypt_json_paths`].
+///
+/// Selected values without [`JSON_PREFIX`] are left alone. Returns the number of values
+/// decrypted.
+pub fn decrypt_json_paths<K: AsKey + ?Sized>(value: &mut Value, paths: &[&str], cryptor: &Cryptor, key: &K) -> Result<usize, Box<dyn Error>> {
+    let key = key.as_key();
+    apply(value, paths, |leaf| decrypt_leaf(leaf, cryptor, &key))
+}
+
+/// Returns `true` if `leaf` holds a token that decrypts to JSON text under `key`,
+/// rather than a plaintext string that merely starts with [`JSON_PREFIX`].
+fn is_encrypted(leaf: &Value, cryptor: &Cryptor, key: &Key) -> bool {
+    token(leaf)
+        .and_then(|token| cryptor.decrypt_text(token, key).ok())
+        .is_some_and(|text| serde_json::from_str::<Value>(&text).is_ok())
+}
+
+/// Replaces `leaf` with the token of its JSON text.
+pub(crate) fn encrypt_leaf(leaf: &mut Value, cryptor: &Cryptor, key: &Key) -> Result<(), Box<dyn Error>> {
+    let token = cryptor.encrypt_text(&serde_json::to_string(leaf)?, key)?;
+    *leaf = Value::String(format!("{JSON_PREFIX}{token}"));
+    Ok(())
+}
+
+pub(crate) fn decrypt_leaf(leaf: &mut Value, cryptor: &Cryptor, key: &Key) -> Result<bool, Box<dyn Error>> {
+    let Some(token) = token(leaf) else {
+        return Ok(false);
+    };
+    *leaf = serde_json::from_str(&cryptor.decrypt_text(token, key)?)?;
+    Ok(true)
+}
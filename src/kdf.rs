@@ -0,0 +1,132 @@
+use std::error::Error;
+use std::ops::RangeInclusive;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::rand::random_bytes;
+use crate::util::generate_password;
+
+/// Fewest PBKDF2 iterations [`CryptorBuilder`](crate::CryptorBuilder) accepts.
+pub const MIN_PBKDF2_ITERATIONS: u32 = 10_000;
+
+/// Most PBKDF2 iterations accepted, from the builder or from a token header. Bounds the
+/// work a forged token can demand from every decryption attempt.
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+pub(crate) const PBKDF2_ITERATIONS: RangeInclusive<u32> = MIN_PBKDF2_ITERATIONS..=MAX_PBKDF2_ITERATIONS;
+
+/// Bytes of the random salt stored with every [`Kdf::Pbkdf2`] token.
+pub(crate) const SALT_LEN: usize = 16;
+
+pub(crate) type Salt = [u8; SALT_LEN];
+
+/// How a key is expanded into the matrix-sized key block.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, Kdf};
+///
+/// let cryptor = Cryptor::builder().kdf(Kdf::Sha256).build().unwrap();
+/// let encrypted = cryptor.encrypt_text("message", "a key longer than the thirty-two byte matrix").unwrap();
+/// assert_eq!(cryptor.decrypt_text(&encrypted, "a key longer than the thirty-two byte matrix").unwrap(), "message");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Kdf {
+    /// Repeats the key until it fills the matrix (the original scheme). Key bytes past
+    /// the matrix size are ignored.
+    #[default]
+    Repeat,
+    /// Expands the key with HMAC-SHA256, so every key byte affects every block byte.
+    Sha256,
+    /// Stretches the key with PBKDF2-HMAC-SHA256 before expanding it, which slows down
+    /// brute-forcing weak passwords. Every token gets a random salt, so each encryption
+    /// pays for the stretching; a [`Key`](crate::Key) only saves it when the same token
+    /// is decrypted again.
+    Pbkdf2 {
+        /// Number of PBKDF2 iterations, from [`MIN_PBKDF2_ITERATIONS`] to
+        /// [`MAX_PBKDF2_ITERATIONS`].
+        iterations: u32,
+    },
+}
+
+impl Kdf {
+    /// Expands `secret` into `matrix` key bytes. Only [`Kdf::Pbkdf2`] uses the `salt`.
+    pub(crate) fn derive(self, secret: &[u8], salt: &[u8], matrix: usize) -> Vec<u8> {
+        match self {
+            Kdf::Repeat => generate_password(matrix, secret),
+            Kdf::Sha256 => expand(secret, matrix),
+            Kdf::Pbkdf2 { iterations } => {
+                let mut seed = [0u8; 32];
+                pbkdf2::pbkdf2_hmac::<Sha256>(secret, salt, iterations, &mut seed);
+                expand(&seed, matrix)
+            }
+        }
+    }
+
+    /// Returns the length of the salt this derivation takes.
+    #[cfg_attr(not(any(feature = "aes-gcm", feature = "config")), allow(dead_code))]
+    pub(crate) fn salt_len(self) -> usize {
+        match self {
+            Kdf::Pbkdf2 { .. } => SALT_LEN,
+            _ => 0,
+        }
+    }
+
+    /// Returns a fresh random salt if this derivation takes one.
+    pub(crate) fn new_salt(self) -> Result<Option<Salt>, Box<dyn Error>> {
+        match self {
+            Kdf::Pbkdf2 { .. } => Ok(Some(random_bytes(SALT_LEN)?.try_into().unwrap())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Serializes the parameters and the salt for a token header.
+    pub(crate) fn to_bytes(self, salt: Option<&Salt>) -> Vec<u8> {
+        match self {
+            Kdf::Repeat => vec![0],
+            Kdf::Sha256 => vec![1],
+            Kdf::Pbkdf2 { iterations } => {
+                let mut bytes = vec![2];
+                bytes.extend_from_slice(&iterations.to_be_bytes());
+                bytes.extend_from_slice(salt.expect("PBKDF2 tokens are salted"));
+                bytes
+            }
+        }
+    }
+
+    /// Parses parameters written by [`Kdf::to_bytes`], rejecting iteration counts
+    /// outside [`MIN_PBKDF2_ITERATIONS`] and [`MAX_PBKDF2_ITERATIONS`].
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<(Self, Option<Salt>), Box<dyn Error>> {
+        match bytes {
+            [0] => Ok((Kdf::Repeat, None)),
+            [1] => Ok((Kdf::Sha256, None)),
+            [2, rest @ ..] if rest.len() == 4 + SALT_LEN => {
+                let (iterations, salt) = rest.split_at(4);
+                let iterations = u32::from_be_bytes(iterations.try_into().unwrap());
+                if !PBKDF2_ITERATIONS.contains(&iterations) {
+                    return Err(format!("Invalid PBKDF2 Iteration Count {iterations}").into());
+                }
+                Ok((Kdf::Pbkdf2 { iterations }, Some(salt.try_into().unwrap())))
+            }
+            _ => Err("Unknown Key Derivation Function".into()),
+        }
+    }
+}
+
+/// Expands `secret` to `len` bytes with HMAC-SHA256 in counter mode.
+fn expand(secret: &[u8], len: usize) -> Vec<u8> {
+    let mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    let mut out = Vec::with_capacity(len + 32);
+    let mut counter = 0u32;
+    while out.len() < len {
+        let mut block = mac.clone();
+        block.update(b"crypt-ro kdf");
+        block.update(&(len as u32).to_be_bytes());
+        block.update(&counter.to_be_bytes());
+        out.extend_from_slice(&block.finalize().into_bytes());
+        counter += 1;
+    }
+    out.truncate(len);
+    out
+}
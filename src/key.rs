@@ -0,0 +1,218 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::kdf::Kdf;
+use crate::transform::RoundCache;
+
+const EXPANDED_CACHE_LEN: usize = 4;
+
+/// Environment variable holding the key read by [`Key::from_env`].
+pub const KEY_ENV_VAR: &str = "CRYPT_RO_KEY";
+
+/// Environment variable naming a file that holds the key read by [`Key::from_env`].
+pub const KEY_FILE_ENV_VAR: &str = "CRYPT_RO_KEY_FILE";
+
+/// Cached expansions, keyed by KDF, salt and matrix size.
+type Expansions = Vec<(Kdf, Vec<u8>, usize, Arc<[u8]>)>;
+
+/// A key prepared for use with a [`Cryptor`](crate::Cryptor).
+///
+/// Every operation expands the key into a matrix-sized key block. `Key` remembers the
+/// most recent expansions, so reusing one `Key` avoids repeating a slow
+/// [`Kdf::Pbkdf2`] derivation when the same token is decrypted again; encryption picks
+/// a fresh salt and always pays for it. It also keeps the S-boxes and shuffle
+/// generator keys of recently used round keys, which depend on the key alone, so
+/// they are not rebuilt for every message. Plain strings can be passed wherever a
+/// `Key` is accepted.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, Kdf, Key};
+///
+/// let cryptor = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).build().unwrap();
+/// let key = Key::new("correct horse battery staple");
+///
+/// let encrypted = cryptor.encrypt_text("message", &key).unwrap();
+/// assert_eq!(cryptor.decrypt_text(&encrypted, &key).unwrap(), "message");
+/// ```
+pub struct Key {
+    secret: Vec<u8>,
+    expanded: Mutex<Expansions>,
+    rounds: RoundCache,
+}
+
+impl Key {
+    /// Creates a key from a password or raw key bytes.
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            expanded: Mutex::new(Vec::new()),
+            rounds: RoundCache::default(),
+        }
+    }
+
+    /// Reads the key from the [`KEY_ENV_VAR`] environment variable, or else from the file
+    /// named by [`KEY_FILE_ENV_VAR`] with trailing whitespace removed.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        if let Some(key) = std::env::var_os(KEY_ENV_VAR) {
+            return Ok(Self::new(key.into_encoded_bytes()));
+        }
+        if let Some(path) = std::env::var_os(KEY_FILE_ENV_VAR) {
+            let key = std::fs::read(&path).map_err(|e| format!("Cannot Read Key File {}: {e}", path.display()))?;
+            return Ok(Self::new(key.trim_ascii_end()));
+        }
+        Err(format!("No Key Set; use {KEY_ENV_VAR} or {KEY_FILE_ENV_VAR}").into())
+    }
+
+    /// Returns a short one-way identifier of the key.
+    ///
+    /// The fingerprint identifies a key in logs and in the headers written by
+    /// [`CryptorBuilder::key_check`](crate::CryptorBuilder::key_check) without revealing
+    /// it. Anyone holding a fingerprint can test guesses of the key against it, so only
+    /// share fingerprints of high-entropy keys.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::Key;
+    ///
+    /// let fingerprint = Key::new("key").fingerprint();
+    /// assert_eq!(fingerprint, Key::new("key").fingerprint());
+    /// assert_ne!(fingerprint, Key::new("other key").fingerprint());
+    /// assert_eq!(fingerprint.to_string().len(), 16);
+    /// ```
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(b"crypt-ro fingerprint");
+        let digest = mac.finalize().into_bytes();
+        Fingerprint(digest[..Fingerprint::LEN].try_into().unwrap())
+    }
+
+    pub(crate) fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    pub(crate) fn rounds(&self) -> &RoundCache {
+        &self.rounds
+    }
+
+    /// Returns the `matrix`-sized key block derived with `kdf` and `salt`.
+    pub(crate) fn expand(&self, kdf: Kdf, salt: &[u8], matrix: usize) -> Arc<[u8]> {
+        if kdf == Kdf::Repeat {
+            return kdf.derive(&self.secret, salt, matrix).into();
+        }
+
+        let cached = |expanded: &Expansions| {
+            expanded.iter()
+                .find(|(k, s, m, _)| *k == kdf && s == salt && *m == matrix)
+                .map(|(_, _, _, bytes)| bytes.clone())
+        };
+        if let Some(bytes) = cached(&self.expanded.lock().unwrap_or_else(|e| e.into_inner())) {
+            return bytes;
+        }
+        // Derive without holding the lock, so threads sharing the key are not serialized
+        // behind a slow KDF
+        let bytes: Arc<[u8]> = kdf.derive(&self.secret, salt, matrix).into();
+        let mut expanded = self.expanded.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bytes) = cached(&expanded) {
+            return bytes;
+        }
+        if expanded.len() == EXPANDED_CACHE_LEN {
+            expanded.remove(0);
+        }
+        expanded.push((kdf, salt.to_vec(), matrix, bytes.clone()));
+        bytes
+    }
+}
+
+impl Clone for Key {
+    fn clone(&self) -> Self {
+        let expanded = self.expanded.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Self {
+            secret: self.secret.clone(),
+            expanded: Mutex::new(expanded),
+            rounds: self.rounds.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key").finish_non_exhaustive()
+    }
+}
+
+impl From<&str> for Key {
+    fn from(secret: &str) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl From<String> for Key {
+    fn from(secret: String) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl From<&[u8]> for Key {
+    fn from(secret: &[u8]) -> Self {
+        Self::new(secret)
+    }
+}
+
+/// One-way identifier of a [`Key`], shown as 16 hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint([u8; Fingerprint::LEN]);
+
+impl Fingerprint {
+    const LEN: usize = 8;
+
+    /// Returns the raw fingerprint bytes.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+
+    /// Parses the bytes of a token header field.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self(bytes.try_into().ok()?))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// Anything that can be used as a key: a [`Key`], a `str` or a `String`.
+pub trait AsKey {
+    /// Returns the key, preparing one if needed.
+    fn as_key(&self) -> Cow<'_, Key>;
+}
+
+impl AsKey for Key {
+    fn as_key(&self) -> Cow<'_, Key> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl AsKey for str {
+    fn as_key(&self) -> Cow<'_, Key> {
+        Cow::Owned(Key::new(self))
+    }
+}
+
+impl AsKey for String {
+    fn as_key(&self) -> Cow<'_, Key> {
+        Cow::Owned(Key::new(self))
+    }
+}
+
+impl<T: AsKey + ?Sized> AsKey for &T {
+    fn as_key(&self) -> Cow<'_, Key> {
+        (**self).as_key()
+    }
+}
@@ -50,23 +50,99 @@
 //! let encrypted = cryptor.encrypt(data, key).unwrap();
 //! let decrypted = cryptor.decrypt(&encrypted, key).unwrap();
 //!
-//! assert_eq!(decrypted.as_bytes(), data);
+//! assert_eq!(decrypted, data);
 //! ```
 
 mod util;
 mod rand;
+#[cfg(feature = "aes-gcm")]
+mod aead;
+mod backend;
+mod builder;
+mod cascade;
+mod commit;
+mod counter;
+mod dotenv;
+#[cfg(feature = "config")]
+mod document;
+mod encoding;
+mod error;
+mod fields;
+mod fixed;
+mod header;
+mod inspect;
+#[cfg(feature = "json")]
+mod json;
+mod kdf;
+mod padding;
+mod permutation;
+mod key;
+mod rounds;
+mod sbox;
+mod transform;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod seekable;
+mod simd;
+#[cfg(feature = "serde")]
+pub mod encrypted;
 
-use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+#[cfg(feature = "aes-gcm")]
+pub use aead::AesGcmCryptor;
+pub use backend::{Algorithm, Decryptor, Encryptor, ENVELOPE_MAGIC};
+pub use cascade::Cascade;
+pub use builder::{CryptorBuilder, MAX_KEY_ID_LEN, MAX_MATRIX, MIN_MATRIX};
+pub use counter::Chaining;
+#[cfg(feature = "config")]
+pub use document::{
+    decrypt_config, decrypt_document, encrypt_config, encrypt_document, DocumentFormat, DOCUMENT_METADATA_KEY,
+};
+pub use dotenv::{apply_env_file, decrypt_env, encrypt_env, load_env_file, ENV_PREFIX, ENV_SUFFIX};
+pub use encoding::Encoding;
+pub use error::{ConfigError, DecryptError};
+pub use fields::EncryptFields;
+#[cfg(feature = "derive")]
+pub use crypt_ro_derive::EncryptFields;
+#[doc(hidden)]
+#[cfg(feature = "derive")]
+pub use fields::{decrypt_each, encrypt_each};
+pub use fixed::FixedCryptor;
+pub use header::FormatVersion;
+pub use inspect::{TokenInfo, TokenLayout};
+#[cfg(feature = "json")]
+pub use json::{decrypt_json_paths, encrypt_json_paths, JSON_PREFIX};
+pub use kdf::{Kdf, MAX_PBKDF2_ITERATIONS, MIN_PBKDF2_ITERATIONS};
+pub use padding::{PaddingPolicy, MAX_PADDING_SIZE};
+pub use key::{AsKey, Fingerprint, Key, KEY_ENV_VAR, KEY_FILE_ENV_VAR};
+pub use rounds::{Preset, MAX_ROUNDS};
+pub use sbox::SBox;
+pub use transform::{KeyContext, Pipeline, Stage, Transform, MAX_STAGES};
+#[cfg(feature = "parallel")]
+pub use parallel::SEGMENT_SIZE;
+pub use seekable::{encrypt_deterministic, SeekableDecryptReader, SeekableEncryptWriter, DEFAULT_CHUNK_SIZE};
+
+use std::borrow::Cow;
 use std::error::Error;
-use std::iter::repeat;
-use crate::rand::SimpleRng;
-use crate::util::{generate_password, mix, shuffle, unmix, unshuffle};
+use std::io::{Cursor, Read};
+use std::ops::Range;
+use crate::commit::{commitment, open_commitment, seal_commitment};
+use crate::counter::{open_counter, open_counter_range, seal_counter};
+use crate::rand::{random_bytes, KeyedRng};
+use crate::cascade::{open_cascade, seal_cascade};
+use crate::transform::RoundCache;
+use crate::header::{Header, SegmentTable, FLAG_CASCADE, FLAG_COUNTER, FLAG_SEEKABLE, FLAG_SEGMENTED};
+use crate::rounds::Scheme;
 
 /// A cryptographic utility for encrypting and decrypting text using a matrix-based transformation.
 ///
 /// The `Cryptor` uses a combination of shuffling, mixing, and matrix operations to obscure the
 /// original text. It supports configurable matrix sizes for the transformation process.
 ///
+/// A `Cryptor` is cheap to clone and can be shared between threads. Use
+/// [`Cryptor::builder`] to configure one with validated settings. With the `serde`
+/// feature it (de)serializes as its [`CryptorBuilder`], and deserializing validates the
+/// configuration.
+///
 /// # Examples
 ///
 /// ```
@@ -77,21 +153,175 @@ use crate::util::{generate_password, mix, shuffle, unmix, unshuffle};
 /// let decrypted = cryptor.decrypt_text(&encrypted, "password").unwrap();
 /// assert_eq!(decrypted, "secret message");
 /// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(into = "CryptorBuilder", try_from = "CryptorBuilder"))]
 pub struct Cryptor {
     matrix: usize,
+    chaining: Chaining,
+    kdf: Kdf,
+    encoding: Encoding,
+    format: FormatVersion,
+    rounds: u32,
+    pipeline: Option<Pipeline>,
+    padding: PaddingPolicy,
+    key_id: Option<String>,
+    key_check: bool,
+    key_commitment: bool,
+    cascade: Option<Cascade>,
+}
+
+impl Default for Cryptor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
-const RANDOM_LEN: usize = 3;
+
 impl Cryptor {
     /// Creates a new `Cryptor` instance with default matrix size (32).
     pub fn new() -> Self {
-        Self { matrix: 32 }
+        Self {
+            matrix: 32,
+            chaining: Chaining::default(),
+            kdf: Kdf::default(),
+            encoding: Encoding::default(),
+            format: FormatVersion::default(),
+            rounds: 1,
+            pipeline: None,
+            padding: PaddingPolicy::default(),
+            key_id: None,
+            key_check: false,
+            key_commitment: false,
+            cascade: None,
+        }
+    }
+
+    /// Returns a [`CryptorBuilder`] for a validated configuration.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::Cryptor;
+    ///
+    /// let cryptor = Cryptor::builder().matrix(64).build().unwrap();
+    /// assert_eq!(cryptor.matrix(), 64);
+    /// ```
+    pub fn builder() -> CryptorBuilder {
+        CryptorBuilder::new()
+    }
+
+    /// Returns the matrix size.
+    pub fn matrix(&self) -> usize {
+        self.matrix
+    }
+
+    /// Returns the chaining mode used for encryption.
+    pub fn chaining(&self) -> Chaining {
+        self.chaining
+    }
+
+    /// Returns the number of transformation rounds.
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    /// Returns the pipeline applied in every round.
+    pub fn pipeline(&self) -> Pipeline {
+        self.scheme().pipeline().into_owned()
+    }
+
+    /// Returns the padding policy used for encryption.
+    pub fn padding(&self) -> PaddingPolicy {
+        self.padding
+    }
+
+    /// Returns the key identifier recorded in the tokens, if any.
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// Returns `true` if tokens record the key's [`Fingerprint`].
+    pub fn key_check(&self) -> bool {
+        self.key_check
+    }
+
+    /// Returns `true` if tokens commit to the key they were encrypted with. Decryption
+    /// then rejects tokens without a commitment.
+    pub fn key_commitment(&self) -> bool {
+        self.key_commitment
+    }
+
+    /// Returns the AEAD layered over the tokens, if any. Decryption then rejects tokens
+    /// not sealed with it.
+    pub fn cascade(&self) -> Option<Cascade> {
+        self.cascade
+    }
+
+    /// Returns the key derivation function used for encryption.
+    pub fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+
+    /// Returns the text encoding.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns the format version of the tokens this `Cryptor` produces.
+    pub fn format(&self) -> FormatVersion {
+        self.format
+    }
+
+    pub(crate) fn scheme(&self) -> Scheme {
+        Scheme {
+            format: self.format,
+            matrix: self.matrix,
+            rounds: self.rounds,
+            pipeline: self.pipeline.clone(),
+            padding: self.padding,
+        }
+    }
+
+    /// Fails with [`DecryptError::AuthenticationFailed`] if a token lacks the
+    /// authentication this configuration writes: the key commitment, or the sealing
+    /// [`Cascade`]. `header` is `None` for headerless tokens.
+    pub(crate) fn check_policy(&self, header: Option<&Header>) -> Result<(), DecryptError> {
+        if self.key_commitment && header.is_none_or(|header| header.commitment.is_none()) {
+            return Err(DecryptError::AuthenticationFailed);
+        }
+        if let Some(cascade) = self.cascade
+            && header.is_none_or(|header| !header.has_flag(FLAG_CASCADE) || header.cascade != Some(cascade))
+        {
+            return Err(DecryptError::AuthenticationFailed);
+        }
+        Ok(())
+    }
+
+    /// Builds the header for a token written with this configuration.
+    pub(crate) fn header(&self, flags: u8, key: &Key) -> Result<Header, Box<dyn Error>> {
+        let mut scheme = self.scheme();
+        // Segments and chunks are only padded to the matrix size
+        if flags & (FLAG_SEGMENTED | FLAG_SEEKABLE) != 0 {
+            if self.cascade.is_some() {
+                return Err("Cascade Only Supports Single Messages".into());
+            }
+            scheme.padding = PaddingPolicy::Minimal;
+        }
+        let mut header = Header::new(flags, scheme, self.kdf)?;
+        if flags & FLAG_CASCADE != 0 {
+            header.cascade = self.cascade;
+        }
+        header.key_id = self.key_id.clone();
+        header.key_check = self.key_check.then(|| key.fingerprint());
+        if self.key_commitment {
+            header.commitment = Some(commitment(key, &header));
+        }
+        Ok(header)
     }
 
     /// Encrypts raw bytes using the provided key.
     ///
     /// # Arguments
     /// * `data` - The bytes to encrypt
-    /// * `key` - The encryption key
+    /// * `key` - The encryption key, either a string or a prepared [`Key`]
     ///
     /// # Returns
     /// A `Result` containing the encrypted bytes or an error if encryption fails.
@@ -104,42 +334,28 @@ impl Cryptor {
     /// let encrypted = cryptor.encrypt(b"secret data", "key123").unwrap();
     /// assert!(!encrypted.is_empty());
     /// ```
-    pub fn encrypt(&self, data: &[u8], key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-        let matrix_size=self.matrix;
-        let pad = (matrix_size - ((10 + data.len()) % matrix_size)) % matrix_size;
-        let key_bytes = generate_password(matrix_size,key.as_bytes());
-        let data_len = data.len();
-        if data_len>u32::MAX as usize {
-            return Err("Data too Big".into());
+    pub fn encrypt<K: AsKey + ?Sized>(&self, data: &[u8], key: &K) -> Result<Vec<u8>, Box<dyn Error>> {
+        let key = key.as_key();
+        if let Some(cascade) = self.cascade {
+            return seal_cascade(self, cascade, data, &key);
         }
-        let data_size = (data_len as u32).to_be_bytes();
-        let random_prefix = SimpleRng::new_with_time_seed().get_random_bytes(6);
-        let seed_random = random_prefix.iter().map(|&b| b as u16).sum::<u16>() as u64;
-        let mut padded_text = Vec::with_capacity(10 + data.len()+pad);
-        padded_text.extend_from_slice(&data_size);
-        padded_text.extend_from_slice(&random_prefix);
-        padded_text.extend_from_slice(data);
-        padded_text.extend(repeat(1).take(pad));
-        let seed_sum: u64 = key_bytes.iter().map(|&b| b as u64).sum();
-        shuffle(&mut padded_text,seed_sum.wrapping_add(seed_random),5);
-
-        let mut matrix = padded_text.chunks_exact_mut(matrix_size).collect::<Vec<_>>();
-        let matrix_len=matrix.len();
-        if matrix_len==0 {
-            return Err("Invalid Padding Length".into());
+        match (self.format, self.chaining) {
+            (FormatVersion::V1, _) => seal(&self.scheme(), data, &key.expand(self.kdf, &[], self.matrix), cached_rounds(&key)),
+            (_, Chaining::Chained) => {
+                let header = self.header(0, &key)?;
+                let mut out = Vec::with_capacity(header.encoded_len() + data.len() + 12 + self.matrix);
+                header.write(&mut out);
+                out.extend_from_slice(&seal(&self.scheme(), data, &header.key_bytes(&key), cached_rounds(&key))?);
+                seal_commitment(&mut out, &header, &key);
+                Ok(out)
+            }
+            (_, Chaining::Counter) => {
+                let header = self.header(FLAG_COUNTER, &key)?;
+                let mut out = seal_counter(&header, data, &header.key_bytes(&key))?;
+                seal_commitment(&mut out, &header, &key);
+                Ok(out)
+            }
         }
-        for i in 0..matrix_len {
-            let seed = matrix.get(i+1)
-                .map(|b| b[0] as u64)
-                .unwrap_or(key_bytes[0] as u64);
-            shuffle(&mut matrix[i], seed.wrapping_add(seed_random),2);
-        }
-
-        mix(matrix_size,&mut padded_text, &key_bytes);
-        let seed_random=(seed_random as u16).to_be_bytes();
-        padded_text.push(seed_random[0]);
-        padded_text.push(seed_random[1]);
-        Ok(padded_text)
     }
 
 
@@ -150,7 +366,8 @@ impl Cryptor {
     /// * `key` - The encryption key
     ///
     /// # Returns
-    /// A `Result` URL-safe base64 string without padding or an error if encryption fails.
+    /// A `Result` containing the text in the configured [`Encoding`] (URL-safe base64
+    /// without padding by default) or an error if encryption fails.
     ///
     /// # Example
     /// ```
@@ -159,12 +376,18 @@ impl Cryptor {
     /// let cryptor = Cryptor::new();
     /// let encrypted = cryptor.encrypt_text("secret message", "password").unwrap();
     /// assert!(!encrypted.contains('/'));  // URL-safe
-    pub fn encrypt_text(&self, text: &str, key: &str) -> Result<String, Box<dyn Error>> {
-        Ok(URL_SAFE.encode(self.encrypt(text.as_bytes(), key)?).trim_end_matches('=').to_string())
+    pub fn encrypt_text<K: AsKey + ?Sized>(&self, text: &str, key: &K) -> Result<String, Box<dyn Error>> {
+        Ok(self.encoding.encode(&self.encrypt(text.as_bytes(), key)?))
     }
 
     /// Decrypts bytes using the provided key.
     ///
+    /// Tokens with a header are decrypted according to it; headerless (format V1) tokens
+    /// use the matrix size and key derivation configured on this `Cryptor`. With
+    /// [`key_commitment`](Cryptor::key_commitment) set, tokens without a commitment are
+    /// rejected with [`DecryptError::AuthenticationFailed`], and so are tokens not sealed
+    /// with the configured [`cascade`](Cryptor::cascade).
+    ///
     /// # Arguments
     /// * `encoded` - The encrypted bytes to decrypt
     /// * `key` - The decryption key
@@ -181,43 +404,28 @@ impl Cryptor {
     /// let decrypted = cryptor.decrypt(&encrypted, "key").unwrap();
     /// assert_eq!(decrypted, b"data");
     /// ```
-    pub fn decrypt(&self, encoded: &Vec<u8>, key: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-        let len=encoded.len();
-        if len < 6 {
-            return Err("Invalid Token Matrix Length".into());
-        }
-
-        let seed_random=u16::from_be_bytes([encoded[len - 2],encoded[len - 1]]) as u64;
-        let mut decoded = encoded[..len-2].to_vec();
-        let len=len-2;
-        let matrix_size=self.matrix;
-
-        let key_bytes = generate_password(matrix_size,key.as_bytes());
-        unmix(matrix_size,&mut decoded, &key_bytes);
-        let mut matrix = decoded.chunks_exact_mut(matrix_size).collect::<Vec<_>>();
-        let matrix_len=matrix.len();
-        for i in (0..matrix_len).rev() {
-            let seed = matrix.get(i+1)
-                .map(|b| b[0] as u64)
-                .unwrap_or(key_bytes[0] as u64);
-            unshuffle(&mut matrix[i], seed.wrapping_add(seed_random),2);
+    pub fn decrypt<K: AsKey + ?Sized>(&self, encoded: &[u8], key: &K) -> Result<Vec<u8>, Box<dyn Error>> {
+        let key = key.as_key();
+        if Header::is_present(encoded) {
+            match Header::read(encoded) {
+                Ok((header, _)) => {
+                    self.check_policy(Some(&header))?;
+                    return decrypt_versioned(encoded, &key, cached_rounds(&key));
+                }
+                Err(e) if self.check_policy(None).is_err() => return Err(e),
+                // A headerless token starts with the magic about once in 16 million
+                Err(e) => return open(&self.scheme(), encoded, &key.expand(self.kdf, &[], self.matrix), cached_rounds(&key))
+                    .map_err(|_| e),
+            }
         }
-
-        let seed_sum: u64 = key_bytes.iter().map(|&b| b as u64).sum();
-        unshuffle(&mut decoded, seed_sum.wrapping_add(seed_random),5);
-
-        let data_size = u32::from_be_bytes([decoded[0], decoded[1], decoded[2], decoded[3]]) as usize;
-        if len < data_size+10 {
-            return Err("Invalid Token Matrix Length".into());
-        }
-        let result_bytes = &decoded[10..data_size+10];
-        Ok(result_bytes.to_vec())
+        self.check_policy(None)?;
+        open(&self.scheme(), encoded, &key.expand(self.kdf, &[], self.matrix), cached_rounds(&key))
     }
 
-    /// Decrypts a URL-safe base64 encoded string using the provided key.
+    /// Decrypts a text token using the provided key.
     ///
     /// # Arguments
-    /// * `encoded` - A URL-safe base64 encoded string to decrypt
+    /// * `encoded` - Text in the configured [`Encoding`] to decrypt
     /// * `key` - The decryption key
     ///
     /// # Returns
@@ -232,15 +440,9 @@ impl Cryptor {
     /// let decrypted = cryptor.decrypt_text(&encrypted, "pass").unwrap();
     /// assert_eq!(decrypted, "message");
     /// ```
-    pub fn decrypt_text(&self, encoded: &str, key: &str) -> Result<String, Box<dyn Error>> {
-        let mut input = encoded.to_string();
-        let padding = input.len() % 4;
-        if padding != 0 {
-            input.push_str(&"=".repeat(4 - padding));
-        }
-
-        let data = URL_SAFE.decode(&input)?;
-        let result = String::from_utf8(self.decrypt(&data, &key)?)?
+    pub fn decrypt_text<K: AsKey + ?Sized>(&self, encoded: &str, key: &K) -> Result<String, Box<dyn Error>> {
+        let data = self.encoding.decode(encoded)?;
+        let result = String::from_utf8(self.decrypt(&data, key)?)?
             .to_string();
         Ok(result)
     }
@@ -248,7 +450,8 @@ impl Cryptor {
     /// Sets the matrix size used for cryptographic operations.
     ///
     /// The matrix size determines how data is chunked and processed during encryption/decryption.
-    /// Must be a positive non-zero value.
+    /// Must be a positive non-zero value; other values are ignored. Prefer
+    /// [`CryptorBuilder::matrix`], which reports invalid sizes.
     ///
     /// # Example
     /// ```
@@ -262,4 +465,186 @@ impl Cryptor {
             self.matrix = size;
         }
     }
-}
\ No newline at end of file
+
+    /// Sets how blocks are chained during encryption.
+    ///
+    /// Decryption follows the mode recorded in the token, whatever this is set to.
+    /// Counter chaining needs a header, so it raises the format to at least
+    /// [`FormatVersion::V2`]. Prefer [`CryptorBuilder::chaining`], which also rejects
+    /// combinations with a custom pipeline.
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::{Chaining, Cryptor};
+    ///
+    /// let mut cryptor = Cryptor::new();
+    /// cryptor.set_chaining(Chaining::Counter);
+    /// let encrypted = cryptor.encrypt(b"hello random access", "key").unwrap();
+    /// assert_eq!(cryptor.decrypt_range(&encrypted, "key", 6..12).unwrap(), b"random");
+    /// ```
+    pub fn set_chaining(&mut self, chaining: Chaining) {
+        self.chaining = chaining;
+        if chaining == Chaining::Counter {
+            self.format = self.format.max(FormatVersion::V2);
+        }
+    }
+
+    /// Decrypts only the bytes in `range` of a [`Chaining::Counter`] token.
+    ///
+    /// Only the blocks overlapping `range` (and the block holding the length) are
+    /// processed, so reading a small slice of a large token is cheap. The range is
+    /// clamped to the plaintext length.
+    ///
+    /// # Arguments
+    /// * `encoded` - The encrypted bytes, produced in counter mode
+    /// * `key` - The decryption key
+    /// * `range` - Byte range of the plaintext to return
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::{Chaining, Cryptor};
+    ///
+    /// let cryptor = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+    /// let encrypted = cryptor.encrypt(b"hello random access", "key").unwrap();
+    /// assert_eq!(cryptor.decrypt_range(&encrypted, "key", 6..12).unwrap(), b"random");
+    /// ```
+    pub fn decrypt_range<K: AsKey + ?Sized>(&self, encoded: &[u8], key: &K, range: Range<usize>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (header, body) = Header::read(encoded)?;
+        self.check_policy(Some(&header))?;
+        if !header.has_flag(FLAG_COUNTER) {
+            return Err("Range Decryption Requires Counter Chaining".into());
+        }
+        if header.has_flag(FLAG_CASCADE) {
+            return Err("Range Decryption Is Not Supported For Cascade Tokens".into());
+        }
+        let key = key.as_key();
+        header.check_key(&key)?;
+        let body = open_commitment(encoded, &header, body, &key)?;
+        let key_bytes = header.key_bytes(&key);
+        open_counter_range(&header.scheme(), body, &key_bytes, range)
+    }
+}
+
+/// Returns the round cache of a prepared key. Keys made from strings for a single
+/// call would only pay for filling it.
+#[allow(clippy::ptr_arg)] // whether the key is borrowed is the point
+fn cached_rounds<'a>(key: &'a Cow<'_, Key>) -> Option<&'a RoundCache> {
+    match key {
+        Cow::Borrowed(key) => Some(key.rounds()),
+        Cow::Owned(_) => None,
+    }
+}
+
+/// Decrypts a token that starts with a [`Header`].
+fn decrypt_versioned(encoded: &[u8], key: &Key, cache: Option<&RoundCache>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (header, rest) = Header::read(encoded)?;
+    header.check_key(key)?;
+    let body = open_commitment(encoded, &header, rest, key)?;
+    if header.has_flag(FLAG_CASCADE) {
+        return open_cascade(&header, &encoded[..encoded.len() - rest.len()], body, key);
+    }
+    let key_bytes = header.key_bytes(key);
+    if header.has_flag(FLAG_COUNTER) {
+        return open_counter(&header.scheme(), body, &key_bytes);
+    }
+    if header.has_flag(FLAG_SEEKABLE) {
+        let mut plain = Vec::new();
+        SeekableDecryptReader::new(Cursor::new(encoded), key)?.read_to_end(&mut plain)?;
+        return Ok(plain);
+    }
+    if !header.has_flag(FLAG_SEGMENTED) {
+        return open(&header.scheme(), body, &key_bytes, cache);
+    }
+
+    let table = SegmentTable::read(body)?;
+    let scheme = header.scheme();
+    // The table is untrusted, and the plaintext is never longer than the token
+    let capacity = table.segment_size.checked_mul(table.segments.len()).ok_or("Invalid Segment Table")?;
+    let mut plain = Vec::with_capacity(capacity.min(encoded.len()));
+    for (i, segment) in table.segments.iter().enumerate() {
+        let segment = open(&scheme, segment, &scheme.subkey(&key_bytes, i as u64), None)?;
+        table.check_plain_len(i, segment.len())?;
+        plain.extend_from_slice(&segment);
+    }
+    Ok(plain)
+}
+
+/// Runs the chained block pipeline: length and random prefix, random padding, then the
+/// scheme's [`Pipeline`] once per round. Round-key material is taken from
+/// `cache` when given.
+fn seal(scheme: &Scheme, data: &[u8], key_bytes: &[u8], cache: Option<&RoundCache>) -> Result<Vec<u8>, Box<dyn Error>> {
+    seal_with(scheme, data, key_bytes, cache, None)
+}
+
+/// [`seal`] drawing the random prefix and padding from `rng` when given, so the output
+/// only depends on the inputs.
+fn seal_with(scheme: &Scheme, data: &[u8], key_bytes: &[u8], cache: Option<&RoundCache>, rng: Option<&mut KeyedRng>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let matrix_size = scheme.matrix;
+    let data_len = data.len();
+    if data_len>u32::MAX as usize {
+        return Err("Data too Big".into());
+    }
+    let padded_len = scheme.padding.padded_len(data_len)?;
+    let pad = padded_len - data_len + (matrix_size - ((10 + padded_len) % matrix_size)) % matrix_size;
+    let data_size = (data_len as u32).to_be_bytes();
+    // The random prefix and the padding come from the same per-message generator
+    let mut random = match rng {
+        Some(rng) => {
+            let mut bytes = vec![0u8; 6 + pad];
+            rng.fill_bytes(&mut bytes);
+            bytes
+        }
+        None => random_bytes(6 + pad)?,
+    };
+    let padding = random.split_off(6);
+    let random_prefix = random;
+    let seed_random = random_prefix.iter().map(|&b| b as u16).sum::<u16>() as u64;
+    let mut padded_text = Vec::with_capacity(10 + data.len()+pad);
+    padded_text.extend_from_slice(&data_size);
+    padded_text.extend_from_slice(&random_prefix);
+    padded_text.extend_from_slice(data);
+    padded_text.extend_from_slice(&padding);
+    if padded_text.len() < matrix_size {
+        return Err("Invalid Padding Length".into());
+    }
+
+    let pipeline = scheme.pipeline();
+    for round_key in scheme.round_keys(key_bytes) {
+        let ctx = KeyContext::new(&round_key, matrix_size, seed_random)
+            .with_cache(cache)
+            .with_format(scheme.format);
+        pipeline.forward(&mut padded_text, &ctx);
+    }
+    let seed_random=(seed_random as u16).to_be_bytes();
+    padded_text.push(seed_random[0]);
+    padded_text.push(seed_random[1]);
+    Ok(padded_text)
+}
+
+/// Reverses [`seal`].
+fn open(scheme: &Scheme, encoded: &[u8], key_bytes: &[u8], cache: Option<&RoundCache>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let matrix_size = scheme.matrix;
+    let len=encoded.len();
+    if len < 6 {
+        return Err("Invalid Token Matrix Length".into());
+    }
+
+    let seed_random=u16::from_be_bytes([encoded[len - 2],encoded[len - 1]]) as u64;
+    let mut decoded = encoded[..len-2].to_vec();
+    let len=len-2;
+
+    let pipeline = scheme.pipeline();
+    for round_key in scheme.round_keys(key_bytes).iter().rev() {
+        let ctx = KeyContext::new(round_key, matrix_size, seed_random)
+            .with_cache(cache)
+            .with_format(scheme.format);
+        pipeline.inverse(&mut decoded, &ctx);
+    }
+
+    let data_size = u32::from_be_bytes([decoded[0], decoded[1], decoded[2], decoded[3]]) as usize;
+    if len < data_size+10 {
+        return Err("Invalid Token Matrix Length".into());
+    }
+    let result_bytes = &decoded[10..data_size+10];
+    Ok(result_bytes.to_vec())
+}
@@ -0,0 +1,113 @@
+use std::error::Error;
+
+/// Largest bucket or fixed size a [`PaddingPolicy`] may use, 16 MiB. Larger sizes would
+/// allocate that much for every message, however short.
+pub const MAX_PADDING_SIZE: u32 = 16 * 1024 * 1024;
+
+/// How much padding [`Cryptor::encrypt`](crate::Cryptor::encrypt) adds to a message.
+///
+/// Ciphertext length otherwise follows the plaintext length to the next matrix boundary.
+/// Padding to buckets or to a fixed size hides it from observers, at the cost of larger
+/// tokens. Padding bytes are random and decryption works the same for every policy. A
+/// policy other than [`PaddingPolicy::Minimal`] is recorded in the token header and needs
+/// [`FormatVersion::V2`](crate::FormatVersion::V2).
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, PaddingPolicy};
+///
+/// let cryptor = Cryptor::builder().padding(PaddingPolicy::Bucket(256)).build().unwrap();
+/// let short = cryptor.encrypt(b"hi", "key").unwrap();
+/// let long = cryptor.encrypt(&[b'x'; 200], "key").unwrap();
+/// assert_eq!(short.len(), long.len());
+/// assert_eq!(Cryptor::new().decrypt(&short, "key").unwrap(), b"hi");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum PaddingPolicy {
+    /// Pads to the next matrix boundary only (the original scheme).
+    #[default]
+    Minimal,
+    /// Pads the plaintext to the next multiple of the given number of bytes, such as 64,
+    /// 256 or 1024, up to [`MAX_PADDING_SIZE`]. Empty messages are padded to one bucket.
+    Bucket(u32),
+    /// Pads every plaintext to exactly the given number of bytes, up to
+    /// [`MAX_PADDING_SIZE`]. Longer messages are rejected.
+    Fixed(u32),
+    /// Pads the plaintext to the next power of two. Leaks only the logarithm of the
+    /// length, but can double the token size.
+    PowerOfTwo,
+    /// Padmé padding: keeps the top bits of the length and rounds off the rest, leaking
+    /// `O(log log n)` bits of the length with at most 12% overhead.
+    Padme,
+}
+
+impl PaddingPolicy {
+    /// Returns the plaintext length after padding a message of `len` bytes, before
+    /// rounding to the matrix size.
+    pub(crate) fn padded_len(self, len: usize) -> Result<usize, Box<dyn Error>> {
+        match self {
+            PaddingPolicy::Minimal => Ok(len),
+            PaddingPolicy::Bucket(size) => {
+                let size = size as usize;
+                len.div_ceil(size).max(1).checked_mul(size).ok_or_else(|| "Data too Big".into())
+            }
+            PaddingPolicy::Fixed(size) if len <= size as usize => Ok(size as usize),
+            PaddingPolicy::Fixed(_) => Err("Message Exceeds Fixed Padding Size".into()),
+            PaddingPolicy::PowerOfTwo => len.max(1).checked_next_power_of_two().ok_or_else(|| "Data too Big".into()),
+            PaddingPolicy::Padme => padme(len).ok_or_else(|| "Data too Big".into()),
+        }
+    }
+
+    /// Serializes the policy for a token header.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        match self {
+            PaddingPolicy::Minimal => vec![0],
+            PaddingPolicy::Bucket(size) => [&[1][..], &size.to_be_bytes()].concat(),
+            PaddingPolicy::Fixed(size) => [&[2][..], &size.to_be_bytes()].concat(),
+            PaddingPolicy::PowerOfTwo => vec![3],
+            PaddingPolicy::Padme => vec![4],
+        }
+    }
+
+    /// Parses a policy written by [`PaddingPolicy::to_bytes`].
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let policy = match bytes {
+            [0] => PaddingPolicy::Minimal,
+            [1, size @ ..] if size.len() == 4 => PaddingPolicy::Bucket(u32::from_be_bytes(size.try_into().unwrap())),
+            [2, size @ ..] if size.len() == 4 => PaddingPolicy::Fixed(u32::from_be_bytes(size.try_into().unwrap())),
+            [3] => PaddingPolicy::PowerOfTwo,
+            [4] => PaddingPolicy::Padme,
+            _ => return Err("Unknown Padding Policy".into()),
+        };
+        match policy.validate() {
+            Some(_) => Err("Unknown Padding Policy".into()),
+            None => Ok(policy),
+        }
+    }
+
+    /// Returns a message describing why the policy cannot be used, if it cannot.
+    pub(crate) fn validate(self) -> Option<&'static str> {
+        match self {
+            PaddingPolicy::Bucket(0) => Some("bucket size must be at least 1"),
+            PaddingPolicy::Fixed(0) => Some("fixed size must be at least 1"),
+            PaddingPolicy::Bucket(size) | PaddingPolicy::Fixed(size) if size > MAX_PADDING_SIZE => {
+                Some("padding size must be at most 16 MiB")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Rounds `len` up, clearing its lowest `e - floor(log2 e) - 1` bits where
+/// `e = floor(log2 len)`.
+fn padme(len: usize) -> Option<usize> {
+    if len < 2 {
+        return Some(len);
+    }
+    let exponent = len.ilog2();
+    let significant = exponent.ilog2() + 1;
+    let mask = (1usize << (exponent - significant)) - 1;
+    Some(len.checked_add(mask)? & !mask)
+}
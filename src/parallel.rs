@@ -0,0 +1,84 @@
+use std::error::Error;
+use rayon::prelude::*;
+use crate::commit::{open_commitment, seal_commitment};
+use crate::header::{Header, SegmentTable, FLAG_SEGMENTED};
+use crate::key::AsKey;
+use crate::{open, seal, Cryptor};
+
+/// Plaintext bytes per segment written by [`Cryptor::encrypt_parallel`].
+pub const SEGMENT_SIZE: usize = 1 << 20;
+
+impl Cryptor {
+    /// Encrypts raw bytes on the rayon thread pool.
+    ///
+    /// The input is split into segments of [`SEGMENT_SIZE`] bytes, each encrypted under
+    /// its own key derived from `key` and the segment index. The output starts with a
+    /// header and a table of segment lengths so that [`Cryptor::decrypt_parallel`] can
+    /// also process every segment concurrently. [`Cryptor::decrypt`] accepts the same
+    /// tokens and processes the segments sequentially. Segments are only padded to the
+    /// matrix size, whatever the [`PaddingPolicy`](crate::PaddingPolicy).
+    ///
+    /// # Example
+    /// ```
+    /// use crypt_ro::Cryptor;
+    ///
+    /// let cryptor = Cryptor::new();
+    /// let data = vec![7u8; 3 * 1024 * 1024];
+    /// let encrypted = cryptor.encrypt_parallel(&data, "key").unwrap();
+    /// let decrypted = cryptor.decrypt_parallel(&encrypted, "key").unwrap();
+    /// assert_eq!(decrypted, data);
+    /// ```
+    pub fn encrypt_parallel<K: AsKey + ?Sized>(&self, data: &[u8], key: &K) -> Result<Vec<u8>, Box<dyn Error>> {
+        let key = key.as_key();
+        let header = self.header(FLAG_SEGMENTED, &key)?;
+        let key_bytes = header.key_bytes(&key);
+
+        let scheme = header.scheme();
+        let segments = data
+            .par_chunks(SEGMENT_SIZE)
+            .enumerate()
+            .map(|(i, segment)| {
+                seal(&scheme, segment, &scheme.subkey(&key_bytes, i as u64), None)
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total: usize = segments.iter().map(Vec::len).sum();
+        let mut out = Vec::with_capacity(header.encoded_len() + 8 + 4 * segments.len() + total);
+        header.write(&mut out);
+        SegmentTable::write(SEGMENT_SIZE, &segments, &mut out)?;
+        seal_commitment(&mut out, &header, &key);
+        Ok(out)
+    }
+
+    /// Decrypts the output of [`Cryptor::encrypt_parallel`] on the rayon thread pool.
+    ///
+    /// The matrix size and key derivation are read from the token header, so they do not
+    /// need to match the ones configured on this `Cryptor`.
+    pub fn decrypt_parallel<K: AsKey + ?Sized>(&self, encoded: &[u8], key: &K) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (header, body) = Header::read(encoded)?;
+        self.check_policy(Some(&header))?;
+        if !header.has_flag(FLAG_SEGMENTED) {
+            return Err("Token Is Not Segmented".into());
+        }
+        let key = key.as_key();
+        header.check_key(&key)?;
+        let body = open_commitment(encoded, &header, body, &key)?;
+        let table = SegmentTable::read(body)?;
+        let key_bytes = header.key_bytes(&key);
+
+        let scheme = header.scheme();
+        let plain = table
+            .segments
+            .par_iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let plain = open(&scheme, segment, &scheme.subkey(&key_bytes, i as u64), None)
+                    .map_err(|e| e.to_string())?;
+                table.check_plain_len(i, plain.len())?;
+                Ok(plain)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(plain.concat())
+    }
+}
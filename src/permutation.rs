@@ -0,0 +1,38 @@
+use crate::rand::KeyedRng;
+use crate::util::{shuffle, shuffle_uniform, shuffle_with, unshuffle, unshuffle_uniform, unshuffle_with};
+
+/// The random generator a permutation is drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PermutationSource {
+    /// The floating point shuffle seeded with the given seed.
+    Legacy(u64),
+    /// The unbiased shuffle seeded with the given seed.
+    Uniform(u64),
+    /// The unbiased shuffle drawn from the given stream of the [`KeyedRng`] keyed by the
+    /// given key.
+    Keyed([u8; 32], u64),
+}
+
+impl PermutationSource {
+    /// Shuffles `data`, drawing a swap at every `step`-th position.
+    pub fn shuffle<T>(self, data: &mut [T], step: usize) {
+        match self {
+            PermutationSource::Legacy(seed) => shuffle(data, seed, step),
+            PermutationSource::Uniform(seed) => shuffle_uniform(data, seed, step),
+            PermutationSource::Keyed(rng_key, stream) => {
+                shuffle_with(data, &mut KeyedRng::new(&rng_key, stream), step)
+            }
+        }
+    }
+
+    /// Reverses [`PermutationSource::shuffle`].
+    pub fn unshuffle<T>(self, data: &mut [T], step: usize) {
+        match self {
+            PermutationSource::Legacy(seed) => unshuffle(data, seed, step),
+            PermutationSource::Uniform(seed) => unshuffle_uniform(data, seed, step),
+            PermutationSource::Keyed(rng_key, stream) => {
+                unshuffle_with(data, &mut KeyedRng::new(&rng_key, stream), step)
+            }
+        }
+    }
+}
@@ -1,5 +1,43 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::error::Error;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 
+/// A stream of random numbers driving the shuffles.
+pub trait RandomSource {
+    /// Generates a random u32 number
+    fn next_u32(&mut self) -> u32;
+
+    /// Generates an unbiased random index in `0..bound` by rejection sampling.
+    ///
+    /// `bound` must be non-zero.
+    fn gen_index(&mut self, bound: usize) -> usize {
+        if let Ok(bound) = u32::try_from(bound) {
+            // Values below 2^32 mod bound would make the low residues more likely
+            let threshold = bound.wrapping_neg() % bound;
+            loop {
+                let value = self.next_u32();
+                if value >= threshold {
+                    return (value % bound) as usize;
+                }
+            }
+        }
+        let bound = bound as u64;
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = (self.next_u32() as u64) << 32 | self.next_u32() as u64;
+            if value >= threshold {
+                return (value % bound) as usize;
+            }
+        }
+    }
+}
+
+/// 64-bit linear congruential generator of the original format.
+///
+/// Its state is recoverable from a few outputs, so it is only kept to read and write
+/// tokens of format versions before [`FormatVersion::V5`](crate::FormatVersion::V5).
 pub struct SimpleRng {
     state: u64,
 }
@@ -10,27 +48,12 @@ impl SimpleRng {
         Self { state: seed }
     }
 
-    pub fn new_with_time_seed() -> Self {
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        Self::new(seed)
-    }
-
     /// Generates a random u32 number
     pub fn next_u32(&mut self) -> u32 {
         self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
         (self.state >> 32) as u32
     }
 
-    /// Generates a random u64 number
-    pub fn next_u64(&mut self) -> u64 {
-        let high = self.next_u32() as u64;
-        let low = self.next_u32() as u64;
-        (high << 32) | low
-    }
-
     /// Generates a random number in the range [0, 1)
     pub fn next_f64(&mut self) -> f64 {
         let val = self.next_u32();
@@ -61,4 +84,156 @@ impl SimpleRng {
 
         bytes
     }
-}
\ No newline at end of file
+}
+
+impl RandomSource for SimpleRng {
+    fn next_u32(&mut self) -> u32 {
+        SimpleRng::next_u32(self)
+    }
+}
+
+/// ChaCha20 keystream used as a cryptographically secure generator.
+///
+/// A 32-byte key derived from key material with [`KeyedRng::derive_key`] selects the
+/// generator and `stream` picks one of 2^64 independent streams under that key, so
+/// opening a new stream per block or message is cheap.
+pub struct KeyedRng {
+    cipher: ChaCha20,
+    block: [u8; 64],
+    pos: usize,
+}
+
+impl KeyedRng {
+    /// Opens stream `stream` of the generator keyed by `key`.
+    pub fn new(key: &[u8; 32], stream: u64) -> Self {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&stream.to_le_bytes());
+        Self {
+            cipher: ChaCha20::new(key.into(), &nonce.into()),
+            block: [0; 64],
+            pos: 64,
+        }
+    }
+
+    /// Derives a generator key from `key_bytes`, separated by `domain`.
+    pub fn derive_key(key_bytes: &[u8], domain: &[u8]) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key_bytes).expect("HMAC accepts any key length");
+        mac.update(b"crypt-ro keyed rng ");
+        mac.update(domain);
+        mac.finalize().into_bytes().into()
+    }
+
+    /// Fills `out` with keystream bytes.
+    pub fn fill_bytes(&mut self, mut out: &mut [u8]) {
+        while !out.is_empty() {
+            if self.pos == self.block.len() {
+                self.block = [0; 64];
+                self.cipher.apply_keystream(&mut self.block);
+                self.pos = 0;
+            }
+            let n = out.len().min(self.block.len() - self.pos);
+            out[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+            self.pos += n;
+            out = &mut out[n..];
+        }
+    }
+}
+
+impl RandomSource for KeyedRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Returns `len` bytes from the operating system's random source.
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Returns a fresh nonce from the operating system's random source.
+pub fn random_nonce() -> Result<u64, Box<dyn Error>> {
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn keystream(key: &[u8; 32], stream: u64, len: usize) -> String {
+        let mut out = vec![0u8; len];
+        KeyedRng::new(key, stream).fill_bytes(&mut out);
+        hex(&out)
+    }
+
+    #[test]
+    fn test_keyed_rng_matches_rfc8439() {
+        // RFC 8439, appendix A.1, test vectors 1 and 2: all-zero key and nonce, blocks 0 and 1
+        assert_eq!(
+            keystream(&[0; 32], 0, 128),
+            "76b8e0ada0f13d90405d6ae55386bd28bdd219b8a08ded1aa836efcc8b770dc7\
+             da41597c5157488d7724e03fb8d84a376a43b8f41518a11cc387b669b2ee6586\
+             9f07e7be5551387a98ba977c732d080dcb0f29a048e3656912c6533e32ee7aed\
+             29b721769ce64e43d57133b074d839d531ed1f28510afb45ace10a1f4b794d6f"
+        );
+    }
+
+    #[test]
+    fn test_keyed_rng_streams() {
+        assert_eq!(
+            keystream(&[0; 32], 1, 64),
+            "ef3fdfd6c61578fbf5cf35bd3dd33b8009631634d21e42ac33960bd138e50d32\
+             111e4caf237ee53ca8ad6426194a88545ddc497a0b466e7d6bbdb0041b2f586b"
+        );
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        assert_eq!(
+            keystream(&key, 7, 64),
+            "480f443fe9dbb832560a751a4ff29af7147f999fdb769ab661f0eb73b36ce7c8\
+             ed24fee9481eb65eba4b4df7f80b0121e0112217e9ca1500f74bcc2075659477"
+        );
+    }
+
+    #[test]
+    fn test_keyed_rng_words_follow_keystream() {
+        let mut rng = KeyedRng::new(&[0; 32], 0);
+        assert_eq!(rng.next_u32(), 0xade0_b876);
+        assert_eq!(rng.next_u32(), 0x903d_f1a0);
+
+        // Reads crossing block boundaries see the same keystream
+        let mut rng = KeyedRng::new(&[0; 32], 0);
+        let mut pieces = Vec::new();
+        for len in [3, 61, 1, 63] {
+            let mut piece = vec![0u8; len];
+            rng.fill_bytes(&mut piece);
+            pieces.extend(piece);
+        }
+        assert_eq!(hex(&pieces), keystream(&[0; 32], 0, 128));
+    }
+
+    #[test]
+    fn test_derive_key_separates_domains() {
+        let a = KeyedRng::derive_key(b"key", b"shuffle");
+        assert_eq!(a, KeyedRng::derive_key(b"key", b"shuffle"));
+        assert_ne!(a, KeyedRng::derive_key(b"key", b"sbox"));
+        assert_ne!(a, KeyedRng::derive_key(b"other key", b"shuffle"));
+    }
+
+    #[test]
+    fn test_gen_index_stays_in_bounds() {
+        let mut rng = KeyedRng::new(&[1; 32], 0);
+        for bound in [1, 2, 3, 7, 255, 1000, u32::MAX as usize] {
+            for _ in 0..100 {
+                assert!(rng.gen_index(bound) < bound);
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::borrow::Cow;
+use crate::header::FormatVersion;
+use crate::padding::PaddingPolicy;
+use crate::sbox::SBox;
+use crate::transform::Pipeline;
+use crate::util::{derive_subkey, derive_subkey_keyed};
+
+/// Largest round count [`CryptorBuilder`](crate::CryptorBuilder) accepts.
+pub const MAX_ROUNDS: u32 = 64;
+
+const ROUND_DOMAIN: u64 = 0x524F_554E_4453_0000;
+
+/// Named round counts trading speed for diffusion.
+///
+/// | Preset     | Rounds | Relative cost |
+/// |------------|--------|---------------|
+/// | `Fast`     | 1      | 1x (original) |
+/// | `Balanced` | 4      | ~4x           |
+/// | `Paranoid` | 12     | ~12x          |
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, Preset};
+///
+/// let cryptor = Cryptor::builder().preset(Preset::Balanced).build().unwrap();
+/// assert_eq!(cryptor.rounds(), 4);
+///
+/// let encrypted = cryptor.encrypt_text("message", "key").unwrap();
+/// assert_eq!(cryptor.decrypt_text(&encrypted, "key").unwrap(), "message");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Preset {
+    /// A single round, identical to the original scheme.
+    #[default]
+    Fast,
+    /// Four rounds.
+    Balanced,
+    /// Twelve rounds.
+    Paranoid,
+}
+
+impl Preset {
+    /// Returns the number of rounds of this preset.
+    pub const fn rounds(self) -> u32 {
+        match self {
+            Preset::Fast => 1,
+            Preset::Balanced => 4,
+            Preset::Paranoid => 12,
+        }
+    }
+}
+
+/// Block layout parameters shared by the encrypt and decrypt pipelines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Scheme {
+    pub format: FormatVersion,
+    pub matrix: usize,
+    pub rounds: u32,
+    /// A custom pipeline, or `None` for [`Pipeline::standard`].
+    pub pipeline: Option<Pipeline>,
+    pub padding: PaddingPolicy,
+}
+
+impl Scheme {
+    /// Returns the pipeline applied in every round.
+    pub fn pipeline(&self) -> Cow<'_, Pipeline> {
+        match &self.pipeline {
+            Some(pipeline) => Cow::Borrowed(pipeline),
+            None => Cow::Owned(Pipeline::standard(self.format)),
+        }
+    }
+
+    /// Returns `true` if randomness is drawn from a [`KeyedRng`](crate::rand::KeyedRng)
+    /// rather than the legacy generator, from [`FormatVersion::V5`] on.
+    pub fn keyed_rng(&self) -> bool {
+        self.format >= FormatVersion::V5
+    }
+
+    /// Returns the S-box of every round, or `None` before [`FormatVersion::V3`].
+    pub fn sboxes<K: AsRef<[u8]>>(&self, round_keys: &[K]) -> Option<Vec<SBox>> {
+        (self.format >= FormatVersion::V3)
+            .then(|| round_keys.iter().map(|key| SBox::for_format(key.as_ref(), self.format)).collect())
+    }
+
+    /// Derives an independent key for `index` from `key_bytes`.
+    pub fn subkey(&self, key_bytes: &[u8], index: u64) -> Vec<u8> {
+        if self.keyed_rng() {
+            derive_subkey_keyed(key_bytes, index)
+        } else {
+            derive_subkey(key_bytes, index)
+        }
+    }
+
+    /// Returns the key of every round. The first round uses `key_bytes` unchanged, so a
+    /// single round matches the original scheme.
+    pub fn round_keys<'k>(&self, key_bytes: &'k [u8]) -> Vec<Cow<'k, [u8]>> {
+        (0..self.rounds.max(1))
+            .map(|round| match round {
+                0 => Cow::Borrowed(key_bytes),
+                round => Cow::Owned(self.subkey(key_bytes, ROUND_DOMAIN | round as u64)),
+            })
+            .collect()
+    }
+}
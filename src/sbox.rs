@@ -0,0 +1,129 @@
+use crate::header::FormatVersion;
+use crate::rand::KeyedRng;
+use crate::util::{shuffle, shuffle_uniform, shuffle_with, subkey_seed};
+
+const SBOX_DOMAIN: u64 = 0x5342_4F58;
+
+/// Key-dependent byte substitution table.
+///
+/// The table is a permutation of all 256 byte values drawn from the key, so every key
+/// yields a bijection that [`SBox::invert`] undoes. Applied between the shuffles and
+/// the XOR mix of [`FormatVersion::V3`](crate::FormatVersion::V3) tokens, it makes the
+/// pipeline non-linear over GF(2).
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::SBox;
+///
+/// let sbox = SBox::new(b"key bytes");
+/// let mut block = *b"some block";
+/// sbox.substitute(&mut block);
+/// sbox.invert(&mut block);
+/// assert_eq!(&block, b"some block");
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct SBox {
+    forward: [u8; 256],
+    inverse: [u8; 256],
+}
+
+impl SBox {
+    /// Derives the table from expanded key bytes.
+    pub fn new(key_bytes: &[u8]) -> Self {
+        let mut forward = Self::identity();
+        shuffle(&mut forward, subkey_seed(key_bytes, SBOX_DOMAIN), 1);
+        Self::from_forward(forward)
+    }
+
+    /// Derives the table used by tokens of `format`: [`SBox::new`] up to
+    /// [`FormatVersion::V3`], then the unbiased shuffles of [`SBox::uniform`] and
+    /// [`SBox::keyed`].
+    pub(crate) fn for_format(key_bytes: &[u8], format: FormatVersion) -> Self {
+        match format {
+            FormatVersion::V1 | FormatVersion::V2 | FormatVersion::V3 => Self::new(key_bytes),
+            FormatVersion::V4 => Self::uniform(key_bytes),
+            FormatVersion::V5 => Self::keyed(key_bytes),
+        }
+    }
+
+    /// Derives the table of [`FormatVersion::V4`] tokens, an unbiased shuffle seeded like
+    /// [`SBox::new`].
+    fn uniform(key_bytes: &[u8]) -> Self {
+        let mut forward = Self::identity();
+        shuffle_uniform(&mut forward, subkey_seed(key_bytes, SBOX_DOMAIN), 1);
+        Self::from_forward(forward)
+    }
+
+    /// Derives the table of [`FormatVersion::V5`] tokens, an unbiased shuffle drawn from
+    /// a [`KeyedRng`].
+    fn keyed(key_bytes: &[u8]) -> Self {
+        let mut forward = Self::identity();
+        let mut rng = KeyedRng::new(&KeyedRng::derive_key(key_bytes, b"sbox"), 0);
+        shuffle_with(&mut forward, &mut rng, 1);
+        Self::from_forward(forward)
+    }
+
+    fn identity() -> [u8; 256] {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    fn from_forward(forward: [u8; 256]) -> Self {
+        let mut inverse = [0u8; 256];
+        for (i, &b) in forward.iter().enumerate() {
+            inverse[b as usize] = i as u8;
+        }
+        Self { forward, inverse }
+    }
+
+    /// Returns the substitute of `byte`.
+    pub fn forward(&self, byte: u8) -> u8 {
+        self.forward[byte as usize]
+    }
+
+    /// Returns the byte whose substitute is `byte`.
+    pub fn inverse(&self, byte: u8) -> u8 {
+        self.inverse[byte as usize]
+    }
+
+    /// Substitutes every byte of `data` in place.
+    pub fn substitute(&self, data: &mut [u8]) {
+        for b in data {
+            *b = self.forward[*b as usize];
+        }
+    }
+
+    /// Reverses [`SBox::substitute`] in place.
+    pub fn invert(&self, data: &mut [u8]) {
+        for b in data {
+            *b = self.inverse[*b as usize];
+        }
+    }
+}
+
+impl std::fmt::Debug for SBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SBox").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbox_follows_format() {
+        let key = b"format key bytes";
+        for format in [FormatVersion::V1, FormatVersion::V2, FormatVersion::V3] {
+            assert!(SBox::for_format(key, format) == SBox::new(key), "{format:?}");
+        }
+
+        // V4 keeps the seed of V3 but draws every swap uniformly
+        let mut uniform = SBox::identity();
+        shuffle_uniform(&mut uniform, subkey_seed(key, SBOX_DOMAIN), 1);
+        let v4 = SBox::for_format(key, FormatVersion::V4);
+        assert_eq!(v4.forward, uniform);
+        assert!(v4 != SBox::new(key));
+        assert!(SBox::for_format(key, FormatVersion::V5) != v4);
+    }
+}
@@ -0,0 +1,370 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::commit::{derived_mac, open_commitment};
+use crate::header::{Header, FLAG_SEEKABLE};
+use crate::key::{AsKey, Key};
+use crate::rand::{random_nonce, KeyedRng};
+use crate::rounds::Scheme;
+use crate::{open, seal_with, Cryptor};
+
+/// Plaintext bytes per chunk used by [`SeekableEncryptWriter::new`].
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+const NONCE_LEN: usize = 8;
+pub(crate) const TAG_LEN: usize = 16;
+/// Chunk size and nonce following the header.
+pub(crate) const PARAMS_LEN: usize = 4 + NONCE_LEN;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Ciphertext length [`seal`] produces for `len` plaintext bytes.
+pub(crate) fn sealed_len(matrix_size: usize, len: usize) -> usize {
+    (10 + len).div_ceil(matrix_size) * matrix_size + 2
+}
+
+/// Keys shared by the writer and the reader of one container.
+struct ChunkKeys {
+    scheme: Scheme,
+    file_key: Vec<u8>,
+    mac: HmacSha256,
+    /// Generator key for the chunk padding of deterministic containers.
+    padding_key: Option<[u8; 32]>,
+}
+
+impl ChunkKeys {
+    /// `preamble` is the header followed by the chunk size and nonce.
+    fn new(preamble: &[u8], header: &Header, key: &Key) -> Self {
+        let nonce = u64::from_be_bytes(preamble[preamble.len() - NONCE_LEN..].try_into().unwrap());
+        let scheme = header.scheme();
+        let file_key = scheme.subkey(&header.key_bytes(key), nonce);
+        let mut mac = derived_mac(key, header.kdf, header.salt_bytes(), header.matrix, b"crypt-ro seekable");
+        mac.update(preamble);
+        Self { scheme, file_key, mac, padding_key: None }
+    }
+
+    fn deterministic(mut self) -> Self {
+        self.padding_key = Some(KeyedRng::derive_key(&self.file_key, b"deterministic padding"));
+        self
+    }
+
+    fn tag(&self, index: u64, last: bool, ciphertext: &[u8]) -> HmacSha256 {
+        let mut mac = self.mac.clone();
+        mac.update(&index.to_be_bytes());
+        mac.update(&[last as u8]);
+        mac.update(ciphertext);
+        mac
+    }
+
+    fn seal_chunk(&self, index: u64, last: bool, plain: &[u8]) -> io::Result<Vec<u8>> {
+        let mut rng = self.padding_key.map(|key| KeyedRng::new(&key, index));
+        let mut chunk = seal_with(&self.scheme, plain, &self.scheme.subkey(&self.file_key, index), None, rng.as_mut())
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let tag = self.tag(index, last, &chunk).finalize().into_bytes();
+        chunk.extend_from_slice(&tag[..TAG_LEN]);
+        Ok(chunk)
+    }
+
+    fn open_chunk(&self, index: u64, last: bool, stored: &[u8]) -> io::Result<Vec<u8>> {
+        if stored.len() < TAG_LEN {
+            return Err(invalid_data("Truncated Chunk"));
+        }
+        let (ciphertext, tag) = stored.split_at(stored.len() - TAG_LEN);
+        self.tag(index, last, ciphertext)
+            .verify_truncated_left(tag)
+            .map_err(|_| invalid_data("Chunk Authentication Failed"))?;
+        open(&self.scheme, ciphertext, &self.scheme.subkey(&self.file_key, index), None)
+            .map_err(|e| invalid_data(&e.to_string()))
+    }
+}
+
+/// Encrypts a stream into a seekable container.
+///
+/// The plaintext is cut into fixed-size chunks, each encrypted under its own key and
+/// authenticated together with its index and whether it is the last chunk, so chunks
+/// cannot be reordered, dropped or truncated unnoticed. [`SeekableDecryptReader`] reads
+/// the result back with random access.
+///
+/// [`finish`](SeekableEncryptWriter::finish) must be called to write the final chunk.
+///
+/// # Example
+/// ```
+/// use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+/// use crypt_ro::{Cryptor, SeekableDecryptReader, SeekableEncryptWriter};
+///
+/// let mut writer = SeekableEncryptWriter::new(&Cryptor::new(), Vec::new(), "key").unwrap();
+/// writer.write_all(b"0123456789").unwrap();
+/// let container = writer.finish().unwrap();
+///
+/// let mut reader = SeekableDecryptReader::new(Cursor::new(container), "key").unwrap();
+/// reader.seek(SeekFrom::Start(6)).unwrap();
+/// let mut tail = String::new();
+/// reader.read_to_string(&mut tail).unwrap();
+/// assert_eq!(tail, "6789");
+/// ```
+pub struct SeekableEncryptWriter<W: Write> {
+    inner: W,
+    keys: ChunkKeys,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    index: u64,
+}
+
+impl<W: Write> SeekableEncryptWriter<W> {
+    /// Creates a writer using [`DEFAULT_CHUNK_SIZE`] and the matrix size of `cryptor`.
+    pub fn new<K: AsKey + ?Sized>(cryptor: &Cryptor, inner: W, key: &K) -> io::Result<Self> {
+        Self::with_chunk_size(cryptor, inner, key, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates a writer with a custom number of plaintext bytes per chunk.
+    pub fn with_chunk_size<K: AsKey + ?Sized>(cryptor: &Cryptor, inner: W, key: &K, chunk_size: usize) -> io::Result<Self> {
+        Self::open(cryptor, inner, &key.as_key(), chunk_size, None)
+    }
+
+    /// Writes the preamble. Without `plaintext` the nonce is random, otherwise it is
+    /// derived from the plaintext and the chunk padding from the chunk keys.
+    fn open(cryptor: &Cryptor, mut inner: W, key: &Key, chunk_size: usize, plaintext: Option<&[u8]>) -> io::Result<Self> {
+        if chunk_size == 0 || chunk_size > u32::MAX as usize - 10 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid Chunk Size"));
+        }
+        let header = cryptor.header(FLAG_SEEKABLE, key)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        // A random salt would make the output differ on every call
+        if plaintext.is_some() && header.salt.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Deterministic Containers Do Not Support PBKDF2"));
+        }
+
+        let mut preamble = Vec::with_capacity(header.encoded_len() + PARAMS_LEN);
+        header.write(&mut preamble);
+        preamble.extend_from_slice(&(chunk_size as u32).to_be_bytes());
+        let nonce = match plaintext {
+            Some(plaintext) => synthetic_nonce(key, &preamble, plaintext),
+            None => random_nonce().map_err(|e| io::Error::other(e.to_string()))?,
+        };
+        preamble.extend_from_slice(&nonce.to_be_bytes());
+        inner.write_all(&preamble)?;
+
+        let keys = ChunkKeys::new(&preamble, &header, key);
+        Ok(Self {
+            inner,
+            keys: if plaintext.is_some() { keys.deterministic() } else { keys },
+            chunk_size,
+            buffer: Vec::with_capacity(chunk_size),
+            index: 0,
+        })
+    }
+
+    fn write_chunk(&mut self, last: bool) -> io::Result<()> {
+        let chunk = self.keys.seal_chunk(self.index, last, &self.buffer)?;
+        self.inner.write_all(&chunk)?;
+        self.buffer.clear();
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Writes the final chunk and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_chunk(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SeekableEncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A full chunk is only written once more data arrives, so that the last one
+        // can be marked as final in `finish`.
+        if self.buffer.len() == self.chunk_size && !buf.is_empty() {
+            self.write_chunk(false)?;
+        }
+        let n = buf.len().min(self.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Nonce of a deterministic container, a MAC over the parameters and the plaintext.
+fn synthetic_nonce(key: &Key, params: &[u8], plaintext: &[u8]) -> u64 {
+    let mut mac = HmacSha256::new_from_slice(key.secret()).expect("HMAC accepts any key length");
+    mac.update(b"crypt-ro synthetic nonce");
+    mac.update(params);
+    mac.update(plaintext);
+    u64::from_be_bytes(mac.finalize().into_bytes()[..NONCE_LEN].try_into().unwrap())
+}
+
+/// Encrypts `plaintext` into a seekable container that only depends on the plaintext,
+/// the key and the settings of `cryptor`.
+///
+/// The nonce is derived from the plaintext and the chunk padding from the chunk keys,
+/// so encrypting the same input twice yields the same bytes. This is what version
+/// control filters need to keep unchanged files clean, at the cost of revealing which
+/// containers hold equal plaintexts. The result is read back with
+/// [`SeekableDecryptReader`].
+///
+/// # Example
+/// ```
+/// use crypt_ro::{encrypt_deterministic, Cryptor, DEFAULT_CHUNK_SIZE};
+///
+/// let cryptor = Cryptor::new();
+/// let first = encrypt_deterministic(&cryptor, Vec::new(), "key", DEFAULT_CHUNK_SIZE, b"hello").unwrap();
+/// let second = encrypt_deterministic(&cryptor, Vec::new(), "key", DEFAULT_CHUNK_SIZE, b"hello").unwrap();
+/// assert_eq!(first, second);
+/// ```
+pub fn encrypt_deterministic<W: Write, K: AsKey + ?Sized>(cryptor: &Cryptor, inner: W, key: &K, chunk_size: usize, plaintext: &[u8]) -> io::Result<W> {
+    let mut writer = SeekableEncryptWriter::open(cryptor, inner, &key.as_key(), chunk_size, Some(plaintext))?;
+    writer.write_all(plaintext)?;
+    writer.finish()
+}
+
+/// Random-access reader over the plaintext of a seekable container.
+///
+/// Only the chunks touched by reads are fetched, authenticated and decrypted, which makes
+/// serving byte ranges of large encrypted files cheap. The last chunk is verified when
+/// the reader is created, so truncated containers are rejected up front.
+///
+/// See [`SeekableEncryptWriter`] for an example.
+pub struct SeekableDecryptReader<R: Read + Seek> {
+    inner: R,
+    keys: ChunkKeys,
+    chunk_size: usize,
+    body_start: u64,
+    body_len: u64,
+    stored_chunk_len: usize,
+    chunk_count: u64,
+    len: u64,
+    pos: u64,
+    current: Option<(u64, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableDecryptReader<R> {
+    /// Opens a container, reading its parameters from the header.
+    pub fn new<K: AsKey + ?Sized>(inner: R, key: &K) -> io::Result<Self> {
+        Self::open(None, inner, &key.as_key())
+    }
+
+    /// Opens a container like [`new`](SeekableDecryptReader::new), rejecting it unless it
+    /// carries the key commitment `cryptor` writes, see
+    /// [`CryptorBuilder::key_commitment`](crate::CryptorBuilder::key_commitment).
+    pub fn with_cryptor<K: AsKey + ?Sized>(cryptor: &Cryptor, inner: R, key: &K) -> io::Result<Self> {
+        Self::open(Some(cryptor), inner, &key.as_key())
+    }
+
+    fn open(cryptor: Option<&Cryptor>, mut inner: R, key: &Key) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(0))?;
+        let mut preamble = Header::read_bytes(&mut inner)?;
+        let header_len = preamble.len();
+        preamble.resize(header_len + PARAMS_LEN, 0);
+        inner.read_exact(&mut preamble[header_len..])?;
+        let (header, rest) = Header::read(&preamble).map_err(|e| invalid_data(&e.to_string()))?;
+        if !header.has_flag(FLAG_SEEKABLE) {
+            return Err(invalid_data("Token Is Not Seekable"));
+        }
+        if let Some(cryptor) = cryptor {
+            cryptor.check_policy(Some(&header)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let chunk_size = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        if chunk_size == 0 {
+            return Err(invalid_data("Invalid Chunk Size"));
+        }
+        header.check_key(key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // The chunk tags authenticate the header, commitment included
+        open_commitment(&preamble, &header, rest, key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let body_start = preamble.len() as u64;
+        let body_len = inner.seek(SeekFrom::End(0))? - body_start;
+        let stored_chunk_len = sealed_len(header.matrix, chunk_size) + TAG_LEN;
+        let chunk_count = body_len.div_ceil(stored_chunk_len as u64).max(1);
+
+        let mut reader = Self {
+            inner,
+            keys: ChunkKeys::new(&preamble, &header, key),
+            body_start,
+            body_len,
+            chunk_size,
+            stored_chunk_len,
+            chunk_count,
+            len: 0,
+            pos: 0,
+            current: None,
+        };
+        let last = reader.load_chunk(chunk_count - 1)?.len() as u64;
+        reader.len = (chunk_count - 1) * chunk_size as u64 + last;
+        Ok(reader)
+    }
+
+    /// Returns the plaintext length.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the plaintext is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn load_chunk(&mut self, index: u64) -> io::Result<&[u8]> {
+        if self.current.as_ref().is_none_or(|(loaded, _)| *loaded != index) {
+            let last = index + 1 == self.chunk_count;
+            let offset = index * self.stored_chunk_len as u64;
+            self.inner.seek(SeekFrom::Start(self.body_start + offset))?;
+
+            // The chunk size comes from the untrusted preamble, the stream length does not
+            let remaining = self.body_len.saturating_sub(offset);
+            let mut stored = Vec::with_capacity(remaining.min(self.stored_chunk_len as u64) as usize);
+            (&mut self.inner).take(self.stored_chunk_len as u64).read_to_end(&mut stored)?;
+            if !last && stored.len() != self.stored_chunk_len {
+                return Err(invalid_data("Truncated Chunk"));
+            }
+
+            let plain = self.keys.open_chunk(index, last, &stored)?;
+            if plain.len() > self.chunk_size || (!last && plain.len() != self.chunk_size) {
+                return Err(invalid_data("Invalid Chunk Length"));
+            }
+            self.current = Some((index, plain));
+        }
+        Ok(&self.current.as_ref().unwrap().1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableDecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+        let index = self.pos / self.chunk_size as u64;
+        let offset = (self.pos % self.chunk_size as u64) as usize;
+        let chunk = self.load_chunk(index)?;
+
+        let n = buf.len().min(chunk.len() - offset);
+        buf[..n].copy_from_slice(&chunk[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableDecryptReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+        })?;
+        Ok(self.pos)
+    }
+}
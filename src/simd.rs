@@ -0,0 +1,261 @@
+//! Wide XOR kernels behind the mixing layer.
+//!
+//! Mixing XORs every block with its predecessor, which is where large payloads spend
+//! most of their time. This module provides interchangeable kernels for that XOR:
+//! a byte-by-byte reference implementation, a portable word-wide one, and SSE2/AVX2
+//! versions on x86 selected at runtime. All backends produce bit-identical output.
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::sync::OnceLock;
+
+/// An implementation of the XOR kernel used by [`mix`] and [`unmix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// Byte-by-byte reference implementation.
+    Scalar,
+    /// Portable implementation working on 64-bit words.
+    Word,
+    /// 128-bit SSE2 implementation (x86 only).
+    Sse2,
+    /// 256-bit AVX2 implementation (x86 only).
+    Avx2,
+}
+
+impl Backend {
+    /// Every backend, from slowest to fastest.
+    pub const ALL: [Backend; 4] = [Backend::Scalar, Backend::Word, Backend::Sse2, Backend::Avx2];
+
+    /// Returns the fastest backend supported by the running CPU.
+    ///
+    /// Detection runs once and is cached for the lifetime of the process.
+    pub fn detect() -> Self {
+        static DETECTED: OnceLock<Backend> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            Self::ALL
+                .into_iter()
+                .rev()
+                .find(|backend| backend.is_available())
+                .unwrap_or(Backend::Scalar)
+        })
+    }
+
+    /// Returns `true` if this backend can run on the current CPU.
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Scalar | Backend::Word => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Backend::Sse2 | Backend::Avx2 => false,
+        }
+    }
+
+    fn kernel(self) -> fn(&mut [u8], &[u8]) {
+        assert!(self.is_available(), "{self:?} is not supported on this CPU");
+        match self {
+            Backend::Scalar => xor_scalar,
+            Backend::Word => xor_words,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => |dst, src| unsafe { xor_sse2(dst, src) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => |dst, src| unsafe { xor_avx2(dst, src) },
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Backend::Sse2 | Backend::Avx2 => unreachable!(),
+        }
+    }
+}
+
+/// XORs `src` into `dst` byte by byte, up to the shorter of the two lengths.
+///
+/// # Panics
+/// Panics if `backend` is not available on the current CPU.
+pub fn xor_into(backend: Backend, dst: &mut [u8], src: &[u8]) {
+    backend.kernel()(dst, src)
+}
+
+/// Chains every full block of `buf` to its predecessor, starting from `key`.
+///
+/// Bytes after the last full block are left untouched.
+///
+/// # Panics
+/// Panics if `backend` is not available on the current CPU.
+pub fn mix(backend: Backend, block_size: usize, buf: &mut [u8], key: &[u8]) {
+    let xor = backend.kernel();
+    let blocks = buf.len() / block_size;
+    if blocks == 0 {
+        return;
+    }
+
+    xor(&mut buf[..block_size], key);
+    for i in 1..blocks {
+        let (head, tail) = buf.split_at_mut(i * block_size);
+        xor(&mut tail[..block_size], &head[(i - 1) * block_size..]);
+    }
+}
+
+/// Reverses [`mix`].
+///
+/// # Panics
+/// Panics if `backend` is not available on the current CPU.
+pub fn unmix(backend: Backend, block_size: usize, buf: &mut [u8], key: &[u8]) {
+    let xor = backend.kernel();
+    let blocks = buf.len() / block_size;
+    if blocks == 0 {
+        return;
+    }
+
+    for i in (1..blocks).rev() {
+        let (head, tail) = buf.split_at_mut(i * block_size);
+        xor(&mut tail[..block_size], &head[(i - 1) * block_size..]);
+    }
+    xor(&mut buf[..block_size], key);
+}
+
+fn xor_scalar(dst: &mut [u8], src: &[u8]) {
+    for (byte, other) in dst.iter_mut().zip(src) {
+        *byte ^= other;
+    }
+}
+
+fn xor_words(dst: &mut [u8], src: &[u8]) {
+    let len = dst.len().min(src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+    let mut dst_words = dst.chunks_exact_mut(8);
+    let mut src_words = src.chunks_exact(8);
+
+    for (d, s) in (&mut dst_words).zip(&mut src_words) {
+        let word = u64::from_ne_bytes(d.try_into().unwrap()) ^ u64::from_ne_bytes(s.try_into().unwrap());
+        d.copy_from_slice(&word.to_ne_bytes());
+    }
+    xor_scalar(dst_words.into_remainder(), src_words.remainder());
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn xor_sse2(dst: &mut [u8], src: &[u8]) {
+    let len = dst.len().min(src.len());
+    let lanes = len / 16;
+
+    for i in 0..lanes {
+        // SAFETY: `i * 16 + 16 <= len`, and unaligned loads/stores are used.
+        unsafe {
+            let d = dst.as_mut_ptr().add(i * 16) as *mut __m128i;
+            let s = src.as_ptr().add(i * 16) as *const __m128i;
+            _mm_storeu_si128(d, _mm_xor_si128(_mm_loadu_si128(d), _mm_loadu_si128(s)));
+        }
+    }
+    xor_words(&mut dst[lanes * 16..len], &src[lanes * 16..len]);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn xor_avx2(dst: &mut [u8], src: &[u8]) {
+    let len = dst.len().min(src.len());
+    let lanes = len / 32;
+
+    for i in 0..lanes {
+        // SAFETY: `i * 32 + 32 <= len`, and unaligned loads/stores are used.
+        unsafe {
+            let d = dst.as_mut_ptr().add(i * 32) as *mut __m256i;
+            let s = src.as_ptr().add(i * 32) as *const __m256i;
+            _mm256_storeu_si256(d, _mm256_xor_si256(_mm256_loadu_si256(d), _mm256_loadu_si256(s)));
+        }
+    }
+    // SAFETY: AVX2 support implies SSE2 support.
+    unsafe { xor_sse2(&mut dst[lanes * 32..len], &src[lanes * 32..len]) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    fn available_backends() -> impl Iterator<Item = Backend> {
+        Backend::ALL.into_iter().filter(|backend| backend.is_available())
+    }
+
+    #[test]
+    fn test_scalar_and_word_always_available() {
+        assert!(Backend::Scalar.is_available());
+        assert!(Backend::Word.is_available());
+        assert!(Backend::detect().is_available());
+    }
+
+    #[test]
+    fn test_xor_into_matches_scalar() {
+        for len in [0, 1, 7, 8, 15, 16, 31, 32, 33, 63, 64, 65, 100, 1000, 4099] {
+            let src = pseudo_random_bytes(len, 1);
+            let dst = pseudo_random_bytes(len, 2);
+
+            let mut expected = dst.clone();
+            xor_into(Backend::Scalar, &mut expected, &src);
+
+            for backend in available_backends() {
+                let mut actual = dst.clone();
+                xor_into(backend, &mut actual, &src);
+                assert_eq!(actual, expected, "{backend:?} differs for length {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_xor_into_uses_shorter_length() {
+        let src = pseudo_random_bytes(40, 3);
+        for backend in available_backends() {
+            let mut dst = vec![0u8; 100];
+            xor_into(backend, &mut dst, &src);
+            assert_eq!(&dst[..40], &src[..]);
+            assert!(dst[40..].iter().all(|&b| b == 0), "{backend:?} wrote past the source");
+        }
+    }
+
+    #[test]
+    fn test_mix_unmix_match_scalar() {
+        for block_size in [1, 3, 8, 16, 31, 32, 33, 64, 128, 1000] {
+            let key = pseudo_random_bytes(block_size, block_size as u64);
+            for blocks in [0, 1, 2, 5, 64] {
+                // Include a partial trailing block, which must be left untouched
+                let data = pseudo_random_bytes(block_size * blocks + block_size / 2, 4);
+
+                let mut mixed = data.clone();
+                mix(Backend::Scalar, block_size, &mut mixed, &key);
+                let mut unmixed = data.clone();
+                unmix(Backend::Scalar, block_size, &mut unmixed, &key);
+
+                for backend in available_backends() {
+                    let mut actual = data.clone();
+                    mix(backend, block_size, &mut actual, &key);
+                    assert_eq!(actual, mixed, "{backend:?} mix differs for block {block_size} x {blocks}");
+                    unmix(backend, block_size, &mut actual, &key);
+                    assert_eq!(actual, data, "{backend:?} does not round trip for block {block_size} x {blocks}");
+
+                    let mut actual = data.clone();
+                    unmix(backend, block_size, &mut actual, &key);
+                    assert_eq!(actual, unmixed, "{backend:?} unmix differs for block {block_size} x {blocks}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mix_chains_blocks() {
+        let key = [0x0F; 4];
+        let mut data = vec![0x01, 0x02, 0x03, 0x04, 0xF0, 0xF0, 0xF0, 0xF0];
+        mix(Backend::Scalar, 4, &mut data, &key);
+        assert_eq!(data, [0x0E, 0x0D, 0x0C, 0x0B, 0xFE, 0xFD, 0xFC, 0xFB]);
+    }
+}
@@ -0,0 +1,473 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use crate::header::FormatVersion;
+use crate::permutation::PermutationSource;
+use crate::rand::KeyedRng;
+use crate::sbox::SBox;
+use crate::util::{mix, unmix};
+
+/// Most stages a [`Pipeline`] may hold.
+pub const MAX_STAGES: usize = 32;
+
+/// Round keys whose [`RoundMaterial`] a [`Key`](crate::Key) keeps.
+const ROUND_CACHE_LEN: usize = 16;
+
+/// Key material available to a [`Transform`] during one round.
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{KeyContext, Stage, Transform};
+///
+/// let ctx = KeyContext::new(&[7; 16], 16, 42);
+/// let mut data = [1u8; 32];
+/// Stage::Mix.forward(&mut data, &ctx);
+/// Stage::Mix.inverse(&mut data, &ctx);
+/// assert_eq!(data, [1u8; 32]);
+/// ```
+pub struct KeyContext<'a> {
+    key: &'a [u8],
+    matrix: usize,
+    seed: u64,
+    material: Arc<RoundMaterial>,
+    format: FormatVersion,
+}
+
+impl<'a> KeyContext<'a> {
+    /// Creates a context for the round key `key`, the matrix size and the per-message
+    /// random seed.
+    ///
+    /// # Panics
+    /// If `matrix` is 0 or `key` is not `matrix` bytes long.
+    pub fn new(key: &'a [u8], matrix: usize, seed: u64) -> Self {
+        assert!(matrix > 0, "matrix size must not be 0");
+        assert_eq!(key.len(), matrix, "round key must be matrix bytes long");
+        Self { key, matrix, seed, material: Arc::default(), format: FormatVersion::V1 }
+    }
+
+    /// Returns the round key.
+    pub fn key(&self) -> &'a [u8] {
+        self.key
+    }
+
+    /// Returns the matrix (block) size.
+    pub fn matrix(&self) -> usize {
+        self.matrix
+    }
+
+    /// Returns the per-message random seed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the S-box of the round key, derived on first use.
+    pub fn sbox(&self) -> &SBox {
+        let slot = match self.format {
+            FormatVersion::V1 | FormatVersion::V2 | FormatVersion::V3 => &self.material.sbox,
+            FormatVersion::V4 => &self.material.uniform_sbox,
+            FormatVersion::V5 => &self.material.keyed_sbox,
+        };
+        slot.get_or_init(|| SBox::for_format(self.key, self.format))
+    }
+
+    /// Reuses the S-boxes and generator keys of the round key from `cache`.
+    pub(crate) fn with_cache(mut self, cache: Option<&RoundCache>) -> Self {
+        if let Some(cache) = cache {
+            self.material = cache.get(self.key);
+        }
+        self
+    }
+
+    /// Derives the S-box and draws the unbiased shuffles as tokens of `format` do, from
+    /// a [`KeyedRng`] keyed by the round key from [`FormatVersion::V5`] on.
+    pub(crate) fn with_format(mut self, format: FormatVersion) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn keyed_rng(&self) -> bool {
+        self.format >= FormatVersion::V5
+    }
+
+    /// Returns the generator of a whole-message shuffle.
+    fn message_source(&self, uniform: bool) -> PermutationSource {
+        match (uniform, self.keyed_rng()) {
+            (false, _) => PermutationSource::Legacy(key_seed(self)),
+            (true, false) => PermutationSource::Uniform(key_seed(self)),
+            (true, true) => PermutationSource::Keyed(self.rng_key(&self.material.shuffle_key, b"shuffle"), self.seed),
+        }
+    }
+
+    /// Returns the [`KeyedRng`] key of the round key for `domain`, derived on first use.
+    fn rng_key(&self, slot: &OnceLock<[u8; 32]>, domain: &[u8]) -> [u8; 32] {
+        *slot.get_or_init(|| KeyedRng::derive_key(self.key, domain))
+    }
+}
+
+/// The parts of a round that only depend on the round key, derived on first use.
+///
+/// The shuffles themselves also depend on the random seed of every message, so they
+/// are not worth keeping.
+#[derive(Debug, Default)]
+pub(crate) struct RoundMaterial {
+    sbox: OnceLock<SBox>,
+    uniform_sbox: OnceLock<SBox>,
+    keyed_sbox: OnceLock<SBox>,
+    shuffle_key: OnceLock<[u8; 32]>,
+    block_shuffle_key: OnceLock<[u8; 32]>,
+}
+
+type RoundEntries = Vec<(Box<[u8]>, Arc<RoundMaterial>)>;
+
+/// [`RoundMaterial`] of the most recently used round keys of a [`Key`](crate::Key),
+/// most recent last.
+#[derive(Debug, Default)]
+pub(crate) struct RoundCache {
+    entries: Mutex<RoundEntries>,
+}
+
+impl RoundCache {
+    /// Returns the material of `round_key`, adding an empty entry on a miss.
+    pub fn get(&self, round_key: &[u8]) -> Arc<RoundMaterial> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = entries.iter().position(|(key, _)| **key == *round_key) {
+            let entry = entries.remove(pos);
+            let material = entry.1.clone();
+            entries.push(entry);
+            return material;
+        }
+        let material = Arc::new(RoundMaterial::default());
+        if entries.len() == ROUND_CACHE_LEN {
+            entries.remove(0);
+        }
+        entries.push((round_key.into(), material.clone()));
+        material
+    }
+}
+
+impl Clone for RoundCache {
+    fn clone(&self) -> Self {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Self { entries: Mutex::new(entries) }
+    }
+}
+
+/// An invertible operation over a padded message, a whole number of matrix blocks long.
+///
+/// [`Stage`] implements it for the built-in operations and [`Pipeline`] for a sequence of
+/// stages.
+pub trait Transform {
+    /// Applies the transform in place.
+    fn forward(&self, data: &mut [u8], ctx: &KeyContext<'_>);
+
+    /// Reverses [`Transform::forward`] in place.
+    fn inverse(&self, data: &mut [u8], ctx: &KeyContext<'_>);
+}
+
+/// A built-in [`Transform`] that can be recorded in a token header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Stage {
+    /// Shuffles the whole message, seeded by the key sum and the message seed. Only every
+    /// `step`-th position draws a swap.
+    Shuffle {
+        /// Distance between swapped positions, at least 1.
+        step: u8,
+    },
+    /// Shuffles every block, seeded by the first byte of the following block (or the
+    /// first key byte for the last block) and the message seed.
+    BlockShuffle {
+        /// Distance between swapped positions, at least 1.
+        step: u8,
+    },
+    /// Like [`Stage::Shuffle`], but every swap index is drawn uniformly with integer
+    /// arithmetic, giving an unbiased Fisher-Yates shuffle. In [`FormatVersion::V5`]
+    /// tokens the swaps are drawn from a ChaCha20 stream keyed by the round key.
+    UniformShuffle {
+        /// Distance between swapped positions, at least 1.
+        step: u8,
+    },
+    /// Like [`Stage::BlockShuffle`] with the unbiased shuffle of [`Stage::UniformShuffle`].
+    UniformBlockShuffle {
+        /// Distance between swapped positions, at least 1.
+        step: u8,
+    },
+    /// Substitutes every byte through the [`SBox`] of the round key.
+    Substitute,
+    /// XORs every block with the key and the previous block.
+    Mix,
+}
+
+impl Transform for Stage {
+    fn forward(&self, data: &mut [u8], ctx: &KeyContext<'_>) {
+        match *self {
+            Stage::Shuffle { step } => permute(data, ctx, step, false, false),
+            Stage::BlockShuffle { step } => shuffle_blocks(data, ctx, step, false),
+            Stage::UniformShuffle { step } => permute(data, ctx, step, true, false),
+            Stage::UniformBlockShuffle { step } => shuffle_blocks(data, ctx, step, true),
+            Stage::Substitute => ctx.sbox().substitute(data),
+            Stage::Mix => mix(ctx.matrix, data, ctx.key),
+        }
+    }
+
+    fn inverse(&self, data: &mut [u8], ctx: &KeyContext<'_>) {
+        match *self {
+            Stage::Shuffle { step } => permute(data, ctx, step, false, true),
+            Stage::BlockShuffle { step } => unshuffle_blocks(data, ctx, step, false),
+            Stage::UniformShuffle { step } => permute(data, ctx, step, true, true),
+            Stage::UniformBlockShuffle { step } => unshuffle_blocks(data, ctx, step, true),
+            Stage::Substitute => ctx.sbox().invert(data),
+            Stage::Mix => unmix(ctx.matrix, data, ctx.key),
+        }
+    }
+}
+
+fn key_seed(ctx: &KeyContext<'_>) -> u64 {
+    let seed_sum: u64 = ctx.key.iter().map(|&b| b as u64).sum();
+    seed_sum.wrapping_add(ctx.seed)
+}
+
+fn block_seed(blocks: &[&mut [u8]], i: usize, ctx: &KeyContext<'_>) -> u64 {
+    let seed = blocks.get(i + 1)
+        .map(|b| b[0] as u64)
+        .unwrap_or(ctx.key[0] as u64);
+    seed.wrapping_add(ctx.seed)
+}
+
+/// Shuffles the whole message, or reverses the shuffle if `inverse` is set.
+fn permute(data: &mut [u8], ctx: &KeyContext<'_>, step: u8, uniform: bool, inverse: bool) {
+    let source = ctx.message_source(uniform);
+    match inverse {
+        false => source.shuffle(data, step as usize),
+        true => source.unshuffle(data, step as usize),
+    }
+}
+
+/// Returns the generator of the shuffle of block `index`, given its seed.
+fn block_source(ctx: &KeyContext<'_>, uniform: bool) -> impl Fn(u64, usize) -> PermutationSource {
+    let rng_key = (uniform && ctx.keyed_rng()).then(|| ctx.rng_key(&ctx.material.block_shuffle_key, b"block shuffle"));
+    move |seed, index| match (uniform, rng_key) {
+        (false, _) => PermutationSource::Legacy(seed),
+        (true, None) => PermutationSource::Uniform(seed),
+        (true, Some(rng_key)) => PermutationSource::Keyed(rng_key, (index as u64) << 32 | seed),
+    }
+}
+
+/// Shuffles every block front to back, before the following block is shuffled.
+fn shuffle_blocks(data: &mut [u8], ctx: &KeyContext<'_>, step: u8, uniform: bool) {
+    let source = block_source(ctx, uniform);
+    let mut blocks = data.chunks_exact_mut(ctx.matrix).collect::<Vec<_>>();
+    for i in 0..blocks.len() {
+        let seed = block_seed(&blocks, i, ctx);
+        source(seed, i).shuffle(blocks[i], step as usize);
+    }
+}
+
+/// Reverses [`shuffle_blocks`] back to front, once the following block is restored.
+fn unshuffle_blocks(data: &mut [u8], ctx: &KeyContext<'_>, step: u8, uniform: bool) {
+    let source = block_source(ctx, uniform);
+    let mut blocks = data.chunks_exact_mut(ctx.matrix).collect::<Vec<_>>();
+    for i in (0..blocks.len()).rev() {
+        let seed = block_seed(&blocks, i, ctx);
+        source(seed, i).unshuffle(blocks[i], step as usize);
+    }
+}
+
+impl Stage {
+    fn to_bytes(self, out: &mut Vec<u8>) {
+        match self {
+            Stage::Shuffle { step } => out.extend_from_slice(&[1, step]),
+            Stage::BlockShuffle { step } => out.extend_from_slice(&[2, step]),
+            Stage::Substitute => out.push(3),
+            Stage::Mix => out.push(4),
+            Stage::UniformShuffle { step } => out.extend_from_slice(&[5, step]),
+            Stage::UniformBlockShuffle { step } => out.extend_from_slice(&[6, step]),
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Shuffle { step } => write!(f, "shuffle:{step}"),
+            Stage::BlockShuffle { step } => write!(f, "block-shuffle:{step}"),
+            Stage::UniformShuffle { step } => write!(f, "uniform-shuffle:{step}"),
+            Stage::UniformBlockShuffle { step } => write!(f, "uniform-block-shuffle:{step}"),
+            Stage::Substitute => f.write_str("substitute"),
+            Stage::Mix => f.write_str("mix"),
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, step) = match s.trim().split_once(':') {
+            Some((name, step)) => (name, Some(step.parse::<u8>()?)),
+            None => (s.trim(), None),
+        };
+        match (name, step) {
+            ("shuffle", Some(step)) => Ok(Stage::Shuffle { step }),
+            ("block-shuffle", Some(step)) => Ok(Stage::BlockShuffle { step }),
+            ("uniform-shuffle", Some(step)) => Ok(Stage::UniformShuffle { step }),
+            ("uniform-block-shuffle", Some(step)) => Ok(Stage::UniformBlockShuffle { step }),
+            ("substitute", None) => Ok(Stage::Substitute),
+            ("mix", None) => Ok(Stage::Mix),
+            _ => Err(format!("Unknown Pipeline Stage {s}").into()),
+        }
+    }
+}
+
+/// The sequence of [`Stage`]s applied in every round.
+///
+/// A custom pipeline is recorded in the token header, so any `Cryptor` can decrypt the
+/// token. Its description can also be stored as text (`shuffle:5,block-shuffle:2,mix`).
+///
+/// # Examples
+///
+/// ```
+/// use crypt_ro::{Cryptor, Pipeline, Stage};
+///
+/// let pipeline = Pipeline::new(vec![Stage::Substitute, Stage::Mix, Stage::Shuffle { step: 1 }]);
+/// let cryptor = Cryptor::builder().pipeline(pipeline.clone()).build().unwrap();
+///
+/// let encrypted = cryptor.encrypt_text("message", "key").unwrap();
+/// assert_eq!(Cryptor::new().decrypt_text(&encrypted, "key").unwrap(), "message");
+///
+/// let description = pipeline.to_string();
+/// assert_eq!(description, "substitute,mix,shuffle:1");
+/// assert_eq!(description.parse::<Pipeline>().unwrap(), pipeline);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Creates a pipeline from its stages.
+    pub fn new(stages: Vec<Stage>) -> Self {
+        Self { stages }
+    }
+
+    /// Returns the pipeline used by `format` when no custom pipeline is set: a global
+    /// shuffle, block shuffles, a substitution from [`FormatVersion::V3`] on, and the mix.
+    /// From [`FormatVersion::V4`] on the shuffles are unbiased; [`FormatVersion::V5`] keeps
+    /// the stages and only changes where their randomness comes from.
+    pub fn standard(format: FormatVersion) -> Self {
+        let mut stages = if format >= FormatVersion::V4 {
+            vec![Stage::UniformShuffle { step: 5 }, Stage::UniformBlockShuffle { step: 2 }]
+        } else {
+            vec![Stage::Shuffle { step: 5 }, Stage::BlockShuffle { step: 2 }]
+        };
+        if format >= FormatVersion::V3 {
+            stages.push(Stage::Substitute);
+        }
+        stages.push(Stage::Mix);
+        Self { stages }
+    }
+
+    /// Returns the stages in application order.
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Returns a message describing why the pipeline cannot be used, if it cannot.
+    pub(crate) fn validate(&self) -> Option<&'static str> {
+        if self.stages.is_empty() || self.stages.len() > MAX_STAGES {
+            return Some("a pipeline needs between 1 and 32 stages");
+        }
+        let zero_step = self.stages.iter().any(|stage| {
+            matches!(
+                stage,
+                Stage::Shuffle { step: 0 }
+                    | Stage::BlockShuffle { step: 0 }
+                    | Stage::UniformShuffle { step: 0 }
+                    | Stage::UniformBlockShuffle { step: 0 }
+            )
+        });
+        zero_step.then_some("shuffle steps must be at least 1")
+    }
+
+    /// Returns a message describing why tokens of `format` cannot use the pipeline, if
+    /// they cannot. [`FormatVersion::V5`] draws all randomness from the [`KeyedRng`], so
+    /// the floating point shuffles of the legacy generator are left to older versions.
+    pub(crate) fn validate_format(&self, format: FormatVersion) -> Option<&'static str> {
+        let legacy = self.stages.iter().any(|stage| matches!(stage, Stage::Shuffle { .. } | Stage::BlockShuffle { .. }));
+        (legacy && format >= FormatVersion::V5).then_some("use the uniform shuffles from format version 5 on")
+    }
+
+    /// Serializes the description for a token header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 * self.stages.len());
+        for stage in &self.stages {
+            stage.to_bytes(&mut out);
+        }
+        out
+    }
+
+    /// Parses a description written by [`Pipeline::to_bytes`].
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut stages = Vec::new();
+        while let [id, rest @ ..] = bytes {
+            let (stage, rest) = match (*id, rest) {
+                (1, [step, rest @ ..]) => (Stage::Shuffle { step: *step }, rest),
+                (2, [step, rest @ ..]) => (Stage::BlockShuffle { step: *step }, rest),
+                (3, rest) => (Stage::Substitute, rest),
+                (4, rest) => (Stage::Mix, rest),
+                (5, [step, rest @ ..]) => (Stage::UniformShuffle { step: *step }, rest),
+                (6, [step, rest @ ..]) => (Stage::UniformBlockShuffle { step: *step }, rest),
+                _ => return Err("Invalid Pipeline Description".into()),
+            };
+            stages.push(stage);
+            bytes = rest;
+        }
+        let pipeline = Self { stages };
+        match pipeline.validate() {
+            Some(_) => Err("Invalid Pipeline Description".into()),
+            None => Ok(pipeline),
+        }
+    }
+}
+
+impl Transform for Pipeline {
+    fn forward(&self, data: &mut [u8], ctx: &KeyContext<'_>) {
+        for stage in &self.stages {
+            stage.forward(data, ctx);
+        }
+    }
+
+    fn inverse(&self, data: &mut [u8], ctx: &KeyContext<'_>) {
+        for stage in self.stages.iter().rev() {
+            stage.inverse(data, ctx);
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{stage}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stages = s.split(',').map(str::parse).collect::<Result<Vec<Stage>, _>>()?;
+        let pipeline = Self { stages };
+        match pipeline.validate() {
+            Some(reason) => Err(format!("Invalid Pipeline: {reason}").into()),
+            None => Ok(pipeline),
+        }
+    }
+}
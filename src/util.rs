@@ -1,4 +1,7 @@
-use crate::rand::SimpleRng;
+use crate::rand::{KeyedRng, RandomSource, SimpleRng};
+use crate::simd::{self, Backend};
+use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use std::error::Error;
 
 #[inline]
 pub fn generate_password(matrix: usize, password: &[u8]) -> Vec<u8> {
@@ -24,7 +27,7 @@ pub fn generate_password(matrix: usize, password: &[u8]) -> Vec<u8> {
 }
 
 
-pub fn shuffle(data: &mut [u8], seed: u64,step: usize) {
+pub fn shuffle<T>(data: &mut [T], seed: u64,step: usize) {
     let mut rng = SimpleRng::new(seed);
     let len=data.len();
     for i in (1..len).rev().step_by(step) {
@@ -33,10 +36,10 @@ pub fn shuffle(data: &mut [u8], seed: u64,step: usize) {
     }
 }
 
-pub fn unshuffle(data: &mut [u8], seed: u64,step: usize) {
+pub fn unshuffle<T>(data: &mut [T], seed: u64,step: usize) {
     let mut rng = SimpleRng::new(seed);
     let len=data.len();
-    let swap_count = len / step + if len % step != 0 { 1 } else { 0 };
+    let swap_count = len.div_ceil(step);
     let mut swaps = Vec::with_capacity(swap_count);
 
     for i in (1..len).rev().step_by(step) {
@@ -49,32 +52,77 @@ pub fn unshuffle(data: &mut [u8], seed: u64,step: usize) {
     }
 }
 
+/// Fisher-Yates shuffle drawing every swap index uniformly from `0..=i` with integer
+/// arithmetic only. Like [`shuffle`], only every `step`-th position draws a swap.
+pub fn shuffle_uniform<T>(data: &mut [T], seed: u64, step: usize) {
+    shuffle_with(data, &mut SimpleRng::new(seed), step);
+}
 
-#[inline]
-pub fn mix(block_size: usize, buf: &mut [u8], key: &[u8]) {
-    let mut prev_block = key;
+/// Reverses [`shuffle_uniform`].
+pub fn unshuffle_uniform<T>(data: &mut [T], seed: u64, step: usize) {
+    unshuffle_with(data, &mut SimpleRng::new(seed), step);
+}
+
+/// The shuffle of [`shuffle_uniform`] drawing from `rng`.
+pub fn shuffle_with<T>(data: &mut [T], rng: &mut impl RandomSource, step: usize) {
+    for i in (1..data.len()).rev().step_by(step) {
+        let j = rng.gen_index(i + 1);
+        data.swap(i, j);
+    }
+}
 
-    for block in buf.chunks_exact_mut(block_size) {
-        for j in 0..block_size {
-            block[j] ^= prev_block[j];
-        }
-        prev_block = block;
+/// Reverses [`shuffle_with`] given a generator in the same state.
+pub fn unshuffle_with<T>(data: &mut [T], rng: &mut impl RandomSource, step: usize) {
+    let swaps: Vec<_> = (1..data.len())
+        .rev()
+        .step_by(step)
+        .map(|i| (i, rng.gen_index(i + 1)))
+        .collect();
+    for &(i, j) in swaps.iter().rev() {
+        data.swap(i, j);
     }
 }
 
+#[inline]
+pub fn mix(block_size: usize, buf: &mut [u8], key: &[u8]) {
+    simd::mix(Backend::detect(), block_size, buf, key);
+}
+
 #[inline]
 pub fn unmix(block_size: usize, buf: &mut [u8], key: &[u8]) {
-    let mut chunks = buf.chunks_exact_mut(block_size).collect::<Vec<_>>();
-
-    for i in (0..chunks.len()).rev() {
-        if i == 0 {
-            for j in 0..block_size {
-                chunks[i][j] ^= key[j];
-            }
-        } else {
-            for j in 0..block_size {
-                chunks[i][j] ^= chunks[i - 1][j];
-            }
-        };
+    simd::unmix(Backend::detect(), block_size, buf, key);
+}
+
+pub fn encode_text(data: &[u8]) -> String {
+    URL_SAFE.encode(data).trim_end_matches('=').to_string()
+}
+
+pub fn decode_text(encoded: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut input = encoded.to_string();
+    let padding = input.len() % 4;
+    if padding != 0 {
+        input.push_str(&"=".repeat(4 - padding));
     }
+    Ok(URL_SAFE.decode(&input)?)
+}
+
+/// Derives a 64-bit seed from an expanded key and `index`.
+pub fn subkey_seed(key_bytes: &[u8], index: u64) -> u64 {
+    key_bytes
+        .iter()
+        .fold(index, |acc, &b| acc.rotate_left(7) ^ b as u64)
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Derives an independent key for `index` from an expanded key of the same length.
+pub fn derive_subkey(key_bytes: &[u8], index: u64) -> Vec<u8> {
+    let stream = SimpleRng::new(subkey_seed(key_bytes, index)).get_random_bytes(key_bytes.len());
+    key_bytes.iter().zip(stream).map(|(k, s)| k ^ s).collect()
+}
+
+/// Like [`derive_subkey`], with the stream drawn from a [`KeyedRng`] keyed by `key_bytes`.
+pub fn derive_subkey_keyed(key_bytes: &[u8], index: u64) -> Vec<u8> {
+    let mut stream = vec![0u8; key_bytes.len()];
+    KeyedRng::new(&KeyedRng::derive_key(key_bytes, b"subkey"), index).fill_bytes(&mut stream);
+    key_bytes.iter().zip(stream).map(|(k, s)| k ^ s).collect()
 }
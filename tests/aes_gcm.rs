@@ -0,0 +1,73 @@
+#![cfg(feature = "aes-gcm")]
+
+use std::error::Error;
+use crypt_ro::{AesGcmCryptor, Algorithm, Cryptor, DecryptError, Decryptor, Encryptor, Kdf, Key};
+
+fn cipher_for(tenant: &str) -> Box<dyn Cipher> {
+    match tenant {
+        "bank" => Box::new(AesGcmCryptor::new()),
+        _ => Box::new(Cryptor::new()),
+    }
+}
+
+trait Cipher: Encryptor + Decryptor {}
+impl<T: Encryptor + Decryptor> Cipher for T {}
+
+#[test]
+fn test_round_trip() {
+    let cipher = AesGcmCryptor::new();
+    let key = Key::new("key");
+    let envelope = cipher.encrypt_bytes(b"data", &key).unwrap();
+    assert_eq!(Algorithm::detect(&envelope).unwrap(), Algorithm::Aes256Gcm);
+    assert_eq!(envelope.len(), 4 + 12 + 4 + 16);
+    assert_ne!(envelope, cipher.encrypt_bytes(b"data", &key).unwrap());
+    assert_eq!(cipher.decrypt_bytes(&envelope, &key).unwrap(), b"data");
+
+    let text = cipher.encrypt_str("message", &key).unwrap();
+    assert_eq!(cipher.decrypt_str(&text, &key).unwrap(), "message");
+}
+
+#[test]
+fn test_tampering_and_wrong_key() {
+    let cipher = AesGcmCryptor::new();
+    let key = Key::new("key");
+    let envelope = cipher.encrypt_bytes(b"data", &key).unwrap();
+
+    let error = cipher.decrypt_bytes(&envelope, &Key::new("other")).unwrap_err();
+    assert_eq!(error.downcast_ref::<DecryptError>(), Some(&DecryptError::AuthenticationFailed));
+    for i in 4..envelope.len() {
+        let mut tampered = envelope.clone();
+        tampered[i] ^= 1;
+        assert!(cipher.decrypt_bytes(&tampered, &key).is_err(), "byte {i}");
+    }
+    assert!(cipher.decrypt_bytes(&envelope[..20], &key).is_err());
+    assert_ne!(AesGcmCryptor::with_kdf(Kdf::Repeat).decrypt_bytes(&envelope, &key).ok(), Some(b"data".to_vec()));
+}
+
+#[test]
+fn test_pbkdf2_envelopes_are_salted() {
+    let cipher = AesGcmCryptor::with_kdf(Kdf::Pbkdf2 { iterations: 10_000 });
+    let key = Key::new("password");
+    let first = cipher.encrypt_bytes(b"data", &key).unwrap();
+    let second = cipher.encrypt_bytes(b"data", &key).unwrap();
+    // magic and algorithm (4) | salt (16) | nonce (12) | ciphertext (4) | tag (16)
+    assert_eq!(first.len(), 4 + 16 + 12 + 4 + 16);
+    assert_ne!(first[4..20], second[4..20]);
+    assert_eq!(cipher.decrypt_bytes(&first, &key).unwrap(), b"data");
+    assert_eq!(cipher.decrypt_bytes(&second, &key).unwrap(), b"data");
+    assert!(AesGcmCryptor::new().decrypt_bytes(&first, &key).is_err());
+}
+
+#[test]
+fn test_backend_per_tenant() -> Result<(), Box<dyn Error>> {
+    let key = Key::new("shared key");
+    let bank = cipher_for("bank").encrypt_str("iban", &key)?;
+    let shop = cipher_for("shop").encrypt_str("address", &key)?;
+    assert_eq!(cipher_for("bank").decrypt_str(&bank, &key)?, "iban");
+    assert_eq!(cipher_for("shop").decrypt_str(&shop, &key)?, "address");
+
+    let error = cipher_for("shop").decrypt_str(&bank, &key).unwrap_err();
+    assert_eq!(error.to_string(), "Algorithm Mismatch: expected crypt-ro, found AES-256-GCM");
+    assert!(cipher_for("bank").decrypt_str(&shop, &key).is_err());
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::cell::RefCell;
+use std::error::Error;
+use crypt_ro::{Algorithm, Cryptor, Decryptor, Encryptor, Key, ENVELOPE_MAGIC};
+
+/// Records calls instead of encrypting, as application tests would.
+#[derive(Default)]
+struct MockCipher {
+    calls: RefCell<Vec<Vec<u8>>>,
+}
+
+impl Encryptor for MockCipher {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::CryptRo
+    }
+
+    fn encrypt_bytes(&self, data: &[u8], _key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.calls.borrow_mut().push(data.to_vec());
+        Ok(data.iter().rev().copied().collect())
+    }
+}
+
+impl Decryptor for MockCipher {
+    fn decrypt_bytes(&self, envelope: &[u8], _key: &Key) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(envelope.iter().rev().copied().collect())
+    }
+}
+
+fn store_secret<E: Encryptor + Decryptor + ?Sized>(cipher: &E, key: &Key) -> String {
+    let token = cipher.encrypt_str("tenant secret", key).unwrap();
+    assert_eq!(cipher.decrypt_str(&token, key).unwrap(), "tenant secret");
+    token
+}
+
+#[test]
+fn test_cryptor_envelope() {
+    let cryptor = Cryptor::new();
+    let key = Key::new("key");
+    let envelope = cryptor.encrypt_bytes(b"data", &key).unwrap();
+    assert!(envelope.starts_with(&ENVELOPE_MAGIC));
+    assert_eq!(Algorithm::detect(&envelope).unwrap(), Algorithm::CryptRo);
+    assert_eq!(cryptor.decrypt_bytes(&envelope, &key).unwrap(), b"data");
+    // The payload is a regular token
+    assert_eq!(cryptor.decrypt(&envelope[4..], &key).unwrap(), b"data");
+    assert!(cryptor.decrypt_bytes(&envelope[4..], &key).is_err());
+    assert!(cryptor.decrypt_bytes(&envelope, &Key::new("other")).is_err());
+
+    store_secret(&cryptor, &key);
+    let boxed: Box<dyn Encryptor> = Box::new(cryptor);
+    assert_eq!(boxed.algorithm(), Algorithm::CryptRo);
+}
+
+#[test]
+fn test_mock_backend() {
+    let mock = MockCipher::default();
+    store_secret(&mock, &Key::new("key"));
+    assert_eq!(mock.calls.borrow().as_slice(), [b"tenant secret".to_vec()]);
+}
+
+#[test]
+fn test_detect_rejects_unknown_envelopes() {
+    assert_eq!(Algorithm::detect(b"CR").unwrap_err().to_string(), "Invalid Envelope");
+    assert_eq!(Algorithm::detect(b"CRO\x02data").unwrap_err().to_string(), "Invalid Envelope");
+    assert_eq!(Algorithm::detect(b"CRE\x7fdata").unwrap_err().to_string(), "Unknown Algorithm 127");
+    assert_eq!(Algorithm::Aes256Gcm.to_string(), "AES-256-GCM");
+}
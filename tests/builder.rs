@@ -0,0 +1,153 @@
+use crypt_ro::{Chaining, ConfigError, Cryptor, CryptorBuilder, Encoding, FormatVersion, Kdf, Key, MAX_MATRIX, MAX_PBKDF2_ITERATIONS, MIN_MATRIX};
+
+#[test]
+fn test_builder_defaults_match_new() {
+    let built = Cryptor::builder().build().unwrap();
+    assert_eq!(built, Cryptor::new());
+    assert_eq!(built, Cryptor::default());
+    assert_eq!(built.format(), FormatVersion::V1);
+
+    // Default configuration still produces legacy tokens readable by `Cryptor::new`
+    let encrypted = built.encrypt(b"legacy", "key").unwrap();
+    assert!(!encrypted.starts_with(b"CRO"));
+    assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"legacy");
+}
+
+#[test]
+fn test_builder_rejects_invalid_matrix() {
+    for size in [0, 1, 8, MIN_MATRIX - 8, 20, 33, MAX_MATRIX + 8] {
+        assert_eq!(Cryptor::builder().matrix(size).build(), Err(ConfigError::MatrixSize(size)));
+    }
+    for size in [MIN_MATRIX, 24, 64, MAX_MATRIX] {
+        assert_eq!(Cryptor::builder().matrix(size).build().unwrap().matrix(), size);
+    }
+}
+
+#[test]
+fn test_builder_rejects_weak_pbkdf2() {
+    let err = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 1 }).build().unwrap_err();
+    assert_eq!(err, ConfigError::Pbkdf2Iterations(1));
+    assert!(err.to_string().contains("PBKDF2"));
+
+    let iterations = MAX_PBKDF2_ITERATIONS + 1;
+    let err = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations }).build().unwrap_err();
+    assert_eq!(err, ConfigError::Pbkdf2Iterations(iterations));
+}
+
+#[test]
+fn test_pbkdf2_tokens_are_salted() {
+    let cryptor = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).build().unwrap();
+    let key = Key::new("salted");
+    let first = cryptor.encrypt(b"same", &key).unwrap();
+    let second = cryptor.encrypt(b"same", &key).unwrap();
+    // header (9) | KDF field tag and length (2) | id (1) | iterations (4) | salt (16)
+    assert_ne!(first[16..32], second[16..32]);
+    assert_eq!(cryptor.decrypt(&first, &key).unwrap(), b"same");
+    assert_eq!(cryptor.decrypt(&second, "salted").unwrap(), b"same");
+
+    let mut other_salt = first.clone();
+    other_salt[16..32].copy_from_slice(&second[16..32]);
+    assert!(cryptor.decrypt(&other_salt, &key).map_or(true, |d| d != b"same"));
+}
+
+#[test]
+fn test_pbkdf2_iterations_are_bounded_when_parsing() {
+    let cryptor = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).build().unwrap();
+    let encrypted = cryptor.encrypt(b"bounded", "key").unwrap();
+    for iterations in [0, 9_999, MAX_PBKDF2_ITERATIONS + 1, u32::MAX] {
+        let mut forged = encrypted.clone();
+        forged[12..16].copy_from_slice(&iterations.to_be_bytes());
+        let err = cryptor.decrypt(&forged, "key").unwrap_err();
+        assert!(err.to_string().contains("PBKDF2"), "{err}");
+    }
+}
+
+#[test]
+fn test_builder_picks_minimal_format() {
+    let kdf = Cryptor::builder().kdf(Kdf::Sha256).build().unwrap();
+    assert_eq!(kdf.format(), FormatVersion::V2);
+
+    let pinned = Cryptor::builder().format(FormatVersion::V2).build().unwrap();
+    let encrypted = pinned.encrypt(b"headered", "key").unwrap();
+    assert!(encrypted.starts_with(b"CRO"));
+    assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"headered");
+
+    let err = CryptorBuilder::new()
+        .kdf(Kdf::Sha256)
+        .format(FormatVersion::V1)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ConfigError::FormatTooOld { required: FormatVersion::V2, .. }));
+}
+
+#[test]
+fn test_header_records_configuration() {
+    let key = "header key";
+    let configs = [
+        Cryptor::builder().matrix(64).format(FormatVersion::V2).build().unwrap(),
+        Cryptor::builder().kdf(Kdf::Sha256).build().unwrap(),
+        Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).matrix(16).build().unwrap(),
+        Cryptor::builder().kdf(Kdf::Sha256).chaining(Chaining::Counter).build().unwrap(),
+    ];
+    for cryptor in configs {
+        let encrypted = cryptor.encrypt(b"self-describing", key).unwrap();
+        assert_eq!(Cryptor::new().decrypt(&encrypted, key).unwrap(), b"self-describing", "{cryptor:?}");
+    }
+}
+
+#[test]
+fn test_kdf_changes_ciphertext_key() {
+    let cryptor = Cryptor::builder().kdf(Kdf::Sha256).build().unwrap();
+    let encrypted = cryptor.encrypt(b"derived", "key").unwrap();
+    assert!(cryptor.decrypt(&encrypted, "other key").map_or(true, |d| d != b"derived"));
+}
+
+#[test]
+fn test_encodings_roundtrip() {
+    let text = "Hello, world! こんにちは! 😊";
+    for encoding in [Encoding::Base64UrlNoPad, Encoding::Base64Url, Encoding::Base64, Encoding::Hex] {
+        let cryptor = Cryptor::builder().encoding(encoding).build().unwrap();
+        let encrypted = cryptor.encrypt_text(text, "key").unwrap();
+        assert_eq!(cryptor.decrypt_text(&encrypted, "key").unwrap(), text, "{encoding:?}");
+    }
+    assert!(Encoding::Hex.decode("abc").is_err());
+    assert!(Encoding::Hex.decode("zz").is_err());
+}
+
+#[test]
+fn test_prepared_key_is_reusable() {
+    let cryptor = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).build().unwrap();
+    let key = Key::new("prepared");
+    let encrypted = cryptor.encrypt(b"first", &key).unwrap();
+    assert_eq!(cryptor.decrypt(&encrypted, &key).unwrap(), b"first");
+    assert_eq!(cryptor.decrypt(&encrypted, "prepared").unwrap(), b"first");
+    assert_eq!(cryptor.decrypt(&encrypted, &key.clone()).unwrap(), b"first");
+}
+
+#[test]
+fn test_cryptor_is_shareable() {
+    fn assert_shareable<T: Send + Sync + Clone + std::fmt::Debug>() {}
+    assert_shareable::<Cryptor>();
+    assert_shareable::<Key>();
+
+    let cryptor = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let cryptor = cryptor.clone();
+            std::thread::spawn(move || {
+                let data = vec![i as u8; 100];
+                let encrypted = cryptor.encrypt(&data, "key").unwrap();
+                cryptor.decrypt(&encrypted, "key").unwrap() == data
+            })
+        })
+        .collect();
+    assert!(handles.into_iter().all(|h| h.join().unwrap()));
+}
+
+#[cfg(not(feature = "cascade"))]
+#[test]
+fn test_builder_cascade_needs_feature() {
+    let err = Cryptor::builder().cascade(crypt_ro::Cascade::Aes256Gcm).build().unwrap_err();
+    assert!(matches!(err, ConfigError::Cascade(_)));
+    assert!(err.to_string().contains("`cascade` feature"));
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "cascade")]
+
+use crypt_ro::{Cascade, Chaining, Cryptor, DecryptError, Kdf, SeekableEncryptWriter, TokenLayout};
+
+const CASCADES: [Cascade; 2] = [Cascade::Aes256Gcm, Cascade::ChaCha20Poly1305];
+
+fn cascading(cascade: Cascade, chaining: Chaining) -> Cryptor {
+    Cryptor::builder().cascade(cascade).chaining(chaining).kdf(Kdf::Sha256).build().unwrap()
+}
+
+#[test]
+fn test_round_trip() {
+    for cascade in CASCADES {
+        for chaining in [Chaining::Chained, Chaining::Counter] {
+            let cryptor = cascading(cascade, chaining);
+            for len in [0, 1, 31, 32, 1000] {
+                let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let encrypted = cryptor.encrypt(&data, "master key").unwrap();
+                assert_eq!(cryptor.decrypt(&encrypted, "master key").unwrap(), data);
+                // The header identifies the cascade, so any Cryptor can decrypt it
+                assert_eq!(Cryptor::new().decrypt(&encrypted, "master key").unwrap(), data);
+            }
+            let text = cryptor.encrypt_text("secret message", "master key").unwrap();
+            assert_eq!(cryptor.decrypt_text(&text, "master key").unwrap(), "secret message");
+        }
+    }
+}
+
+#[test]
+fn test_builder() {
+    let cryptor = Cryptor::builder().cascade(Cascade::Aes256Gcm).build().unwrap();
+    assert_eq!(cryptor.cascade(), Some(Cascade::Aes256Gcm));
+    assert_eq!(cryptor.format(), crypt_ro::FormatVersion::V2);
+    assert_eq!(Cryptor::new().cascade(), None);
+    assert!(Cryptor::builder().cascade(Cascade::Aes256Gcm).format(crypt_ro::FormatVersion::V1).build().is_err());
+    assert_eq!(Cascade::ChaCha20Poly1305.to_string(), "ChaCha20-Poly1305");
+}
+
+#[test]
+fn test_tampering_and_wrong_key() {
+    for cascade in CASCADES {
+        let cryptor = cascading(cascade, Chaining::Chained);
+        let encrypted = cryptor.encrypt(b"audited data", "master key").unwrap();
+        let error = cryptor.decrypt(&encrypted, "other key").unwrap_err();
+        assert_eq!(error.downcast_ref::<DecryptError>(), Some(&DecryptError::AuthenticationFailed));
+        for i in 0..encrypted.len() {
+            let mut tampered = encrypted.clone();
+            tampered[i] ^= 1;
+            assert!(cryptor.decrypt(&tampered, "master key").is_err(), "{cascade} byte {i}");
+        }
+        assert!(cryptor.decrypt(&encrypted[..encrypted.len() - 1], "master key").is_err());
+    }
+}
+
+#[test]
+fn test_downgrades_are_rejected() {
+    let cryptor = cascading(Cascade::Aes256Gcm, Chaining::Chained);
+    let downgrades = [
+        Cryptor::new().encrypt(b"data", "master key").unwrap(),
+        Cryptor::builder().kdf(Kdf::Sha256).build().unwrap().encrypt(b"data", "master key").unwrap(),
+        cascading(Cascade::ChaCha20Poly1305, Chaining::Chained).encrypt(b"data", "master key").unwrap(),
+    ];
+    for token in downgrades {
+        let error = cryptor.decrypt(&token, "master key").unwrap_err();
+        assert_eq!(error.downcast_ref::<DecryptError>(), Some(&DecryptError::AuthenticationFailed));
+        assert_eq!(Cryptor::new().decrypt(&token, "master key").unwrap(), b"data");
+    }
+}
+
+#[test]
+fn test_key_fields_stay_on_the_outer_header() {
+    let cryptor = Cryptor::builder()
+        .cascade(Cascade::ChaCha20Poly1305)
+        .kdf(Kdf::Sha256)
+        .key_id("2024-06")
+        .key_check(true)
+        .key_commitment(true)
+        .build()
+        .unwrap();
+    let encrypted = cryptor.encrypt(b"data", "master key").unwrap();
+    assert_eq!(cryptor.decrypt(&encrypted, "master key").unwrap(), b"data");
+    let error = cryptor.decrypt(&encrypted, "other key").unwrap_err();
+    assert_eq!(error.downcast_ref::<DecryptError>(), Some(&DecryptError::WrongKey));
+}
+
+#[test]
+fn test_inspect() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = cascading(Cascade::Aes256Gcm, chaining);
+        let encrypted = cryptor.encrypt(b"seventeen letters", "key").unwrap();
+        let info = Cryptor::inspect(&encrypted).unwrap();
+        assert_eq!(info.cascade, Some(Cascade::Aes256Gcm));
+        assert!(info.payload_len.contains(&17), "{:?}", info.payload_len);
+        let layout = if chaining == Chaining::Counter { TokenLayout::Counter } else { TokenLayout::Chained };
+        assert_eq!(info.layout, layout);
+        assert!(info.to_string().contains("cascade:     AES-256-GCM"));
+    }
+    let info = Cryptor::inspect(&Cryptor::new().encrypt(b"data", "key").unwrap()).unwrap();
+    assert_eq!(info.cascade, None);
+}
+
+#[test]
+fn test_unsupported_modes() {
+    let cryptor = cascading(Cascade::Aes256Gcm, Chaining::Counter);
+    assert!(SeekableEncryptWriter::new(&cryptor, Vec::new(), "key").is_err());
+    let encrypted = cryptor.encrypt(b"hello random access", "key").unwrap();
+    let error = cryptor.decrypt_range(&encrypted, "key", 0..5).unwrap_err();
+    assert_eq!(error.to_string(), "Range Decryption Is Not Supported For Cascade Tokens");
+}
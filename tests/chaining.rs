@@ -0,0 +1,104 @@
+use crypt_ro::{Chaining, Cryptor};
+
+fn counter_cryptor(matrix: usize) -> Cryptor {
+    Cryptor::builder()
+        .matrix(matrix)
+        .chaining(Chaining::Counter)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_counter_roundtrip() {
+    let key = "counter key";
+    for matrix in [16, 24, 32, 64] {
+        let cryptor = counter_cryptor(matrix);
+        for len in [0, 1, 4, 31, 32, 33, 1000] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            let encrypted = cryptor.encrypt(&data, key).unwrap();
+            assert_eq!(cryptor.decrypt(&encrypted, key).unwrap(), data, "matrix {matrix}, length {len}");
+        }
+    }
+}
+
+#[test]
+fn test_counter_text_roundtrip() {
+    let cryptor = counter_cryptor(32);
+    let text = "Hello, world! こんにちは! 😊";
+
+    let encrypted = cryptor.encrypt_text(text, "key").unwrap();
+    assert_eq!(cryptor.decrypt_text(&encrypted, "key").unwrap(), text);
+}
+
+#[test]
+fn test_decrypt_follows_token_mode() {
+    let counter = counter_cryptor(32);
+    let chained = Cryptor::new();
+
+    let encrypted = counter.encrypt(b"recorded in the header", "key").unwrap();
+    assert!(encrypted.starts_with(b"CRO"));
+    assert_eq!(chained.decrypt(&encrypted, "key").unwrap(), b"recorded in the header");
+
+    let encrypted = chained.encrypt(b"legacy token", "key").unwrap();
+    assert_eq!(counter.decrypt(&encrypted, "key").unwrap(), b"legacy token");
+}
+
+#[test]
+fn test_counter_uses_fresh_nonce() {
+    let cryptor = counter_cryptor(32);
+    let first = cryptor.encrypt(b"same message", "key").unwrap();
+    let second = cryptor.encrypt(b"same message", "key").unwrap();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn test_decrypt_range() {
+    let key = "range key";
+    let data: Vec<u8> = (0..500u32).map(|i| (i % 251) as u8).collect();
+
+    for matrix in [16, 24, 32] {
+        let cryptor = counter_cryptor(matrix);
+        let encrypted = cryptor.encrypt(&data, key).unwrap();
+        for (start, end) in [(0, 0), (0, 1), (0, 500), (3, 4), (31, 33), (100, 260), (499, 500)] {
+            let range = cryptor.decrypt_range(&encrypted, key, start..end).unwrap();
+            assert_eq!(range, &data[start..end], "matrix {matrix}, range {start}..{end}");
+        }
+    }
+}
+
+#[test]
+fn test_decrypt_range_is_clamped() {
+    let cryptor = counter_cryptor(32);
+    let encrypted = cryptor.encrypt(b"short", "key").unwrap();
+
+    assert_eq!(cryptor.decrypt_range(&encrypted, "key", 2..100).unwrap(), b"ort");
+    assert!(cryptor.decrypt_range(&encrypted, "key", 10..20).unwrap().is_empty());
+}
+
+#[test]
+fn test_decrypt_range_requires_counter_mode() {
+    let cryptor = Cryptor::new();
+    let encrypted = cryptor.encrypt(b"chained token", "key").unwrap();
+    assert!(cryptor.decrypt_range(&encrypted, "key", 0..4).is_err());
+}
+
+#[test]
+fn test_counter_rejects_malformed_tokens() {
+    let cryptor = counter_cryptor(32);
+    let encrypted = cryptor.encrypt(b"some data", "key").unwrap();
+
+    assert!(cryptor.decrypt(&encrypted[..encrypted.len() - 1], "key").is_err());
+    assert!(cryptor.decrypt(&encrypted[..10], "key").is_err());
+    assert!(cryptor.decrypt(&encrypted, "wrong key").is_err());
+}
+
+#[test]
+fn test_set_chaining_matches_builder() {
+    let mut cryptor = Cryptor::new();
+    cryptor.set_chaining(Chaining::Counter);
+    assert_eq!(cryptor, counter_cryptor(32));
+
+    let encrypted = cryptor.encrypt(b"set at runtime", "key").unwrap();
+    assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"set at runtime");
+    assert_eq!(cryptor.decrypt_range(&encrypted, "key", 7..14).unwrap(), b"runtime");
+}
@@ -0,0 +1,137 @@
+use std::io::{Cursor, Read, Write};
+use crypt_ro::{
+    Chaining, ConfigError, Cryptor, DecryptError, FormatVersion, Kdf, Key, SeekableDecryptReader,
+    SeekableEncryptWriter,
+};
+
+fn committing(chaining: Chaining) -> Cryptor {
+    Cryptor::builder().key_commitment(true).chaining(chaining).build().unwrap()
+}
+
+fn decrypt_error(cryptor: &Cryptor, token: &[u8], key: &str) -> DecryptError {
+    let err = cryptor.decrypt(token, key).unwrap_err();
+    err.downcast_ref::<DecryptError>().cloned().unwrap_or_else(|| panic!("unexpected error {err}"))
+}
+
+/// Offset of the commitment value in a token whose only header field is the commitment.
+const COMMITMENT: usize = 9 + 2;
+
+#[test]
+fn test_accepted_under_one_key_only() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = committing(chaining);
+        assert!(cryptor.key_commitment());
+        assert_eq!(cryptor.format(), FormatVersion::V2);
+
+        let token = cryptor.encrypt(b"transfer 10 to alice", "key A").unwrap();
+        assert_eq!(cryptor.decrypt(&token, "key A").unwrap(), b"transfer 10 to alice");
+        assert_eq!(decrypt_error(&cryptor, &token, "key B"), DecryptError::WrongKey, "{chaining:?}");
+        // Keys that expand to the same key block are still told apart
+        assert_eq!(decrypt_error(&cryptor, &token, "key Akey A"), DecryptError::WrongKey);
+    }
+}
+
+#[test]
+fn test_commitment_cannot_be_swapped() {
+    let cryptor = committing(Chaining::Chained);
+    let token_a = cryptor.encrypt(b"message for A", "key A").unwrap();
+    let token_b = cryptor.encrypt(b"message for B", "key B").unwrap();
+
+    // Moving B's commitment onto A's ciphertext makes the token open under neither key
+    let mut forged = token_a.clone();
+    forged[COMMITMENT..COMMITMENT + 32].copy_from_slice(&token_b[COMMITMENT..COMMITMENT + 32]);
+    assert_eq!(decrypt_error(&cryptor, &forged, "key A"), DecryptError::WrongKey);
+    assert_eq!(decrypt_error(&cryptor, &forged, "key B"), DecryptError::AuthenticationFailed);
+}
+
+#[test]
+fn test_tampering_is_detected() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = committing(chaining);
+        let token = cryptor.encrypt(&[7u8; 100], "key").unwrap();
+        for position in [4, COMMITMENT + 40, token.len() / 2, token.len() - 1] {
+            let mut tampered = token.clone();
+            tampered[position] ^= 1;
+            assert!(cryptor.decrypt(&tampered, "key").is_err(), "{chaining:?} byte {position}");
+        }
+        let err = decrypt_error(&cryptor, &token[..token.len() - 1], "key");
+        assert_eq!(err, DecryptError::AuthenticationFailed);
+    }
+}
+
+/// Removes the commitment field and the trailing tag, leaving a valid plain token.
+fn strip_commitment(token: &[u8]) -> Vec<u8> {
+    let mut stripped = token[..7].to_vec();
+    stripped.extend_from_slice(&[0, 0]);
+    stripped.extend_from_slice(&token[COMMITMENT + 32..token.len() - 32]);
+    stripped
+}
+
+#[test]
+fn test_commitment_is_required() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = committing(chaining);
+        let token = cryptor.encrypt(b"transfer 10 to alice", "key").unwrap();
+        let stripped = strip_commitment(&token);
+        assert_eq!(Cryptor::new().decrypt(&stripped, "key").unwrap(), b"transfer 10 to alice");
+        assert_eq!(decrypt_error(&cryptor, &stripped, "key"), DecryptError::AuthenticationFailed, "{chaining:?}");
+    }
+
+    let cryptor = committing(Chaining::Counter);
+    let stripped = strip_commitment(&cryptor.encrypt(b"hello committed world", "key").unwrap());
+    assert!(cryptor.decrypt_range(&stripped, "key", 6..15).is_err());
+
+    // Headerless tokens cannot commit to a key at all
+    let legacy = Cryptor::new().encrypt(b"legacy", "key").unwrap();
+    assert_eq!(decrypt_error(&cryptor, &legacy, "key"), DecryptError::AuthenticationFailed);
+}
+
+#[test]
+fn test_range_and_inspect() {
+    let cryptor = committing(Chaining::Counter);
+    let token = cryptor.encrypt(b"hello committed world", "key").unwrap();
+    assert_eq!(cryptor.decrypt_range(&token, "key", 6..15).unwrap(), b"committed");
+    assert!(cryptor.decrypt_range(&token, "other", 6..15).is_err());
+
+    let info = Cryptor::inspect(&token).unwrap();
+    assert!(info.key_commitment);
+    assert!(info.payload_len.contains(&21), "{:?}", info.payload_len);
+    assert!(!Cryptor::inspect(&Cryptor::new().encrypt(b"x", "key").unwrap()).unwrap().key_commitment);
+}
+
+#[test]
+fn test_seekable_commits_to_key() {
+    let cryptor = committing(Chaining::Chained);
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&cryptor, Vec::new(), "key A", 64).unwrap();
+    writer.write_all(&[1u8; 500]).unwrap();
+    let container = writer.finish().unwrap();
+
+    assert!(SeekableDecryptReader::new(Cursor::new(&container), "key B").is_err());
+    let mut plain = Vec::new();
+    SeekableDecryptReader::new(Cursor::new(&container), "key A").unwrap().read_to_end(&mut plain).unwrap();
+    assert_eq!(plain, [1u8; 500]);
+    assert!(SeekableDecryptReader::with_cryptor(&cryptor, Cursor::new(&container), "key A").is_ok());
+
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&Cryptor::new(), Vec::new(), "key A", 64).unwrap();
+    writer.write_all(&[1u8; 500]).unwrap();
+    let plain_container = writer.finish().unwrap();
+    assert!(SeekableDecryptReader::new(Cursor::new(&plain_container), "key A").is_ok());
+    assert!(SeekableDecryptReader::with_cryptor(&cryptor, Cursor::new(&plain_container), "key A").is_err());
+    assert_eq!(decrypt_error(&cryptor, &plain_container, "key A"), DecryptError::AuthenticationFailed);
+}
+
+#[test]
+fn test_commitment_with_slow_kdf() {
+    let cryptor = Cryptor::builder()
+        .key_commitment(true)
+        .kdf(Kdf::Pbkdf2 { iterations: 10_000 })
+        .build()
+        .unwrap();
+    let key = Key::new("password");
+    let token = cryptor.encrypt(b"data", &key).unwrap();
+    assert_eq!(cryptor.decrypt(&token, &key).unwrap(), b"data");
+    assert!(cryptor.decrypt(&token, "passw0rd").is_err());
+
+    let err = Cryptor::builder().key_commitment(true).format(FormatVersion::V1).build().unwrap_err();
+    assert!(matches!(err, ConfigError::FormatTooOld { option: "key commitment", .. }));
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "derive")]
+
+use crypt_ro::{Chaining, Cryptor, EncryptFields, Key, PaddingPolicy};
+
+#[derive(Debug, Clone, PartialEq, EncryptFields)]
+struct Address {
+    #[encrypt]
+    street: String,
+    city: String,
+}
+
+#[derive(Debug, Clone, PartialEq, EncryptFields)]
+struct Customer {
+    id: u64,
+    name: String,
+    #[encrypt]
+    ssn: String,
+    #[encrypt]
+    photo: Vec<u8>,
+    #[encrypt]
+    phone: Option<String>,
+    #[encrypt]
+    address: Address,
+    #[encrypt]
+    billing: Option<Address>,
+}
+
+#[derive(Debug, Clone, PartialEq, EncryptFields)]
+struct Pair(#[encrypt] String, String);
+
+#[derive(Debug, Clone, PartialEq, EncryptFields)]
+struct Wrapper<T: EncryptFields> {
+    #[encrypt]
+    inner: T,
+}
+
+fn customer() -> Customer {
+    Customer {
+        id: 1,
+        name: "Ann".into(),
+        ssn: "123-45-6789".into(),
+        photo: vec![1, 2, 3],
+        phone: Some("+1 555 0100".into()),
+        address: Address { street: "1 Main St".into(), city: "Springfield".into() },
+        billing: None,
+    }
+}
+
+#[test]
+fn test_marked_fields_are_encrypted() {
+    let cryptor = Cryptor::new();
+    let key = Key::new("field key");
+    let mut encrypted = customer();
+    encrypted.encrypt_fields(&cryptor, &key).unwrap();
+
+    assert_eq!(encrypted.id, 1);
+    assert_eq!(encrypted.name, "Ann");
+    assert_eq!(encrypted.address.city, "Springfield");
+    assert_eq!(encrypted.billing, None);
+    assert_eq!(cryptor.decrypt_text(&encrypted.ssn, &key).unwrap(), "123-45-6789");
+    assert_eq!(cryptor.decrypt(&encrypted.photo, &key).unwrap(), [1, 2, 3]);
+    assert_eq!(cryptor.decrypt_text(encrypted.phone.as_ref().unwrap(), &key).unwrap(), "+1 555 0100");
+    assert_eq!(cryptor.decrypt_text(&encrypted.address.street, &key).unwrap(), "1 Main St");
+
+    encrypted.decrypt_fields(&cryptor, &key).unwrap();
+    assert_eq!(encrypted, customer());
+}
+
+#[test]
+fn test_wrong_key_is_reported() {
+    let cryptor = Cryptor::builder().key_check(true).chaining(Chaining::Counter).build().unwrap();
+    let mut value = customer();
+    value.encrypt_fields(&cryptor, &Key::new("a")).unwrap();
+    assert!(value.decrypt_fields(&cryptor, &Key::new("b")).is_err());
+}
+
+#[test]
+fn test_failures_leave_no_field_half_done() {
+    // Fixed padding rejects the photo after the SSN was already encrypted
+    let cryptor = Cryptor::builder().padding(PaddingPolicy::Fixed(16)).build().unwrap();
+    let key = Key::new("key");
+    let mut value = customer();
+    value.photo = vec![0; 100];
+    let original = value.clone();
+    assert!(value.encrypt_fields(&cryptor, &key).is_err());
+    assert_eq!(value, original);
+
+    // A damaged token after the SSN fails decryption with the SSN encrypted again
+    let cryptor = Cryptor::new();
+    let mut value = customer();
+    value.encrypt_fields(&cryptor, &key).unwrap();
+    value.photo.truncate(3);
+    let damaged = value.clone();
+    assert!(value.decrypt_fields(&cryptor, &key).is_err());
+    assert_eq!(value.photo, damaged.photo);
+    assert_eq!(cryptor.decrypt_text(&value.ssn, &key).unwrap(), "123-45-6789");
+}
+
+#[test]
+fn test_tuple_and_generic_structs() {
+    let cryptor = Cryptor::new();
+    let key = Key::new("key");
+
+    let mut pair = Pair("secret".into(), "public".into());
+    pair.encrypt_fields(&cryptor, &key).unwrap();
+    assert_ne!(pair.0, "secret");
+    assert_eq!(pair.1, "public");
+    pair.decrypt_fields(&cryptor, &key).unwrap();
+    assert_eq!(pair, Pair("secret".into(), "public".into()));
+
+    let mut wrapper = Wrapper { inner: Some(Box::new(Pair("x".into(), "y".into()))) };
+    wrapper.encrypt_fields(&cryptor, &key).unwrap();
+    wrapper.decrypt_fields(&cryptor, &key).unwrap();
+    assert_eq!(wrapper.inner.unwrap().0, "x");
+}
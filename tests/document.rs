@@ -0,0 +1,194 @@
+#![cfg(feature = "config")]
+
+use std::process::Command;
+use crypt_ro::{
+    decrypt_config, decrypt_document, encrypt_config, encrypt_document, Cryptor, DecryptError, DocumentFormat,
+    Kdf, DOCUMENT_METADATA_KEY,
+};
+use serde_json::json;
+
+const TOML: &str = r#"name = "billing"
+replicas = 3
+
+[database]
+host = "db.internal"
+password = "hunter2"
+ports = [5432, 5433]
+"#;
+
+const YAML: &str = "name: billing\ndatabase:\n  password: hunter2\n  tls: true\n  hosts:\n  - a.internal\n  - b.internal\n";
+
+fn is_authentication_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<DecryptError>() == Some(&DecryptError::AuthenticationFailed)
+}
+
+#[test]
+fn test_document_round_trip() {
+    let cryptor = Cryptor::new();
+    let original = json!({"a": {"b": [1, "two", null, true]}, "c": 1.5, "empty": {}});
+    let mut document = original.clone();
+    encrypt_document(&mut document, &cryptor, "key").unwrap();
+
+    // Keys stay readable, every value is a token
+    assert!(document["a"]["b"].as_array().unwrap().iter().all(|v| v.as_str().unwrap().starts_with("cro:")));
+    assert!(document["c"].as_str().unwrap().starts_with("cro:"));
+    assert!(document[DOCUMENT_METADATA_KEY]["mac"].is_string());
+    assert_eq!(
+        encrypt_document(&mut document.clone(), &cryptor, "key").unwrap_err().to_string(),
+        "Document Is Already Encrypted"
+    );
+
+    decrypt_document(&mut document, &cryptor, "key").unwrap();
+    assert_eq!(document, original);
+    assert!(encrypt_document(&mut json!([1, 2]), &cryptor, "key").is_err());
+}
+
+#[test]
+fn test_document_encrypts_values_with_prefix() {
+    let cryptor = Cryptor::new();
+    let original = json!({"token": "cro:looks encrypted", "nested": {"token": "cro:"}});
+    let mut document = original.clone();
+    encrypt_document(&mut document, &cryptor, "key").unwrap();
+    assert!(!document.to_string().contains("looks encrypted"));
+    decrypt_document(&mut document, &cryptor, "key").unwrap();
+    assert_eq!(document, original);
+}
+
+#[test]
+fn test_pbkdf2_document_is_salted() {
+    let cryptor = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).build().unwrap();
+    let original = json!({"password": "hunter2"});
+    let mut first = original.clone();
+    let mut second = original.clone();
+    encrypt_document(&mut first, &cryptor, "key").unwrap();
+    encrypt_document(&mut second, &cryptor, "key").unwrap();
+    assert_ne!(first[DOCUMENT_METADATA_KEY]["salt"], second[DOCUMENT_METADATA_KEY]["salt"]);
+
+    let mut unsalted = first.clone();
+    unsalted[DOCUMENT_METADATA_KEY].as_object_mut().unwrap().remove("salt");
+    assert!(decrypt_document(&mut unsalted, &cryptor, "key").is_err());
+    decrypt_document(&mut first, &cryptor, "key").unwrap();
+    assert_eq!(first, original);
+}
+
+#[test]
+fn test_tampering_is_detected() {
+    let cryptor = Cryptor::new();
+    let mut document = json!({"user": "app", "password": "hunter2", "port": 5432});
+    encrypt_document(&mut document, &cryptor, "key").unwrap();
+
+    let mut swapped = document.clone();
+    swapped["user"] = document["password"].clone();
+    swapped["password"] = document["user"].clone();
+    let mut removed = document.clone();
+    removed.as_object_mut().unwrap().remove("port");
+    let mut added = document.clone();
+    added["debug"] = json!(true);
+    let mut renamed = document.clone();
+    let value = renamed.as_object_mut().unwrap().remove("password").unwrap();
+    renamed["passwd"] = value;
+
+    for mut tampered in [swapped, removed, added, renamed] {
+        let err = decrypt_document(&mut tampered, &cryptor, "key").unwrap_err();
+        assert!(is_authentication_error(err.as_ref()), "{err}");
+    }
+    let err = decrypt_document(&mut document.clone(), &cryptor, "other").unwrap_err();
+    assert!(is_authentication_error(err.as_ref()));
+    // A failed check leaves the document as it was
+    let before = document.clone();
+    let _ = decrypt_document(&mut document, &cryptor, "other");
+    assert_eq!(document, before);
+}
+
+#[test]
+fn test_config_formats() {
+    let cryptor = Cryptor::builder().kdf(Kdf::Sha256).build().unwrap();
+    for (format, text, secret) in [(DocumentFormat::Toml, TOML, "hunter2"), (DocumentFormat::Yaml, YAML, "hunter2")] {
+        let encrypted = encrypt_config(text, format, &cryptor, "key").unwrap();
+        assert!(!encrypted.contains(secret), "{encrypted}");
+        assert!(encrypted.contains("password"));
+        let decrypted = decrypt_config(&encrypted, format, &cryptor, "key").unwrap();
+        assert_eq!(format.parse(&decrypted).unwrap(), format.parse(text).unwrap(), "{format:?}");
+    }
+
+    let json = r#"{"token": "abc", "limits": {"rps": 10}}"#;
+    let encrypted = encrypt_config(json, DocumentFormat::Json, &cryptor, "key").unwrap();
+    let decrypted = decrypt_config(&encrypted, DocumentFormat::Json, &cryptor, "key").unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&decrypted).unwrap(), json!({"token": "abc", "limits": {"rps": 10}}));
+
+    assert_eq!(DocumentFormat::from_path("app.YML"), Some(DocumentFormat::Yaml));
+    assert_eq!(DocumentFormat::from_path("config/app.toml"), Some(DocumentFormat::Toml));
+    assert_eq!(DocumentFormat::from_path("app.ini"), None);
+}
+
+#[test]
+fn test_cli_encrypt_and_edit() {
+    let dir = std::env::temp_dir().join(format!("crypt-ro-document-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let plain = dir.join("plain.toml");
+    let secret = dir.join("app.toml");
+    std::fs::write(&plain, TOML).unwrap();
+    let cli = || {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_crypt-ro"));
+        command
+            .env("CRYPT_RO_KEY", "cli key")
+            .env_remove("CRYPT_RO_KEY_FILE")
+            .env("CRYPT_RO_PBKDF2_ITERATIONS", "10000");
+        command
+    };
+
+    let output = cli().arg("encrypt").arg(&plain).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    std::fs::write(&secret, &output.stdout).unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("hunter2"));
+
+    // The editor changes the password in the decrypted copy
+    let status = cli().arg("edit").arg(&secret).env("EDITOR", "sed -i s/hunter2/correct-horse/").status().unwrap();
+    assert!(status.success());
+    let edited = std::fs::read_to_string(&secret).unwrap();
+    assert!(!edited.contains("correct-horse"));
+    // The new file was renamed into place, leaving no temporary sibling
+    assert!(std::fs::read_dir(&dir).unwrap().all(|entry| !entry.unwrap().file_name().to_string_lossy().starts_with('.')));
+
+    let output = cli().arg("decrypt").arg(&secret).output().unwrap();
+    let decrypted = String::from_utf8(output.stdout).unwrap();
+    assert!(decrypted.contains("password = \"correct-horse\""), "{decrypted}");
+
+    // The decrypted copy lives in a private directory that is removed afterwards
+    #[cfg(unix)]
+    {
+        let runtime = dir.join("runtime");
+        std::fs::create_dir_all(&runtime).unwrap();
+        let record = dir.join("editor.log");
+        let editor = format!("ls -ld \"$(dirname \"$1\")\" >{} && true", record.display());
+        let status = cli().arg("edit").arg(&secret).env("EDITOR", editor).env("XDG_RUNTIME_DIR", &runtime).status().unwrap();
+        assert!(status.success());
+        let listing = std::fs::read_to_string(&record).unwrap();
+        assert!(listing.starts_with("drwx------"), "{listing}");
+        assert!(listing.contains(runtime.to_str().unwrap()), "{listing}");
+        assert_eq!(std::fs::read_dir(&runtime).unwrap().count(), 0);
+        assert_eq!(std::fs::read_to_string(&secret).unwrap(), edited);
+    }
+
+    // A failing editor leaves the file alone
+    let output = cli().arg("edit").arg(&secret).env("EDITOR", "false").output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read_to_string(&secret).unwrap(), edited);
+
+    // The key can come from a file
+    let key_file = dir.join("key");
+    std::fs::write(&key_file, "cli key\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_crypt-ro"))
+        .arg("decrypt")
+        .arg(&secret)
+        .env_remove("CRYPT_RO_KEY")
+        .env("CRYPT_RO_KEY_FILE", &key_file)
+        .env("CRYPT_RO_PBKDF2_ITERATIONS", "10000")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = cli().arg("decrypt").arg(&secret).env("CRYPT_RO_KEY", "wrong").output().unwrap();
+    assert!(!output.status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
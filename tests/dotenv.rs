@@ -0,0 +1,152 @@
+use std::process::Command;
+use crypt_ro::{apply_env_file, decrypt_env, encrypt_env, load_env_file, Cryptor, Kdf, Key, KEY_ENV_VAR};
+
+const ENV: &str = "\
+# Database
+export DB_HOST=db.internal
+DB_PASSWORD = hunter2 # rotated monthly
+API_TOKEN=\"quoted \\\"value\\\"\\nsecond line\"
+LITERAL='no $expansion here'
+EMPTY=
+";
+
+#[test]
+fn test_encrypt_keeps_layout() {
+    let cryptor = Cryptor::new();
+    let encrypted = encrypt_env(ENV, &cryptor, "key").unwrap();
+    let lines: Vec<&str> = encrypted.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "# Database");
+    assert!(lines[1].starts_with("export DB_HOST=ENC["), "{}", lines[1]);
+    assert!(lines[2].starts_with("DB_PASSWORD = ENC["));
+    assert!(lines[2].ends_with("] # rotated monthly"));
+    assert!(!encrypted.contains("hunter2"));
+    assert!(!encrypted.contains("quoted"));
+
+    let vars = decrypt_env(&encrypted, &cryptor, "key").unwrap();
+    assert_eq!(vars["DB_HOST"], "db.internal");
+    assert_eq!(vars["DB_PASSWORD"], "hunter2");
+    assert_eq!(vars["API_TOKEN"], "quoted \"value\"\nsecond line");
+    assert_eq!(vars["LITERAL"], "no $expansion here");
+    assert_eq!(vars["EMPTY"], "");
+    assert_eq!(vars, decrypt_env(ENV, &cryptor, "key").unwrap());
+}
+
+#[test]
+fn test_mixed_files_and_reencryption() {
+    let cryptor = Cryptor::new();
+    let encrypted = encrypt_env("A=1\n", &cryptor, "key").unwrap();
+    let mixed = format!("{encrypted}B=2\n");
+    assert_eq!(decrypt_env(&mixed, &cryptor, "key").unwrap()["B"], "2");
+
+    // Only the new plain entry is encrypted
+    let again = encrypt_env(&mixed, &cryptor, "key").unwrap();
+    assert!(again.starts_with(&encrypted));
+    assert!(!again.contains("B=2"));
+    let vars = decrypt_env(&again, &cryptor, "key").unwrap();
+    assert_eq!((vars["A"].as_str(), vars["B"].as_str()), ("1", "2"));
+
+    let err = decrypt_env(&again, &cryptor, "wrong").unwrap_err();
+    assert!(err.to_string().starts_with("Cannot Decrypt A"), "{err}");
+}
+
+#[test]
+fn test_plain_values_that_look_encrypted() {
+    let cryptor = Cryptor::new();
+    let text = "QUOTED=\"ENC[hunter2]\"\nSINGLE='ENC[abc]'\nUNQUOTED=ENC[not a token!]\n";
+    let encrypted = encrypt_env(text, &cryptor, "key").unwrap();
+    assert!(!encrypted.contains("hunter2") && !encrypted.contains("not a token"), "{encrypted}");
+    assert_eq!(encrypted.lines().filter(|line| line.contains("=ENC[")).count(), 3);
+
+    let vars = decrypt_env(&encrypted, &cryptor, "key").unwrap();
+    assert_eq!(vars["QUOTED"], "ENC[hunter2]");
+    assert_eq!(vars["SINGLE"], "ENC[abc]");
+    assert_eq!(vars["UNQUOTED"], "ENC[not a token!]");
+
+    // Without encrypting, the values are read as written
+    assert_eq!(decrypt_env(text, &cryptor, "key").unwrap(), vars);
+}
+
+#[test]
+fn test_invalid_lines() {
+    let cryptor = Cryptor::new();
+    for (text, line) in [("NO_EQUALS\n", 1), ("A=1\n1BAD=x\n", 2), ("A=\"open\n", 1), ("A='x' trailing\n", 1)] {
+        let err = decrypt_env(text, &cryptor, "key").unwrap_err();
+        assert_eq!(err.to_string(), format!("Invalid Env Line {line}"), "{text:?}");
+    }
+}
+
+#[test]
+fn test_load_and_apply() {
+    let cryptor = Cryptor::new();
+    let dir = std::env::temp_dir().join(format!("crypt-ro-dotenv-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(".env");
+    let text = "CRYPT_RO_TEST_SECRET=s3cret\nCRYPT_RO_TEST_PRESET=from file\n";
+    std::fs::write(&path, encrypt_env(text, &cryptor, "master").unwrap()).unwrap();
+
+    // SAFETY: this is the only test of this binary changing the environment, and the
+    // others only read it through std, which serialises environment access
+    unsafe {
+        std::env::set_var(KEY_ENV_VAR, "master");
+        std::env::set_var("CRYPT_RO_TEST_PRESET", "from environment");
+    }
+    assert_eq!(Key::from_env().unwrap().fingerprint(), Key::new("master").fingerprint());
+    assert_eq!(load_env_file(&path, &cryptor).unwrap()["CRYPT_RO_TEST_SECRET"], "s3cret");
+
+    // SAFETY: as above
+    let set = unsafe { apply_env_file(&path, &cryptor) }.unwrap();
+    assert_eq!(set, ["CRYPT_RO_TEST_SECRET"]);
+    assert_eq!(std::env::var("CRYPT_RO_TEST_SECRET").unwrap(), "s3cret");
+    assert_eq!(std::env::var("CRYPT_RO_TEST_PRESET").unwrap(), "from environment");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_encrypt_env_in_place() {
+    let dir = std::env::temp_dir().join(format!("crypt-ro-dotenv-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(".env");
+    std::fs::write(&path, ENV).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_crypt-ro"))
+        .arg("encrypt-env")
+        .arg(&path)
+        .env(KEY_ENV_VAR, "cli key")
+        .env("CRYPT_RO_PBKDF2_ITERATIONS", "10000")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let encrypted = std::fs::read_to_string(&path).unwrap();
+    assert!(!encrypted.contains("hunter2"));
+
+    // Tokens written by the CLI are ordinary tokens, with the key stretched by PBKDF2
+    let vars = decrypt_env(&encrypted, &Cryptor::new(), "cli key").unwrap();
+    assert_eq!(vars["DB_PASSWORD"], "hunter2");
+    let line = encrypted.lines().find(|line| line.starts_with("DB_PASSWORD = ENC[")).unwrap();
+    let token = line.trim_start_matches("DB_PASSWORD = ENC[").split(']').next().unwrap();
+    let info = Cryptor::inspect(token.as_bytes()).unwrap();
+    assert_eq!(info.kdf, Some(Kdf::Pbkdf2 { iterations: 10_000 }));
+
+    // Iteration counts outside the PBKDF2 bounds are rejected
+    std::fs::write(&path, ENV).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_crypt-ro"))
+        .arg("encrypt-env")
+        .arg(&path)
+        .env(KEY_ENV_VAR, "cli key")
+        .env("CRYPT_RO_PBKDF2_ITERATIONS", "10")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), ENV);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_crypt-ro"))
+        .arg("encrypt-env")
+        .arg(&path)
+        .env_remove(KEY_ENV_VAR)
+        .env_remove("CRYPT_RO_KEY_FILE")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,92 @@
+use std::io::{Cursor, Read, Write};
+use crypt_ro::{
+    Chaining, ConfigError, Cryptor, DecryptError, FormatVersion, Kdf, Key, SeekableDecryptReader,
+    SeekableEncryptWriter,
+};
+
+fn is_wrong_key(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<DecryptError>() == Some(&DecryptError::WrongKey)
+}
+
+#[test]
+fn test_fingerprint_is_stable() {
+    let key = Key::new("key");
+    assert_eq!(key.fingerprint(), Key::new("key").fingerprint());
+    assert_eq!(key.fingerprint(), key.clone().fingerprint());
+    assert_ne!(key.fingerprint(), Key::new("key2").fingerprint());
+
+    let text = key.fingerprint().to_string();
+    assert_eq!(text.len(), 16);
+    assert!(text.bytes().all(|b| b.is_ascii_hexdigit()));
+    assert!(!text.contains("6b6579"), "fingerprint leaks the key bytes");
+}
+
+#[test]
+fn test_key_check_recorded_in_header() {
+    let key = Key::new("key");
+    let cryptor = Cryptor::builder().key_check(true).build().unwrap();
+    assert!(cryptor.key_check());
+    assert_eq!(cryptor.format(), FormatVersion::V2);
+
+    let token = cryptor.encrypt(b"message", &key).unwrap();
+    let field = [&[6u8, 8][..], key.fingerprint().as_bytes()].concat();
+    assert_eq!(&token[7..9], &(field.len() as u16).to_be_bytes());
+    assert_eq!(&token[9..9 + field.len()], field.as_slice());
+    assert_eq!(Cryptor::inspect(&token).unwrap().key_fingerprint, Some(key.fingerprint()));
+    assert_eq!(Cryptor::new().decrypt(&token, "key").unwrap(), b"message");
+}
+
+#[test]
+fn test_wrong_key_fails_fast() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = Cryptor::builder().key_check(true).chaining(chaining).build().unwrap();
+        let token = cryptor.encrypt(&[1u8; 1000], "key").unwrap();
+        let err = cryptor.decrypt(&token, "wrong key").unwrap_err();
+        assert!(is_wrong_key(err.as_ref()), "{chaining:?}: {err}");
+        assert_eq!(err.to_string(), "Wrong Key");
+    }
+
+    let cryptor = Cryptor::builder().key_check(true).chaining(Chaining::Counter).build().unwrap();
+    let token = cryptor.encrypt(b"hello random access", "key").unwrap();
+    let err = cryptor.decrypt_range(&token, "wrong key", 0..5).unwrap_err();
+    assert!(is_wrong_key(err.as_ref()));
+    assert_eq!(cryptor.decrypt_range(&token, "key", 0..5).unwrap(), b"hello");
+}
+
+#[test]
+fn test_wrong_key_in_seekable_container() {
+    let cryptor = Cryptor::builder().key_check(true).build().unwrap();
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&cryptor, Vec::new(), "key", 100).unwrap();
+    writer.write_all(&[4u8; 1000]).unwrap();
+    let container = writer.finish().unwrap();
+
+    let Err(err) = SeekableDecryptReader::new(Cursor::new(&container), "wrong key") else {
+        panic!("opened with the wrong key");
+    };
+    assert!(is_wrong_key(err.get_ref().unwrap()));
+    assert!(is_wrong_key(cryptor.decrypt(&container, "wrong key").unwrap_err().as_ref()));
+
+    let mut plain = Vec::new();
+    SeekableDecryptReader::new(Cursor::new(&container), "key").unwrap().read_to_end(&mut plain).unwrap();
+    assert_eq!(plain, [4u8; 1000]);
+}
+
+#[test]
+fn test_tokens_without_check_are_unaffected() {
+    let token = Cryptor::builder().kdf(Kdf::Sha256).build().unwrap().encrypt(b"data", "key").unwrap();
+    assert_eq!(Cryptor::inspect(&token).unwrap().key_fingerprint, None);
+    assert!(!Cryptor::new().decrypt(&token, "wrong key").is_ok_and(|plain| plain == b"data"));
+}
+
+#[test]
+fn test_key_check_validation() {
+    let err = Cryptor::builder()
+        .key_check(true)
+        .kdf(Kdf::Pbkdf2 { iterations: 20_000 })
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ConfigError::KeyCheck(_)));
+
+    let err = Cryptor::builder().key_check(true).format(FormatVersion::V1).build().unwrap_err();
+    assert!(matches!(err, ConfigError::FormatTooOld { option: "a key check", .. }));
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use crypt_ro::{encrypt_deterministic, Cryptor, Kdf, SeekableDecryptReader, KEY_ENV_VAR};
+
+const FILTER: &str = env!("CARGO_BIN_EXE_crypt-ro-git");
+
+fn git(repo: &Path, args: &[&str]) -> Output {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .env(KEY_ENV_VAR, "repository key")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", repo)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+/// Returns `false`, after a note on stderr, when no `git` binary is on PATH.
+fn has_git() -> bool {
+    let found = Command::new("git").arg("--version").output().is_ok_and(|output| output.status.success());
+    if !found {
+        eprintln!("skipping: git is not installed");
+    }
+    found
+}
+
+fn filter(command: &str, input: &[u8], key: &str) -> Output {
+    let mut child = Command::new(FILTER)
+        .arg(command)
+        .env(KEY_ENV_VAR, key)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_deterministic_containers() {
+    let cryptor = Cryptor::new();
+    let plain: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+    let first = encrypt_deterministic(&cryptor, Vec::new(), "key", 1000, &plain).unwrap();
+    let second = encrypt_deterministic(&cryptor, Vec::new(), "key", 1000, &plain).unwrap();
+    assert_eq!(first, second);
+    assert_ne!(first, encrypt_deterministic(&cryptor, Vec::new(), "other", 1000, &plain).unwrap());
+
+    let mut changed = plain.clone();
+    changed[2999] ^= 1;
+    let third = encrypt_deterministic(&cryptor, Vec::new(), "key", 1000, &changed).unwrap();
+    // The nonce covers the whole plaintext, so even the untouched chunks differ
+    assert_ne!(first[..first.len() / 2], third[..third.len() / 2]);
+
+    let mut reader = SeekableDecryptReader::new(std::io::Cursor::new(first), "key").unwrap();
+    let mut decrypted = Vec::new();
+    std::io::Read::read_to_end(&mut reader, &mut decrypted).unwrap();
+    assert_eq!(decrypted, plain);
+
+    // PBKDF2 salts every container at random
+    let salted = Cryptor::builder().kdf(Kdf::Pbkdf2 { iterations: 10_000 }).build().unwrap();
+    assert!(encrypt_deterministic(&salted, Vec::new(), "key", 1000, &plain).is_err());
+}
+
+#[test]
+fn test_filter_round_trip() {
+    let clean = filter("clean", b"secret", "key");
+    assert!(clean.status.success());
+    assert!(!clean.stdout.windows(6).any(|w| w == b"secret"));
+    assert_eq!(filter("clean", b"secret", "key").stdout, clean.stdout);
+    // Already encrypted input is not encrypted twice
+    assert_eq!(filter("clean", &clean.stdout, "key").stdout, clean.stdout);
+
+    assert_eq!(filter("smudge", &clean.stdout, "key").stdout, b"secret");
+    assert_eq!(filter("smudge", b"plain file", "key").stdout, b"plain file");
+
+    let wrong = filter("smudge", &clean.stdout, "other key");
+    assert!(!wrong.status.success());
+    assert!(wrong.stdout.is_empty());
+}
+
+#[test]
+fn test_filter_encrypts_text_encoded_containers() {
+    // A plaintext file holding a hex-encoded container is still plaintext
+    let container = filter("clean", b"secret", "key").stdout;
+    let hex: Vec<u8> = container.iter().flat_map(|b| format!("{b:02x}").into_bytes()).collect();
+    assert!(Cryptor::inspect(&hex).is_ok());
+
+    let clean = filter("clean", &hex, "key");
+    assert!(clean.status.success());
+    assert_ne!(clean.stdout, hex);
+    assert!(Cryptor::inspect(&clean.stdout).unwrap().encoding.is_none());
+
+    let smudge = filter("smudge", &clean.stdout, "key");
+    assert!(smudge.status.success());
+    assert_eq!(smudge.stdout, hex);
+    // Checked out as is, without a key
+    assert_eq!(filter("smudge", &hex, "other key").stdout, hex);
+}
+
+#[test]
+fn test_git_repository() {
+    if !has_git() {
+        return;
+    }
+    let repo = std::env::temp_dir().join(format!("crypt-ro-git-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&repo);
+    fs::create_dir_all(repo.join("secrets")).unwrap();
+
+    git(&repo, &["init", "-q"]);
+    git(&repo, &["config", "user.name", "Test"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+    git(&repo, &["config", "filter.crypt-ro.clean", &format!("'{FILTER}' clean %f")]);
+    git(&repo, &["config", "filter.crypt-ro.smudge", &format!("'{FILTER}' smudge %f")]);
+    git(&repo, &["config", "filter.crypt-ro.required", "true"]);
+    fs::write(repo.join(".gitattributes"), "secrets/** filter=crypt-ro\n").unwrap();
+    fs::write(repo.join("secrets/token.txt"), "super secret token\n").unwrap();
+    fs::write(repo.join("README"), "public\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "initial"]);
+
+    let blob = git(&repo, &["cat-file", "-p", "HEAD:secrets/token.txt"]).stdout;
+    assert!(Cryptor::inspect(&blob).is_ok());
+    assert!(!String::from_utf8_lossy(&blob).contains("super secret"));
+    assert_eq!(git(&repo, &["cat-file", "-p", "HEAD:README"]).stdout, b"public\n");
+
+    // Touching the file without changing it leaves the tree clean
+    fs::write(repo.join("secrets/token.txt"), "super secret token\n").unwrap();
+    git(&repo, &["add", "."]);
+    assert!(git(&repo, &["status", "--porcelain"]).stdout.is_empty());
+    assert!(git(&repo, &["diff", "--cached", "--stat"]).stdout.is_empty());
+
+    // A fresh checkout decrypts the file again
+    fs::remove_file(repo.join("secrets/token.txt")).unwrap();
+    git(&repo, &["checkout", "--", "secrets/token.txt"]);
+    assert_eq!(fs::read_to_string(repo.join("secrets/token.txt")).unwrap(), "super secret token\n");
+
+    fs::write(repo.join("secrets/token.txt"), "rotated token\n").unwrap();
+    let status = git(&repo, &["status", "--porcelain"]).stdout;
+    assert_eq!(String::from_utf8(status).unwrap(), " M secrets/token.txt\n");
+
+    fs::remove_dir_all(&repo).unwrap();
+}
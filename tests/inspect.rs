@@ -0,0 +1,125 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crypt_ro::{
+    Chaining, ConfigError, Cryptor, Encoding, FormatVersion, Kdf, PaddingPolicy, SeekableEncryptWriter,
+    TokenLayout, MAX_KEY_ID_LEN,
+};
+
+#[test]
+fn test_inspect_legacy_token() {
+    let token = Cryptor::new().encrypt_text("my secret message", "key").unwrap();
+    let info = Cryptor::inspect(token.as_bytes()).unwrap();
+    assert_eq!(info.format, FormatVersion::V1);
+    assert_eq!(info.encoding, Some(Encoding::Base64UrlNoPad));
+    assert_eq!(info.layout, TokenLayout::Chained);
+    assert_eq!(info.matrix, None);
+    assert_eq!(info.kdf, None);
+    assert!(info.payload_len.contains(&17));
+
+    // Padded base64 from another port
+    let info = Cryptor::inspect(b"q2Vyb2MUUm8MFAoSHAoBFhE-G38KIANBchBXbnMFcnUB2Q==").unwrap();
+    assert_eq!(info.encoding, Some(Encoding::Base64Url));
+    assert!(info.payload_len.contains(&17));
+}
+
+#[test]
+fn test_inspect_header_fields() {
+    let cryptor = Cryptor::builder()
+        .matrix(64)
+        .rounds(3)
+        .kdf(Kdf::Pbkdf2 { iterations: 20_000 })
+        .key_id("billing-2024")
+        .build()
+        .unwrap();
+    let token = cryptor.encrypt(b"hello", "key").unwrap();
+    let info = Cryptor::inspect(&token).unwrap();
+    assert_eq!(info.format, FormatVersion::V2);
+    assert_eq!(info.encoding, None);
+    assert_eq!(info.matrix, Some(64));
+    assert_eq!(info.rounds, Some(3));
+    assert_eq!(info.kdf, Some(Kdf::Pbkdf2 { iterations: 20_000 }));
+    assert_eq!(info.key_id.as_deref(), Some("billing-2024"));
+    assert_eq!(info.payload_len, 0..=54);
+    assert_eq!(Cryptor::new().decrypt(&token, "key").unwrap(), b"hello");
+}
+
+#[test]
+fn test_inspect_detects_encodings() {
+    for encoding in [Encoding::Base64UrlNoPad, Encoding::Base64Url, Encoding::Base64, Encoding::Hex] {
+        let cryptor = Cryptor::builder().encoding(encoding).chaining(Chaining::Counter).build().unwrap();
+        let token = cryptor.encrypt_text("some text to encrypt here", "key").unwrap();
+        let info = Cryptor::inspect(token.as_bytes()).unwrap();
+        assert_eq!(info.layout, TokenLayout::Counter);
+        assert!(info.payload_len.contains(&25), "{encoding:?}: {:?}", info.payload_len);
+        // Base64 variants without padding or special characters read the same
+        if encoding == Encoding::Hex || encoding == Encoding::Base64UrlNoPad {
+            assert_eq!(info.encoding, Some(encoding));
+        }
+    }
+}
+
+#[test]
+fn test_inspect_bounds_are_tight() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        for padding in [PaddingPolicy::Minimal, PaddingPolicy::Bucket(100), PaddingPolicy::Padme] {
+            let cryptor = Cryptor::builder().chaining(chaining).padding(padding).format(FormatVersion::V2).build().unwrap();
+            for len in [0, 1, 22, 23, 99, 100, 101, 500] {
+                let token = cryptor.encrypt(&vec![0u8; len], "key").unwrap();
+                let bounds = Cryptor::inspect(&token).unwrap().payload_len;
+                assert!(bounds.contains(&len), "{chaining:?} {padding:?} {len}: {bounds:?}");
+                // Every length in the bounds encrypts to the same size
+                for other in [*bounds.start(), *bounds.end()] {
+                    let other = cryptor.encrypt(&vec![0u8; other], "key").unwrap();
+                    assert_eq!(other.len(), token.len());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_inspect_containers() {
+    let data = vec![5u8; 5000];
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&Cryptor::new(), Vec::new(), "key", 1024).unwrap();
+    writer.write_all(&data).unwrap();
+    let container = writer.finish().unwrap();
+    let info = Cryptor::inspect(&container).unwrap();
+    assert_eq!(info.layout, TokenLayout::Seekable { chunk_size: 1024 });
+    assert!(info.payload_len.contains(&5000));
+    assert!(*info.payload_len.start() >= 4096);
+
+    assert!(Cryptor::inspect(b"CRO").is_err());
+}
+
+#[test]
+fn test_key_id_validation() {
+    let long = "x".repeat(MAX_KEY_ID_LEN + 1);
+    assert_eq!(Cryptor::builder().key_id(long).build(), Err(ConfigError::KeyId(MAX_KEY_ID_LEN + 1)));
+    let cryptor = Cryptor::builder().key_id("k1").build().unwrap();
+    assert_eq!(cryptor.key_id(), Some("k1"));
+    assert_eq!(cryptor.format(), FormatVersion::V2);
+}
+
+#[test]
+fn test_cli_inspect() {
+    let token = Cryptor::builder().key_id("cli-key").build().unwrap().encrypt_text("data", "key").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_crypt-ro")).args(["inspect", &token]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("format:      V2"), "{stdout}");
+    assert!(stdout.contains("key id:      cli-key"), "{stdout}");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crypt-ro"))
+        .arg("inspect")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(token.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8(output.stdout).unwrap().contains("cli-key"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_crypt-ro")).args(["inspect", "not a token"]).output().unwrap();
+    assert!(!output.status.success());
+}
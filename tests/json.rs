@@ -0,0 +1,122 @@
+#![cfg(feature = "json")]
+
+use crypt_ro::{decrypt_json_paths, encrypt_json_paths, Chaining, Cryptor, Key, JSON_PREFIX};
+use serde_json::{json, Value};
+
+fn payload() -> Value {
+    json!({
+        "user": {"email": "ann@example.com", "name": "Ann", "age": 41},
+        "cards": [
+            {"number": "4111 1111 1111 1111", "brand": "visa"},
+            {"number": "5500 0000 0000 0004", "brand": "mc"}
+        ],
+        "tags": ["a", "b"],
+        "odd key": {"x": null}
+    })
+}
+
+#[test]
+fn test_round_trip_keeps_types() {
+    let cryptor = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+    let key = Key::new("json key");
+    let paths = ["$.user.email", "$.user.age", "$.cards[*].number", "$.tags", "$['odd key'].x"];
+
+    let mut value = payload();
+    assert_eq!(encrypt_json_paths(&mut value, &paths, &cryptor, &key).unwrap(), 6);
+    let text = value.to_string();
+    for plain in ["ann@example.com", "4111", "5500"] {
+        assert!(!text.contains(plain), "{plain} in {text}");
+    }
+    assert_eq!(value["user"]["name"], "Ann");
+    assert_eq!(value["cards"][1]["brand"], "mc");
+    assert!(value["user"]["age"].as_str().unwrap().starts_with(JSON_PREFIX));
+    assert!(value["tags"].is_string());
+
+    assert_eq!(decrypt_json_paths(&mut value, &paths, &cryptor, &key).unwrap(), 6);
+    assert_eq!(value, payload());
+}
+
+#[test]
+fn test_mixed_documents() {
+    let cryptor = Cryptor::new();
+    let mut value = payload();
+    encrypt_json_paths(&mut value, &["$.user.email"], &cryptor, "key").unwrap();
+    let once = value.clone();
+
+    // Encrypting again skips leaves that are already encrypted
+    assert_eq!(encrypt_json_paths(&mut value, &["$.user.*"], &cryptor, "key").unwrap(), 2);
+    assert_eq!(value["user"]["email"], once["user"]["email"]);
+
+    // Decrypting a wider path only touches encrypted leaves
+    assert_eq!(decrypt_json_paths(&mut value, &["$.*.*"], &cryptor, "key").unwrap(), 3);
+    assert_eq!(value, payload());
+}
+
+#[test]
+fn test_plaintext_with_prefix_is_encrypted() {
+    let cryptor = Cryptor::new();
+    let original = json!({"note": "cro:not a token", "lookalike": "cro:AAAA", "other": "cro:"});
+    let mut value = original.clone();
+    assert_eq!(encrypt_json_paths(&mut value, &["$.*"], &cryptor, "key").unwrap(), 3);
+    assert!(!value.to_string().contains("not a token"));
+    assert_eq!(decrypt_json_paths(&mut value, &["$.*"], &cryptor, "key").unwrap(), 3);
+    assert_eq!(value, original);
+
+    // Leaves encrypted under another key are not skipped as already encrypted
+    let mut value = original.clone();
+    encrypt_json_paths(&mut value, &["$.note"], &cryptor, "other key").unwrap();
+    assert_eq!(encrypt_json_paths(&mut value, &["$.note"], &cryptor, "key").unwrap(), 1);
+}
+
+#[test]
+fn test_indices_and_missing_paths() {
+    let cryptor = Cryptor::new();
+    let mut value = payload();
+    let paths = ["$.cards[1].number", "$.cards[5].number", "$.nope.deeper", "$.tags[0]"];
+    assert_eq!(encrypt_json_paths(&mut value, &paths, &cryptor, "key").unwrap(), 2);
+    assert_eq!(value["cards"][0]["number"], "4111 1111 1111 1111");
+    assert_eq!(value["tags"][1], "b");
+
+    let err = decrypt_json_paths(&mut value, &paths, &cryptor, "wrong").unwrap_err();
+    assert!(!err.to_string().is_empty());
+    decrypt_json_paths(&mut value, &paths, &cryptor, "key").unwrap();
+    assert_eq!(value, payload());
+}
+
+#[test]
+fn test_invalid_paths() {
+    let cryptor = Cryptor::new();
+    for path in ["user.email", "$.", "$[x]", "$[0", "$.a b[", "$x"] {
+        let err = encrypt_json_paths(&mut payload(), &[path], &cryptor, "key").unwrap_err();
+        assert_eq!(err.to_string(), format!("Invalid JSON Path {path}"));
+    }
+    let mut root = json!(12);
+    encrypt_json_paths(&mut root, &["$"], &cryptor, "key").unwrap();
+    decrypt_json_paths(&mut root, &["$"], &cryptor, "key").unwrap();
+    assert_eq!(root, json!(12));
+}
+
+#[test]
+fn test_overlapping_paths() {
+    let cryptor = Cryptor::new();
+    for paths in [
+        ["$.user.email", "$.user"],
+        ["$.user", "$['user'].name"],
+        ["$.cards[*].number", "$.cards[1]"],
+        ["$.*", "$.tags[0]"],
+        ["$.tags", "$.tags"],
+    ] {
+        let mut value = payload();
+        let err = encrypt_json_paths(&mut value, &paths, &cryptor, "key").unwrap_err();
+        assert_eq!(err.to_string(), format!("Overlapping JSON Paths {} and {}", paths[0], paths[1]));
+        assert_eq!(value, payload());
+        assert!(decrypt_json_paths(&mut value, &paths, &cryptor, "key").is_err());
+    }
+
+    // Siblings and different indices do not overlap
+    let paths = ["$.user.email", "$.user.name", "$.cards[0].number", "$.cards[1].brand"];
+    let mut value = payload();
+    assert_eq!(encrypt_json_paths(&mut value, &paths, &cryptor, "key").unwrap(), 4);
+    assert_eq!(decrypt_json_paths(&mut value, &paths, &cryptor, "key").unwrap(), 4);
+    assert_eq!(value, payload());
+}
@@ -1,4 +1,4 @@
-use crypt_ro::Cryptor;
+use crypt_ro::{Cryptor, FixedCryptor, Kdf, PaddingPolicy};
 
 #[test]
 fn test_decrypt_python() {
@@ -18,6 +18,15 @@ fn test_decrypt_js() {
     assert_eq!(decrypted, secret);
 }
 #[test]
+fn test_decrypt_legacy_token_starting_with_magic() {
+    let cryptor = Cryptor::new();
+    let token = crypt_ro::Encoding::Hex
+        .decode("43524fe42339205a87850004006700f8456b3079561c4785ac3670e88c34ac71026a")
+        .unwrap();
+    assert!(token.starts_with(b"CRO"));
+    assert_eq!(cryptor.decrypt(&token, "legacy key").unwrap(), b"legacy");
+}
+#[test]
 fn test_encrypt_decrypt_roundtrip() {
     let cryptor = Cryptor::new(); // Default 32-byte matrix
     let text = "abc".repeat(1000);
@@ -67,25 +76,6 @@ fn test_empty_input() {
     assert_eq!(decrypted, "");
 }
 
-fn count_char_differences(text1: &str, text2: &str) -> usize {
-    let mut differences = 0;
-
-    // Compare characters up to the length of the shorter string
-    let mut chars1 = text1.chars();
-    let mut chars2 = text2.chars();
-
-    loop {
-        match (chars1.next(), chars2.next()) {
-            (Some(c1), Some(c2)) if c1 != c2 => differences += 1,
-            (Some(_), None) | (None, Some(_)) => differences += 1, // One string is longer
-            (None, None) => break,                                 // Both strings ended
-            _ => continue,                                         // Characters are equal
-        }
-    }
-
-    differences
-}
-
 #[test]
 fn test_special_characters() {
     let cryptor = Cryptor::new();
@@ -182,4 +172,65 @@ fn test_url_safe_base64() {
     assert!(!encrypted.contains('+'));
     assert!(!encrypted.contains('/'));
     assert!(!encrypted.ends_with('='));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_fixed_decrypt_python() {
+    let cryptor = FixedCryptor::<32>::new();
+    let decrypted = cryptor.decrypt_text("B2VzbxcUAgMTFh7eT8JlA3U9Cg0KRQNhElMQCnNkcqgDFg", "strong password").unwrap();
+    assert_eq!(decrypted, "my secret message");
+}
+
+#[test]
+fn test_fixed_matches_dynamic() {
+    let text = "The quick brown fox jumps over the lazy dog".repeat(7);
+    let key = "fixed matrix key";
+
+    let mut dynamic = Cryptor::new();
+    dynamic.set_matrix(16);
+    let fixed = FixedCryptor::<16>::new();
+
+    let encrypted = fixed.encrypt_text(&text, key).unwrap();
+    assert_eq!(dynamic.decrypt_text(&encrypted, key).unwrap(), text);
+
+    let encrypted = dynamic.encrypt_text(&text, key).unwrap();
+    assert_eq!(fixed.decrypt_text(&encrypted, key).unwrap(), text);
+}
+
+#[test]
+fn test_fixed_from_builder() {
+    let fixed = FixedCryptor::<64>::from_builder(Cryptor::builder().kdf(Kdf::Sha256).padding(PaddingPolicy::Bucket(256))).unwrap();
+    assert_eq!(fixed.cryptor().matrix(), 64);
+    let encrypted = fixed.encrypt(b"configured", "key").unwrap();
+    assert!(encrypted.starts_with(b"CRO"));
+    assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"configured");
+
+    assert!(FixedCryptor::<7>::from_builder(Cryptor::builder()).is_err());
+}
+
+#[test]
+fn test_fixed_different_matrix_sizes() {
+    fn roundtrip<const N: usize>(data: &[u8], key: &str) {
+        let cryptor = FixedCryptor::<N>::new();
+        let encrypted = cryptor.encrypt(data, key).unwrap();
+        assert_eq!((encrypted.len() - 2) % N, 0);
+        assert_eq!(cryptor.decrypt(&encrypted, key).unwrap(), data);
+    }
+
+    for data in [&b""[..], b"x", &[0xAB; 100], &[0x00; 1000]] {
+        roundtrip::<1>(data, "key");
+        roundtrip::<7>(data, "key");
+        roundtrip::<32>(data, "key");
+        roundtrip::<128>(data, "");
+    }
+}
+
+#[test]
+fn test_large_payload_roundtrip() {
+    let cryptor = Cryptor::new();
+    let data: Vec<u8> = (0..1u32 << 20).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+    let key = "large payload key";
+
+    let encrypted = cryptor.encrypt(&data, key).unwrap();
+    assert_eq!(cryptor.decrypt(&encrypted, key).unwrap(), data);
+}
@@ -0,0 +1,185 @@
+use std::io::{Cursor, Read, Write};
+use crypt_ro::{
+    Chaining, ConfigError, Cryptor, FormatVersion, KeyContext, PaddingPolicy, Pipeline, SeekableDecryptReader,
+    SeekableEncryptWriter, Stage, Transform, MAX_PADDING_SIZE,
+};
+
+fn padded(policy: PaddingPolicy, chaining: Chaining) -> Cryptor {
+    Cryptor::builder().padding(policy).chaining(chaining).build().unwrap()
+}
+
+#[test]
+fn test_minimal_padding_lengths() {
+    let cryptor = Cryptor::new();
+    assert_eq!(cryptor.padding(), PaddingPolicy::Minimal);
+    for len in [0, 1, 21, 22, 23, 100] {
+        let encrypted = cryptor.encrypt(&vec![b'a'; len], "key").unwrap();
+        assert_eq!(encrypted.len(), (10 + len).div_ceil(32) * 32 + 2, "length {len}");
+    }
+}
+
+#[test]
+fn test_padding_bytes_are_random() {
+    // With only the mix stage the padded plaintext can be recovered from the token
+    let pipeline = Pipeline::new(vec![Stage::Mix]);
+    let cryptor = Cryptor::builder().pipeline(pipeline).build().unwrap();
+    let key = [b'k'; 32];
+    let data = b"short";
+
+    let encrypted = cryptor.encrypt(data, "k").unwrap();
+    let header_len = 9 + 2 + cryptor.pipeline().to_bytes().len();
+    let mut body = encrypted[header_len..encrypted.len() - 2].to_vec();
+    Stage::Mix.inverse(&mut body, &KeyContext::new(&key, 32, 0));
+
+    assert_eq!(&body[..4], &(data.len() as u32).to_be_bytes());
+    assert_eq!(&body[10..15], data);
+    let padding = &body[15..];
+    assert_eq!(padding.len(), 17);
+    assert!(padding.iter().any(|&b| b != 1), "padding is the constant fill");
+}
+
+#[test]
+fn test_padding_differs_within_a_second() {
+    let pipeline = Pipeline::new(vec![Stage::Mix]);
+    let cryptor = Cryptor::builder().pipeline(pipeline).build().unwrap();
+    let header_len = 9 + 2 + cryptor.pipeline().to_bytes().len();
+    let recover = |encrypted: Vec<u8>| {
+        let mut body = encrypted[header_len..encrypted.len() - 2].to_vec();
+        Stage::Mix.inverse(&mut body, &KeyContext::new(&[b'k'; 32], 32, 0));
+        body
+    };
+
+    // Both tokens are made well within the same second
+    let first = recover(cryptor.encrypt(b"short", "k").unwrap());
+    let second = recover(cryptor.encrypt(b"short", "k").unwrap());
+    assert_ne!(first[4..10], second[4..10], "random prefix repeated");
+    assert_ne!(first[15..], second[15..], "padding repeated");
+}
+
+#[test]
+fn test_bucket_padding_hides_length() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = padded(PaddingPolicy::Bucket(256), chaining);
+        let lengths: Vec<usize> = [0, 1, 100, 255, 256]
+            .iter()
+            .map(|&len| cryptor.encrypt(&vec![7u8; len], "key").unwrap().len())
+            .collect();
+        assert!(lengths.iter().all(|&len| len == lengths[0]), "{chaining:?}: {lengths:?}");
+
+        let longer = cryptor.encrypt(&[7u8; 257], "key").unwrap();
+        assert!(longer.len() >= lengths[0] + 256);
+        assert_eq!(Cryptor::new().decrypt(&longer, "key").unwrap(), vec![7u8; 257]);
+    }
+}
+
+#[test]
+fn test_fixed_padding() {
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = padded(PaddingPolicy::Fixed(64), chaining);
+        let short = cryptor.encrypt(b"a", "key").unwrap();
+        let full = cryptor.encrypt(&[b'b'; 64], "key").unwrap();
+        assert_eq!(short.len(), full.len());
+        assert_eq!(Cryptor::new().decrypt(&short, "key").unwrap(), b"a");
+        assert_eq!(Cryptor::new().decrypt(&full, "key").unwrap(), [b'b'; 64]);
+
+        let err = cryptor.encrypt(&[0u8; 65], "key").unwrap_err();
+        assert_eq!(err.to_string(), "Message Exceeds Fixed Padding Size");
+    }
+}
+
+#[test]
+fn test_padding_validation() {
+    assert_eq!(
+        Cryptor::builder().padding(PaddingPolicy::Bucket(0)).build(),
+        Err(ConfigError::Padding("bucket size must be at least 1"))
+    );
+    assert_eq!(
+        Cryptor::builder().padding(PaddingPolicy::Fixed(0)).build(),
+        Err(ConfigError::Padding("fixed size must be at least 1"))
+    );
+    assert!(Cryptor::builder().padding(PaddingPolicy::Fixed(1)).build().is_ok());
+
+    for policy in [PaddingPolicy::Bucket(u32::MAX), PaddingPolicy::Fixed(MAX_PADDING_SIZE + 1)] {
+        assert_eq!(
+            Cryptor::builder().padding(policy).build(),
+            Err(ConfigError::Padding("padding size must be at most 16 MiB"))
+        );
+    }
+    let largest = Cryptor::builder().padding(PaddingPolicy::Bucket(MAX_PADDING_SIZE)).build().unwrap();
+    let encrypted = largest.encrypt(b"hi", "key").unwrap();
+    assert!(encrypted.len() > MAX_PADDING_SIZE as usize);
+    assert_eq!(largest.decrypt(&encrypted, "key").unwrap(), b"hi");
+}
+
+#[test]
+fn test_power_of_two_padding() {
+    let cryptor = padded(PaddingPolicy::PowerOfTwo, Chaining::Counter);
+    let sizes: Vec<usize> = [100, 128, 129, 200, 256]
+        .iter()
+        .map(|&len| cryptor.encrypt(&vec![1u8; len], "key").unwrap().len())
+        .collect();
+    assert_eq!(sizes[0], sizes[1]);
+    assert_eq!(sizes[2], sizes[3]);
+    assert_eq!(sizes[3], sizes[4]);
+    assert!(sizes[2] > sizes[1]);
+}
+
+#[test]
+fn test_padme_padding() {
+    let cryptor = padded(PaddingPolicy::Padme, Chaining::Counter);
+    let size = |len: usize| cryptor.encrypt(&vec![1u8; len], "key").unwrap().len();
+    // Lengths 1000..=1024 share the Padmé length 1024, with at most 12% overhead
+    assert_eq!(size(1000), size(1024));
+    assert!(size(1000) <= 1000 * 112 / 100 + 64);
+    assert!(size(1025) > size(1024));
+    for len in [0, 1, 2, 3, 17, 1000, 5000] {
+        let data = vec![9u8; len];
+        let encrypted = cryptor.encrypt(&data, "key").unwrap();
+        assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), data, "length {len}");
+    }
+}
+
+#[test]
+fn test_policy_recorded_in_header() {
+    let minimal = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+    let minimal_header = minimal.encrypt(b"x", "key").unwrap()[7..9].to_vec();
+    assert_eq!(minimal_header, [0, 0]);
+
+    for (policy, field) in [
+        (PaddingPolicy::Bucket(64), vec![4, 5, 1, 0, 0, 0, 64]),
+        (PaddingPolicy::Fixed(300), vec![4, 5, 2, 0, 0, 1, 44]),
+        (PaddingPolicy::PowerOfTwo, vec![4, 1, 3]),
+        (PaddingPolicy::Padme, vec![4, 1, 4]),
+    ] {
+        let cryptor = Cryptor::builder().padding(policy).build().unwrap();
+        assert_eq!(cryptor.format(), FormatVersion::V2);
+        let encrypted = cryptor.encrypt(b"message", "key").unwrap();
+        assert_eq!(&encrypted[7..9], &(field.len() as u16).to_be_bytes());
+        assert_eq!(&encrypted[9..9 + field.len()], field.as_slice(), "{policy:?}");
+        // Any cryptor decrypts regardless of the sender's policy
+        assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"message");
+    }
+}
+
+#[test]
+fn test_padding_policy_needs_header() {
+    let err = Cryptor::builder()
+        .padding(PaddingPolicy::Padme)
+        .format(FormatVersion::V1)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ConfigError::FormatTooOld { option: "a padding policy", .. }));
+}
+
+#[test]
+fn test_segmented_tokens_ignore_policy() {
+    let cryptor = padded(PaddingPolicy::Fixed(4096), Chaining::Chained);
+    let data = vec![3u8; 10_000];
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&cryptor, Vec::new(), "key", 1000).unwrap();
+    writer.write_all(&data).unwrap();
+    let container = writer.finish().unwrap();
+    let mut reader = SeekableDecryptReader::new(Cursor::new(container), "key").unwrap();
+    let mut plain = Vec::new();
+    reader.read_to_end(&mut plain).unwrap();
+    assert_eq!(plain, data);
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "parallel")]
+
+use crypt_ro::{Cryptor, SEGMENT_SIZE};
+
+fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_parallel_roundtrip() {
+    let cryptor = Cryptor::new();
+    let key = "parallel key";
+
+    for len in [0, 1, 1000, SEGMENT_SIZE, SEGMENT_SIZE + 1, SEGMENT_SIZE * 5 / 2] {
+        let data = pseudo_random_bytes(len, len as u64);
+        let encrypted = cryptor.encrypt_parallel(&data, key).unwrap();
+        assert_eq!(cryptor.decrypt_parallel(&encrypted, key).unwrap(), data, "length {len}");
+    }
+}
+
+#[test]
+fn test_sequential_decrypt_accepts_parallel_tokens() {
+    let cryptor = Cryptor::new();
+    let data = pseudo_random_bytes(SEGMENT_SIZE * 2 + 17, 1);
+
+    let encrypted = cryptor.encrypt_parallel(&data, "key").unwrap();
+    assert_eq!(cryptor.decrypt(&encrypted, "key").unwrap(), data);
+}
+
+#[test]
+fn test_parallel_matrix_read_from_header() {
+    let mut cryptor = Cryptor::new();
+    cryptor.set_matrix(64);
+    let data = pseudo_random_bytes(SEGMENT_SIZE + 3, 2);
+
+    let encrypted = cryptor.encrypt_parallel(&data, "key").unwrap();
+    assert_eq!(Cryptor::new().decrypt_parallel(&encrypted, "key").unwrap(), data);
+}
+
+#[test]
+fn test_parallel_segments_are_independently_keyed() {
+    let cryptor = Cryptor::new();
+    let data = vec![0u8; SEGMENT_SIZE * 2];
+
+    let encrypted = cryptor.encrypt_parallel(&data, "key").unwrap();
+    // header (9) | segment size (4) | count (4) | two lengths (4 each)
+    let first_len = u32::from_be_bytes(encrypted[17..21].try_into().unwrap()) as usize;
+    let first = &encrypted[25..25 + first_len];
+    let second = &encrypted[25 + first_len..];
+    assert_eq!(first.len(), second.len());
+    assert_ne!(first, second);
+}
+
+#[test]
+fn test_parallel_wrong_key_fails() {
+    let cryptor = Cryptor::new();
+    let data = pseudo_random_bytes(SEGMENT_SIZE * 2, 3);
+
+    let encrypted = cryptor.encrypt_parallel(&data, "right key").unwrap();
+    assert!(cryptor.decrypt_parallel(&encrypted, "wrong key").is_err());
+}
+
+#[test]
+fn test_parallel_rejects_malformed_tokens() {
+    let cryptor = Cryptor::new();
+    let data = pseudo_random_bytes(SEGMENT_SIZE + 1, 4);
+    let encrypted = cryptor.encrypt_parallel(&data, "key").unwrap();
+
+    assert!(cryptor.decrypt_parallel(&encrypted[..encrypted.len() - 1], "key").is_err());
+    assert!(cryptor.decrypt_parallel(&encrypted[..20], "key").is_err());
+
+    let mut extended = encrypted.clone();
+    extended.push(0);
+    assert!(cryptor.decrypt_parallel(&extended, "key").is_err());
+
+    let legacy = cryptor.encrypt(&data, "key").unwrap();
+    assert!(cryptor.decrypt_parallel(&legacy, "key").is_err());
+
+    // header (9) | segment size (4): a huge segment size must not be allocated up front
+    let mut oversized = encrypted.clone();
+    oversized[9..13].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(cryptor.decrypt(&oversized, "key").is_err());
+    assert!(cryptor.decrypt_parallel(&oversized, "key").is_err());
+    assert!(Cryptor::inspect(&oversized).is_ok());
+}
+
+#[test]
+fn test_parallel_key_commitment() {
+    let cryptor = Cryptor::builder().key_commitment(true).key_check(true).build().unwrap();
+    let data = pseudo_random_bytes(SEGMENT_SIZE + 100, 7);
+    let encrypted = cryptor.encrypt_parallel(&data, "key").unwrap();
+    assert_eq!(cryptor.decrypt_parallel(&encrypted, "key").unwrap(), data);
+    assert_eq!(cryptor.decrypt(&encrypted, "key").unwrap(), data);
+    assert!(cryptor.decrypt_parallel(&encrypted, "other").is_err());
+
+    let mut tampered = encrypted.clone();
+    tampered[encrypted.len() / 2] ^= 1;
+    assert_eq!(cryptor.decrypt_parallel(&tampered, "key").unwrap_err().to_string(), "Authentication Failed");
+
+    let uncommitted = Cryptor::new().encrypt_parallel(&data, "key").unwrap();
+    assert_eq!(cryptor.decrypt_parallel(&uncommitted, "key").unwrap_err().to_string(), "Authentication Failed");
+}
@@ -0,0 +1,135 @@
+use crypt_ro::{Chaining, ConfigError, Cryptor, FormatVersion, KeyContext, Pipeline, Stage, Transform};
+
+const ALL_STAGES: [Stage; 6] = [
+    Stage::Shuffle { step: 1 },
+    Stage::Shuffle { step: 5 },
+    Stage::BlockShuffle { step: 1 },
+    Stage::BlockShuffle { step: 2 },
+    Stage::Substitute,
+    Stage::Mix,
+];
+
+#[test]
+fn test_stages_are_invertible() {
+    let key: Vec<u8> = (0..32).map(|i| i * 7 + 3).collect();
+    let ctx = KeyContext::new(&key, 32, 1234);
+    let original: Vec<u8> = (0..96u32).map(|i| (i * 11) as u8).collect();
+
+    for stage in ALL_STAGES {
+        let mut data = original.clone();
+        stage.forward(&mut data, &ctx);
+        assert_ne!(data, original, "{stage} left the data unchanged");
+        stage.inverse(&mut data, &ctx);
+        assert_eq!(data, original, "{stage}");
+    }
+}
+
+#[test]
+fn test_standard_pipelines() {
+    assert_eq!(Pipeline::standard(FormatVersion::V1).to_string(), "shuffle:5,block-shuffle:2,mix");
+    assert_eq!(Pipeline::standard(FormatVersion::V2), Pipeline::standard(FormatVersion::V1));
+    assert_eq!(Pipeline::standard(FormatVersion::V3).to_string(), "shuffle:5,block-shuffle:2,substitute,mix");
+    assert_eq!(Cryptor::new().pipeline(), Pipeline::standard(FormatVersion::V1));
+}
+
+#[test]
+fn test_custom_pipeline_roundtrip() {
+    let pipelines = [
+        "mix",
+        "substitute,mix,substitute",
+        "shuffle:1,mix,block-shuffle:3,substitute,mix",
+    ];
+    for description in pipelines {
+        let pipeline: Pipeline = description.parse().unwrap();
+        for rounds in [1, 3] {
+            let cryptor = Cryptor::builder().pipeline(pipeline.clone()).rounds(rounds).build().unwrap();
+            assert_eq!(cryptor.format(), FormatVersion::V2);
+            for len in [0, 5, 100] {
+                let data: Vec<u8> = (0..len).map(|i| (i * 3) as u8).collect();
+                let encrypted = cryptor.encrypt(&data, "key").unwrap();
+                // The header carries the pipeline, so any Cryptor can decrypt
+                assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), data, "{description}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pipeline_description_roundtrip() {
+    let pipeline = Pipeline::new(ALL_STAGES.to_vec());
+    assert_eq!(Pipeline::from_bytes(&pipeline.to_bytes()).unwrap(), pipeline);
+    assert_eq!(pipeline.to_string().parse::<Pipeline>().unwrap(), pipeline);
+    assert_eq!(" shuffle:2 , mix ".parse::<Pipeline>().unwrap().stages(), [Stage::Shuffle { step: 2 }, Stage::Mix]);
+
+    assert!("shuffle".parse::<Pipeline>().is_err());
+    assert!("mix:2".parse::<Pipeline>().is_err());
+    assert!("rotate".parse::<Pipeline>().is_err());
+    assert!(Pipeline::from_bytes(&[]).is_err());
+    assert!(Pipeline::from_bytes(&[1]).is_err());
+    assert!(Pipeline::from_bytes(&[9]).is_err());
+    assert!(Pipeline::from_bytes(&[1, 0]).is_err());
+
+    // Text is held to the same rules as header bytes
+    for description in ["shuffle:0", "mix,uniform-block-shuffle:0"] {
+        assert!(description.parse::<Pipeline>().is_err(), "{description}");
+    }
+    let too_long = vec!["mix"; 33].join(",");
+    assert!(too_long.parse::<Pipeline>().is_err());
+}
+
+#[test]
+#[should_panic(expected = "matrix bytes long")]
+fn test_key_context_rejects_short_key() {
+    KeyContext::new(&[], 16, 0);
+}
+
+#[test]
+#[should_panic(expected = "matrix size")]
+fn test_key_context_rejects_zero_matrix() {
+    KeyContext::new(&[], 0, 0);
+}
+
+#[test]
+fn test_pipeline_validation() {
+    let build = |pipeline: Pipeline| Cryptor::builder().pipeline(pipeline).build();
+    assert!(matches!(build(Pipeline::new(vec![])), Err(ConfigError::Pipeline(_))));
+    assert!(matches!(build(Pipeline::new(vec![Stage::Mix; 33])), Err(ConfigError::Pipeline(_))));
+    assert!(matches!(build(Pipeline::new(vec![Stage::Shuffle { step: 0 }])), Err(ConfigError::Pipeline(_))));
+    assert!(matches!(
+        Cryptor::builder().pipeline(Pipeline::new(vec![Stage::Mix])).chaining(Chaining::Counter).build(),
+        Err(ConfigError::Pipeline(_))
+    ));
+    assert!(matches!(
+        Cryptor::builder().pipeline(Pipeline::new(vec![Stage::Mix])).format(FormatVersion::V1).build(),
+        Err(ConfigError::FormatTooOld { .. })
+    ));
+}
+
+#[test]
+fn test_custom_transform() {
+    /// Rotates every byte left by the first key bit count.
+    struct Rotate;
+
+    impl Transform for Rotate {
+        fn forward(&self, data: &mut [u8], ctx: &KeyContext<'_>) {
+            let bits = ctx.key()[0].count_ones();
+            data.iter_mut().for_each(|b| *b = b.rotate_left(bits));
+        }
+
+        fn inverse(&self, data: &mut [u8], ctx: &KeyContext<'_>) {
+            let bits = ctx.key()[0].count_ones();
+            data.iter_mut().for_each(|b| *b = b.rotate_right(bits));
+        }
+    }
+
+    let ctx = KeyContext::new(&[0b0101_0101; 16], 16, 0);
+    let transforms: [&dyn Transform; 3] = [&Rotate, &Stage::Mix, &Pipeline::standard(FormatVersion::V3)];
+    let mut data = *b"0123456789abcdef0123456789abcdef";
+    for transform in transforms {
+        transform.forward(&mut data, &ctx);
+    }
+    for transform in transforms.iter().rev() {
+        transform.inverse(&mut data, &ctx);
+    }
+    assert_eq!(&data, b"0123456789abcdef0123456789abcdef");
+}
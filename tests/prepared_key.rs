@@ -0,0 +1,34 @@
+use crypt_ro::{Cryptor, FormatVersion, Key};
+
+fn sample(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 256) as u8).collect()
+}
+
+#[test]
+fn test_prepared_key_matches_plain_key() {
+    for format in [FormatVersion::V1, FormatVersion::V2, FormatVersion::V4, FormatVersion::V5] {
+        let rounds = if format == FormatVersion::V1 { 1 } else { 3 };
+        let cryptor = Cryptor::builder().format(format).rounds(rounds).build().unwrap();
+        let key = Key::new("cached permutations");
+        for len in [0, 10, 100, 1000, 100, 10] {
+            let data = sample(len);
+            // Repeated calls reuse the round material cached in `key`
+            for _ in 0..2 {
+                let encrypted = cryptor.encrypt(&data, &key).unwrap();
+                assert_eq!(cryptor.decrypt(&encrypted, "cached permutations").unwrap(), data, "{format:?}, length {len}");
+                let encrypted = cryptor.encrypt(&data, "cached permutations").unwrap();
+                assert_eq!(cryptor.decrypt(&encrypted, &key).unwrap(), data, "{format:?}, length {len}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_prepared_key_long_messages() {
+    // Long messages share the cached round material of short ones
+    let cryptor = Cryptor::new();
+    let key = Key::new("long");
+    let data = sample((1 << 20) + 1);
+    let encrypted = cryptor.encrypt(&data, &key).unwrap();
+    assert_eq!(cryptor.decrypt(&encrypted, &key).unwrap(), data);
+}
@@ -0,0 +1,78 @@
+use std::io::{Cursor, Read, Write};
+use crypt_ro::{Chaining, ConfigError, Cryptor, FormatVersion, Preset, SeekableDecryptReader, SeekableEncryptWriter, MAX_ROUNDS};
+
+fn rounds_cryptor(rounds: u32, chaining: Chaining) -> Cryptor {
+    Cryptor::builder().rounds(rounds).chaining(chaining).build().unwrap()
+}
+
+#[test]
+fn test_presets() {
+    assert_eq!(Preset::Fast.rounds(), 1);
+    assert_eq!(Preset::Balanced.rounds(), 4);
+    assert_eq!(Preset::Paranoid.rounds(), 12);
+    assert_eq!(Cryptor::builder().preset(Preset::Fast).build().unwrap(), Cryptor::new());
+    assert_eq!(Cryptor::builder().preset(Preset::Paranoid).build().unwrap().rounds(), 12);
+}
+
+#[test]
+fn test_rounds_roundtrip() {
+    let key = "rounds key";
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        for rounds in [1, 2, 4, 12, MAX_ROUNDS] {
+            let cryptor = rounds_cryptor(rounds, chaining);
+            for len in [0, 1, 21, 22, 100, 1000] {
+                let data: Vec<u8> = (0..len).map(|i| (i * 13) as u8).collect();
+                let encrypted = cryptor.encrypt(&data, key).unwrap();
+                assert_eq!(cryptor.decrypt(&encrypted, key).unwrap(), data, "{chaining:?}, {rounds} rounds, length {len}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rounds_recorded_in_header() {
+    let cryptor = rounds_cryptor(4, Chaining::Chained);
+    let encrypted = cryptor.encrypt(b"four rounds", "key").unwrap();
+    assert!(encrypted.starts_with(b"CRO"));
+    assert_eq!(Cryptor::new().decrypt(&encrypted, "key").unwrap(), b"four rounds");
+
+    let counter = rounds_cryptor(4, Chaining::Counter);
+    let encrypted = counter.encrypt(b"0123456789", "key").unwrap();
+    assert_eq!(Cryptor::new().decrypt_range(&encrypted, "key", 2..5).unwrap(), b"234");
+}
+
+#[test]
+fn test_rounds_change_ciphertext() {
+    let one = Cryptor::builder().rounds(1).format(FormatVersion::V2).build().unwrap();
+    let four = rounds_cryptor(4, Chaining::Chained);
+    let data = vec![0u8; 256];
+
+    // Both bodies hold 266 bytes padded to 288 plus the 2-byte seed
+    let a = one.encrypt(&data, "key").unwrap();
+    let b = four.encrypt(&data, "key").unwrap();
+    assert_ne!(a[a.len() - 290..], b[b.len() - 290..]);
+}
+
+#[test]
+fn test_rounds_seekable() {
+    let cryptor = rounds_cryptor(3, Chaining::Chained);
+    let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&cryptor, Vec::new(), "key", 100).unwrap();
+    writer.write_all(&data).unwrap();
+    let container = writer.finish().unwrap();
+
+    let mut plain = Vec::new();
+    SeekableDecryptReader::new(Cursor::new(container), "key").unwrap().read_to_end(&mut plain).unwrap();
+    assert_eq!(plain, data);
+}
+
+#[test]
+fn test_rounds_validation() {
+    assert_eq!(Cryptor::builder().rounds(0).build(), Err(ConfigError::Rounds(0)));
+    assert_eq!(Cryptor::builder().rounds(MAX_ROUNDS + 1).build(), Err(ConfigError::Rounds(MAX_ROUNDS + 1)));
+    assert!(matches!(
+        Cryptor::builder().rounds(2).format(FormatVersion::V1).build(),
+        Err(ConfigError::FormatTooOld { .. })
+    ));
+}
@@ -0,0 +1,102 @@
+use crypt_ro::{Chaining, Cryptor, FormatVersion, SBox};
+
+fn assert_bijection(key: &[u8]) {
+    let sbox = SBox::new(key);
+    let mut seen = [false; 256];
+    for byte in 0..=255u8 {
+        let substituted = sbox.forward(byte);
+        assert!(!seen[substituted as usize], "key {key:?} maps two bytes to {substituted}");
+        seen[substituted as usize] = true;
+        assert_eq!(sbox.inverse(substituted), byte, "key {key:?}");
+    }
+}
+
+#[test]
+fn test_sbox_is_bijection_for_short_keys() {
+    assert_bijection(&[]);
+    for a in 0..=255u8 {
+        assert_bijection(&[a]);
+        for b in 0..=255u8 {
+            assert_bijection(&[a, b]);
+        }
+    }
+}
+
+#[test]
+fn test_sbox_is_bijection_for_expanded_keys() {
+    let mut state = 0x1234_5678_9ABC_DEF0u64;
+    for len in [16, 32, 64, 4096] {
+        for _ in 0..64 {
+            let key: Vec<u8> = (0..len)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    (state >> 56) as u8
+                })
+                .collect();
+            assert_bijection(&key);
+        }
+    }
+    assert_bijection(&[0; 32]);
+    assert_bijection(&[0xFF; 32]);
+}
+
+#[test]
+fn test_sbox_depends_on_key() {
+    assert_ne!(SBox::new(b"key one"), SBox::new(b"key two"));
+    assert_eq!(SBox::new(b"same key"), SBox::new(b"same key"));
+
+    let sbox = SBox::new(b"not the identity");
+    assert!((0..=255u8).any(|b| sbox.forward(b) != b));
+}
+
+#[test]
+fn test_sbox_substitute_invert() {
+    let sbox = SBox::new(b"block key");
+    let original: Vec<u8> = (0..=255u8).rev().collect();
+    let mut data = original.clone();
+    sbox.substitute(&mut data);
+    assert_ne!(data, original);
+    sbox.invert(&mut data);
+    assert_eq!(data, original);
+}
+
+#[test]
+fn test_v3_roundtrip() {
+    let key = "substitution key";
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        for rounds in [1, 4] {
+            let cryptor = Cryptor::builder()
+                .format(FormatVersion::V3)
+                .chaining(chaining)
+                .rounds(rounds)
+                .build()
+                .unwrap();
+            for len in [0, 1, 22, 100, 1000] {
+                let data: Vec<u8> = (0..len).map(|i| (i * 31) as u8).collect();
+                let encrypted = cryptor.encrypt(&data, key).unwrap();
+                assert_eq!(encrypted[3], 3);
+                assert_eq!(Cryptor::new().decrypt(&encrypted, key).unwrap(), data, "{chaining:?}, {rounds} rounds, length {len}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_v3_differs_from_v2() {
+    let data = vec![0u8; 64];
+    let v2 = Cryptor::builder().format(FormatVersion::V2).build().unwrap();
+    let v3 = Cryptor::builder().format(FormatVersion::V3).build().unwrap();
+
+    let a = v2.encrypt(&data, "key").unwrap();
+    let b = v3.encrypt(&data, "key").unwrap();
+    assert_ne!(a[9..], b[9..]);
+    assert!(v3.decrypt(&b, "wrong key").map_or(true, |d| d != data));
+}
+
+#[test]
+fn test_unknown_version_rejected() {
+    let cryptor = Cryptor::builder().format(FormatVersion::V3).build().unwrap();
+    let mut encrypted = cryptor.encrypt(b"data", "key").unwrap();
+    encrypted[3] = 0xEE;
+    assert!(cryptor.decrypt(&encrypted, "key").is_err());
+}
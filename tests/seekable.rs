@@ -0,0 +1,113 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use crypt_ro::{Cryptor, SeekableDecryptReader, SeekableEncryptWriter};
+
+fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+fn encrypt_container(data: &[u8], key: &str, chunk_size: usize) -> Vec<u8> {
+    let mut writer = SeekableEncryptWriter::with_chunk_size(&Cryptor::new(), Vec::new(), key, chunk_size).unwrap();
+    // Write in uneven pieces to exercise chunk buffering
+    for piece in data.chunks(37) {
+        writer.write_all(piece).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_seekable_roundtrip() {
+    for len in [0, 1, 99, 100, 101, 1000] {
+        let data = pseudo_random_bytes(len, len as u64);
+        let container = encrypt_container(&data, "key", 100);
+
+        let mut reader = SeekableDecryptReader::new(Cursor::new(container), "key").unwrap();
+        assert_eq!(reader.len(), len as u64);
+        let mut plain = Vec::new();
+        reader.read_to_end(&mut plain).unwrap();
+        assert_eq!(plain, data, "length {len}");
+    }
+}
+
+#[test]
+fn test_seekable_range_reads() {
+    let data = pseudo_random_bytes(1000, 1);
+    let container = encrypt_container(&data, "key", 64);
+    let mut reader = SeekableDecryptReader::new(Cursor::new(container), "key").unwrap();
+
+    for (start, len) in [(0, 10), (60, 10), (64, 64), (500, 300), (990, 10), (999, 1)] {
+        reader.seek(SeekFrom::Start(start)).unwrap();
+        let mut buf = vec![0u8; len];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, &data[start as usize..start as usize + len]);
+    }
+
+    assert_eq!(reader.seek(SeekFrom::End(-5)).unwrap(), 995);
+    let mut tail = Vec::new();
+    reader.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, &data[995..]);
+
+    reader.seek(SeekFrom::Start(100)).unwrap();
+    assert_eq!(reader.seek(SeekFrom::Current(-40)).unwrap(), 60);
+    assert!(reader.seek(SeekFrom::Current(-100)).is_err());
+
+    reader.seek(SeekFrom::Start(5000)).unwrap();
+    assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+}
+
+#[test]
+fn test_cryptor_decrypt_accepts_containers() {
+    let data = pseudo_random_bytes(300, 2);
+    let container = encrypt_container(&data, "key", 128);
+    assert_eq!(Cryptor::new().decrypt(&container, "key").unwrap(), data);
+}
+
+#[test]
+fn test_seekable_wrong_key_fails() {
+    let container = encrypt_container(b"secret media", "right key", 64);
+    assert!(SeekableDecryptReader::new(Cursor::new(container), "wrong key").is_err());
+}
+
+#[test]
+fn test_seekable_detects_tampering() {
+    let data = pseudo_random_bytes(500, 3);
+    let container = encrypt_container(&data, "key", 100);
+
+    let mut tampered = container.clone();
+    tampered[40] ^= 1;
+    let mut reader = SeekableDecryptReader::new(Cursor::new(tampered), "key").unwrap();
+    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+
+    let mut tampered = container.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(SeekableDecryptReader::new(Cursor::new(tampered), "key").is_err());
+}
+
+#[test]
+fn test_seekable_detects_truncation() {
+    let data = pseudo_random_bytes(400, 4);
+    let container = encrypt_container(&data, "key", 100);
+    let stored_chunk = (container.len() - 21) / 4;
+
+    // Dropping whole trailing chunks leaves a chunk that was not marked final
+    let truncated = container[..container.len() - stored_chunk].to_vec();
+    assert!(SeekableDecryptReader::new(Cursor::new(truncated), "key").is_err());
+
+    let truncated = container[..container.len() - 3].to_vec();
+    assert!(SeekableDecryptReader::new(Cursor::new(truncated), "key").is_err());
+}
+
+#[test]
+fn test_seekable_rejects_forged_chunk_size() {
+    let data = pseudo_random_bytes(400, 5);
+    let mut container = encrypt_container(&data, "key", 100);
+    // header (9) | chunk size (4): the reader must not trust it for allocations
+    container[9..13].copy_from_slice(&(u32::MAX - 10).to_be_bytes());
+    assert!(SeekableDecryptReader::new(Cursor::new(container), "key").is_err());
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+use crypt_ro::encrypted::{self, Encrypted};
+use crypt_ro::{Chaining, ConfigError, Cryptor, Encoding, Kdf, Key, PaddingPolicy, Pipeline, Stage};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    id: u32,
+    #[serde(with = "encrypted::string")]
+    ssn: String,
+    #[serde(with = "encrypted::bytes")]
+    avatar: Vec<u8>,
+    scores: Encrypted<BTreeMap<String, u32>>,
+    note: Option<Encrypted<String>>,
+}
+
+fn record() -> Record {
+    Record {
+        id: 7,
+        ssn: "123-45-6789".into(),
+        avatar: vec![0, 1, 2, 255],
+        scores: Encrypted(BTreeMap::from([("math".to_string(), 90)])),
+        note: Some(Encrypted("private".to_string())),
+    }
+}
+
+#[test]
+fn test_fields_round_trip() {
+    let cryptor = Cryptor::builder().chaining(Chaining::Counter).build().unwrap();
+    let key = Key::new("field key");
+    let json = encrypted::with_key(&cryptor, &key, || serde_json::to_string(&record())).unwrap();
+    for plain in ["123-45-6789", "math", "private"] {
+        assert!(!json.contains(plain), "{plain} in {json}");
+    }
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["id"], 7);
+
+    // Fields are ordinary tokens
+    let ssn = value["ssn"].as_str().unwrap();
+    assert_eq!(cryptor.decrypt_text(ssn, &key).unwrap(), "123-45-6789");
+
+    let back: Record = encrypted::with_key(&cryptor, &key, || serde_json::from_str(&json)).unwrap();
+    assert_eq!(back, record());
+}
+
+#[test]
+fn test_missing_or_wrong_key() {
+    let cryptor = Cryptor::new();
+    let err = serde_json::to_string(&record()).unwrap_err();
+    assert!(err.to_string().contains("No Encryption Key Set"), "{err}");
+
+    let json = encrypted::with_key(&cryptor, "key", || serde_json::to_string(&record())).unwrap();
+    let result: Result<Record, _> = encrypted::with_key(&cryptor, "other", || serde_json::from_str(&json));
+    assert!(result.is_err());
+    // The context does not outlive the call
+    assert!(serde_json::from_str::<Record>(&json).is_err());
+}
+
+#[test]
+fn test_nested_contexts() {
+    let outer = encrypted::with_key(&Cryptor::new(), "outer", || {
+        let inner = encrypted::with_key(&Cryptor::new(), "inner", || serde_json::to_string(&Encrypted(1u8)).unwrap());
+        (inner, serde_json::to_string(&Encrypted(2u8)).unwrap())
+    });
+    let read = |json: &str, key: &str| {
+        encrypted::with_key(&Cryptor::new(), key, || serde_json::from_str::<Encrypted<u8>>(json)).map(Encrypted::into_inner)
+    };
+    assert_eq!(read(&outer.0, "inner").unwrap(), 1);
+    assert_eq!(read(&outer.1, "outer").unwrap(), 2);
+}
+
+#[test]
+fn test_cryptor_config_round_trip() {
+    let cryptor = Cryptor::builder()
+        .matrix(64)
+        .kdf(Kdf::Pbkdf2 { iterations: 20_000 })
+        .padding(PaddingPolicy::Bucket(256))
+        .pipeline(Pipeline::new(vec![Stage::UniformShuffle { step: 1 }, Stage::Mix]))
+        .encoding(Encoding::Hex)
+        .key_id("2024")
+        .build()
+        .unwrap();
+    let json = serde_json::to_string(&cryptor).unwrap();
+    assert!(json.contains(r#""kdf":{"pbkdf2":{"iterations":20000}}"#), "{json}");
+    let back: Cryptor = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, cryptor);
+}
+
+#[test]
+fn test_cryptor_config_is_validated() {
+    let cryptor: Cryptor = serde_json::from_str(r#"{"chaining":"counter","rounds":3}"#).unwrap();
+    assert_eq!(cryptor.chaining(), Chaining::Counter);
+    assert_eq!(cryptor.rounds(), 3);
+    assert_eq!(cryptor.matrix(), 32);
+
+    let err = serde_json::from_str::<Cryptor>(r#"{"matrix":3}"#).unwrap_err();
+    assert!(err.to_string().contains(&ConfigError::MatrixSize(3).to_string()), "{err}");
+    assert!(serde_json::from_str::<Cryptor>(r#"{"matrx":64}"#).is_err());
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use crypt_ro::{Chaining, Cryptor, FormatVersion, KeyContext, Pipeline, Stage, Transform};
+
+const SAMPLES_PER_PERMUTATION: usize = 2000;
+
+fn factorial(n: usize) -> usize {
+    (1..=n).product()
+}
+
+/// Counts how often every permutation of `0..len` is produced by `stage`.
+fn permutation_counts(stage: Stage, len: usize, samples: usize) -> HashMap<Vec<u8>, usize> {
+    let key = [0u8; 16];
+    let mut counts = HashMap::new();
+    for seed in 0..samples as u64 {
+        let ctx = KeyContext::new(&key, 16, seed);
+        let mut data: Vec<u8> = (0..len as u8).collect();
+        stage.forward(&mut data, &ctx);
+        *counts.entry(data).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Pearson's chi-squared statistic against the uniform distribution.
+fn chi_squared(counts: &HashMap<Vec<u8>, usize>, categories: usize, samples: usize) -> f64 {
+    let expected = samples as f64 / categories as f64;
+    let observed: f64 = counts.values().map(|&c| (c as f64 - expected).powi(2) / expected).sum();
+    // Permutations never produced contribute their full expected count
+    observed + (categories - counts.len()) as f64 * expected
+}
+
+#[test]
+fn test_uniform_shuffle_hits_every_permutation_evenly() {
+    // 99.9% quantiles of the chi-squared distribution for 2!-1, 3!-1, 4!-1 and 5!-1
+    // degrees of freedom
+    for (len, critical) in [(2, 10.83), (3, 20.52), (4, 49.73), (5, 173.6)] {
+        let categories = factorial(len);
+        let samples = categories * SAMPLES_PER_PERMUTATION;
+        let counts = permutation_counts(Stage::UniformShuffle { step: 1 }, len, samples);
+
+        assert_eq!(counts.len(), categories, "length {len} misses permutations");
+        let statistic = chi_squared(&counts, categories, samples);
+        assert!(statistic < critical, "length {len}: chi-squared {statistic} >= {critical}");
+    }
+}
+
+#[test]
+fn test_legacy_shuffle_is_not_uniform() {
+    // The floating point shuffle never swaps an element with itself, so only cyclic
+    // permutations appear and the identity is unreachable
+    let len = 4;
+    let counts = permutation_counts(Stage::Shuffle { step: 1 }, len, factorial(len) * 200);
+    assert!(counts.len() < factorial(len));
+    assert!(!counts.contains_key(&vec![0, 1, 2, 3]));
+}
+
+#[test]
+fn test_uniform_stages_are_invertible() {
+    let key: Vec<u8> = (0..32).collect();
+    let ctx = KeyContext::new(&key, 32, 99);
+    let original: Vec<u8> = (0..=255).collect();
+    for stage in [
+        Stage::UniformShuffle { step: 1 },
+        Stage::UniformShuffle { step: 5 },
+        Stage::UniformBlockShuffle { step: 2 },
+    ] {
+        let mut data = original.clone();
+        stage.forward(&mut data, &ctx);
+        assert_ne!(data, original);
+        stage.inverse(&mut data, &ctx);
+        assert_eq!(data, original, "{stage}");
+    }
+}
+
+#[test]
+fn test_v4_uses_uniform_shuffles() {
+    assert_eq!(
+        Pipeline::standard(FormatVersion::V4).to_string(),
+        "uniform-shuffle:5,uniform-block-shuffle:2,substitute,mix"
+    );
+}
+
+#[test]
+fn test_v4_roundtrip() {
+    let key = "integer shuffle key";
+    for chaining in [Chaining::Chained, Chaining::Counter] {
+        let cryptor = Cryptor::builder().format(FormatVersion::V4).chaining(chaining).rounds(2).build().unwrap();
+        for len in [0, 1, 22, 100, 1000] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 17) as u8).collect();
+            let encrypted = cryptor.encrypt(&data, key).unwrap();
+            assert_eq!(encrypted[3], 4);
+            assert_eq!(Cryptor::new().decrypt(&encrypted, key).unwrap(), data, "{chaining:?}, length {len}");
+        }
+    }
+}